@@ -0,0 +1,2 @@
+DELETE FROM permissions WHERE name = 'resources:manage';
+DROP TABLE IF EXISTS resources CASCADE;
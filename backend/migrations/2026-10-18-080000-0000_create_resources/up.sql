@@ -0,0 +1,21 @@
+CREATE TABLE resources
+(
+    id          UUID PRIMARY KEY             DEFAULT gen_random_uuid(),
+    name        VARCHAR(100) UNIQUE NOT NULL,
+    description TEXT,
+    created_at  TIMESTAMPTZ         NOT NULL DEFAULT now(),
+    updated_at  TIMESTAMPTZ         NOT NULL DEFAULT now(),
+    -- Soft Delete
+    deleted_at  TIMESTAMPTZ                  DEFAULT NULL
+);
+
+SELECT diesel_manage_updated_at('resources');
+
+INSERT INTO permissions (name)
+VALUES ('resources:manage') ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO roles_permissions (role_id, permission_id)
+SELECT r.id, p.id
+FROM roles r
+         JOIN permissions p ON p.name = 'resources:manage'
+WHERE r.name IN ('owner', 'mod') ON CONFLICT DO NOTHING;
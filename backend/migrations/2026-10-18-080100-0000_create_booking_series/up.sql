@@ -0,0 +1,24 @@
+CREATE TABLE booking_series
+(
+    id               UUID PRIMARY KEY        DEFAULT gen_random_uuid(),
+    user_id          UUID          NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    resource_id      UUID          NOT NULL REFERENCES resources (id) ON DELETE CASCADE,
+    title            VARCHAR(255)  NOT NULL,
+    description      TEXT,
+
+    -- Recurrence (RFC 5545)
+    rrule            TEXT          NOT NULL,
+    dtstart          TIMESTAMPTZ   NOT NULL,
+    duration_minutes INTEGER       NOT NULL CHECK (duration_minutes > 0),
+    exdates          TIMESTAMPTZ[] NOT NULL DEFAULT '{}',
+    -- IANA zone the rule is expanded in, so occurrences keep their local
+    -- time across daylight saving changes
+    timezone         VARCHAR(64)   NOT NULL DEFAULT 'UTC',
+
+    created_at       TIMESTAMPTZ   NOT NULL DEFAULT now(),
+    updated_at       TIMESTAMPTZ   NOT NULL DEFAULT now(),
+    -- Soft Delete
+    deleted_at       TIMESTAMPTZ            DEFAULT NULL
+);
+
+SELECT diesel_manage_updated_at('booking_series');
@@ -0,0 +1,11 @@
+DROP TRIGGER IF EXISTS set_updated_at ON bookings;
+DROP INDEX IF EXISTS bookings_series_occurrence_idx;
+DROP INDEX IF EXISTS bookings_resource_time_idx;
+
+ALTER TABLE bookings
+    DROP CONSTRAINT IF EXISTS bookings_end_after_start,
+    DROP COLUMN IF EXISTS is_override,
+    DROP COLUMN IF EXISTS recurrence_id,
+    DROP COLUMN IF EXISTS series_id,
+    DROP COLUMN IF EXISTS end_date,
+    DROP COLUMN IF EXISTS resource_id;
@@ -0,0 +1,20 @@
+ALTER TABLE bookings
+    ADD COLUMN resource_id   UUID REFERENCES resources (id) ON DELETE CASCADE,
+    ADD COLUMN end_date      TIMESTAMPTZ,
+    -- Recurrence: the series this booking was generated from and the
+    -- occurrence start it stands for (RECURRENCE-ID)
+    ADD COLUMN series_id     UUID REFERENCES booking_series (id) ON DELETE CASCADE,
+    ADD COLUMN recurrence_id TIMESTAMPTZ,
+    ADD COLUMN is_override   BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE bookings
+SET end_date = booking_date + INTERVAL '1 hour';
+
+ALTER TABLE bookings
+    ALTER COLUMN end_date SET NOT NULL,
+    ADD CONSTRAINT bookings_end_after_start CHECK (end_date > booking_date);
+
+CREATE INDEX bookings_resource_time_idx ON bookings (resource_id, booking_date, end_date) WHERE deleted_at IS NULL;
+CREATE UNIQUE INDEX bookings_series_occurrence_idx ON bookings (series_id, recurrence_id) WHERE deleted_at IS NULL;
+
+SELECT diesel_manage_updated_at('bookings');
@@ -0,0 +1,14 @@
+CREATE TABLE calendar_feeds
+(
+    id           UUID PRIMARY KEY     DEFAULT gen_random_uuid(),
+    user_id      UUID        NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- NULL: the user's own bookings, otherwise every booking on the resource
+    resource_id  UUID REFERENCES resources (id) ON DELETE CASCADE,
+
+    -- SHA-256 of the feed token, the token itself is only shown once
+    token_hash   VARCHAR(64) UNIQUE NOT NULL,
+
+    last_used_at TIMESTAMPTZ          DEFAULT NULL,
+    revoked_at   TIMESTAMPTZ          DEFAULT NULL,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,16 @@
+-- Time on a resource that is unavailable without being a booking, e.g.
+-- events imported from an external calendar
+CREATE TABLE busy_blocks
+(
+    id          UUID PRIMARY KEY     DEFAULT gen_random_uuid(),
+    user_id     UUID        NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    resource_id UUID        NOT NULL REFERENCES resources (id) ON DELETE CASCADE,
+    summary     VARCHAR(255),
+    starts_at   TIMESTAMPTZ NOT NULL,
+    ends_at     TIMESTAMPTZ NOT NULL CHECK (ends_at > starts_at),
+    -- UID of the imported VEVENT
+    source_uid  TEXT,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX busy_blocks_resource_time_idx ON busy_blocks (resource_id, starts_at, ends_at);
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS booking_attendees;
+DROP TYPE IF EXISTS attendee_status;
+ALTER TABLE resources DROP COLUMN IF EXISTS capacity;
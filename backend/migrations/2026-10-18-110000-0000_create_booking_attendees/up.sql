@@ -0,0 +1,29 @@
+-- Maximum number of people (organiser included) on one booking; NULL means unlimited
+ALTER TABLE resources
+    ADD COLUMN capacity INTEGER CHECK (capacity IS NULL OR capacity > 0);
+
+DO
+$$
+BEGIN
+  IF
+NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'attendee_status') THEN
+CREATE TYPE attendee_status AS ENUM ('invited', 'accepted', 'declined');
+END IF;
+END
+$$;
+
+CREATE TABLE booking_attendees
+(
+    booking_id   UUID            NOT NULL REFERENCES bookings (id) ON DELETE CASCADE,
+    user_id      UUID            NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    status       attendee_status NOT NULL DEFAULT 'invited',
+    invited_by   UUID                     REFERENCES users (id) ON DELETE SET NULL,
+    responded_at TIMESTAMPTZ,
+    created_at   TIMESTAMPTZ     NOT NULL DEFAULT now(),
+    updated_at   TIMESTAMPTZ     NOT NULL DEFAULT now(),
+    PRIMARY KEY (booking_id, user_id)
+);
+
+CREATE INDEX booking_attendees_user_idx ON booking_attendees (user_id);
+
+SELECT diesel_manage_updated_at('booking_attendees');
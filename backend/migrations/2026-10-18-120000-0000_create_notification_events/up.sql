@@ -0,0 +1,12 @@
+-- Things that happened to a user, for delivery by email/push or polling
+CREATE TABLE notification_events
+(
+    id         UUID PRIMARY KEY     DEFAULT gen_random_uuid(),
+    user_id    UUID        NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    kind       VARCHAR(64) NOT NULL,
+    payload    JSONB       NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    read_at    TIMESTAMPTZ          DEFAULT NULL
+);
+
+CREATE INDEX notification_events_user_idx ON notification_events (user_id, created_at DESC);
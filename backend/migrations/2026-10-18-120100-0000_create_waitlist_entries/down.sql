@@ -0,0 +1,3 @@
+DELETE FROM permissions WHERE name = 'waitlist:prioritize';
+DROP TABLE IF EXISTS waitlist_entries;
+DROP TYPE IF EXISTS waitlist_status;
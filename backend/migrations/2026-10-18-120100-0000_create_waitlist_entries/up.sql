@@ -0,0 +1,41 @@
+DO
+$$
+BEGIN
+  IF
+NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'waitlist_status') THEN
+CREATE TYPE waitlist_status AS ENUM ('waiting', 'offered', 'accepted', 'declined', 'expired', 'cancelled');
+END IF;
+END
+$$;
+
+CREATE TABLE waitlist_entries
+(
+    id               UUID PRIMARY KEY         DEFAULT gen_random_uuid(),
+    user_id          UUID            NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    resource_id      UUID            NOT NULL REFERENCES resources (id) ON DELETE CASCADE,
+    title            VARCHAR(255)    NOT NULL,
+    description      TEXT,
+    starts_at        TIMESTAMPTZ     NOT NULL,
+    ends_at          TIMESTAMPTZ     NOT NULL CHECK (ends_at > starts_at),
+    -- Higher goes first; equal priorities are served in join order
+    priority         INTEGER         NOT NULL DEFAULT 0,
+    status           waitlist_status NOT NULL DEFAULT 'waiting',
+    -- Pending booking holding the slot while an offer is open
+    booking_id       UUID                     REFERENCES bookings (id) ON DELETE SET NULL,
+    offer_expires_at TIMESTAMPTZ,
+    created_at       TIMESTAMPTZ     NOT NULL DEFAULT now(),
+    updated_at       TIMESTAMPTZ     NOT NULL DEFAULT now()
+);
+
+CREATE INDEX waitlist_entries_queue_idx ON waitlist_entries (resource_id, status, priority DESC, created_at);
+
+SELECT diesel_manage_updated_at('waitlist_entries');
+
+INSERT INTO permissions (name)
+VALUES ('waitlist:prioritize') ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO roles_permissions (role_id, permission_id)
+SELECT r.id, p.id
+FROM roles r
+         JOIN permissions p ON p.name = 'waitlist:prioritize'
+WHERE r.name IN ('owner', 'mod') ON CONFLICT DO NOTHING;
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS bookings_hold_expiry_idx;
+ALTER TABLE bookings DROP COLUMN IF EXISTS hold_expires_at;
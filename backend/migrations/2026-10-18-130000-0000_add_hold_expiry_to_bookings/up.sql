@@ -0,0 +1,7 @@
+-- Set on pending bookings created as tentative holds; the reaper cancels
+-- them once this passes
+ALTER TABLE bookings
+    ADD COLUMN hold_expires_at TIMESTAMPTZ DEFAULT NULL;
+
+CREATE INDEX bookings_hold_expiry_idx ON bookings (hold_expires_at)
+    WHERE status = 'pending' AND hold_expires_at IS NOT NULL;
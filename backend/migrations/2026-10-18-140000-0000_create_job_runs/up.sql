@@ -0,0 +1,14 @@
+-- One row per scheduled job execution that acquired its lock
+CREATE TABLE job_runs
+(
+    id          UUID PRIMARY KEY      DEFAULT gen_random_uuid(),
+    job_name    VARCHAR(100) NOT NULL,
+    started_at  TIMESTAMPTZ  NOT NULL,
+    finished_at TIMESTAMPTZ  NOT NULL,
+    succeeded   BOOLEAN      NOT NULL,
+    -- Rows the job changed
+    affected    INTEGER      NOT NULL DEFAULT 0,
+    error       TEXT
+);
+
+CREATE INDEX job_runs_job_idx ON job_runs (job_name, started_at DESC);
@@ -0,0 +1,2 @@
+ALTER TABLE resources DROP COLUMN IF EXISTS checkin_enabled;
+ALTER TABLE bookings DROP COLUMN IF EXISTS checked_in_at;
@@ -0,0 +1,6 @@
+-- Bookings on resources with check-in become no-shows unless checked in
+ALTER TABLE bookings
+    ADD COLUMN checked_in_at TIMESTAMPTZ DEFAULT NULL;
+
+ALTER TABLE resources
+    ADD COLUMN checkin_enabled BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+DELETE FROM permissions WHERE name = 'bookings:checkin';
+ALTER TABLE bookings DROP COLUMN IF EXISTS checked_out_at;
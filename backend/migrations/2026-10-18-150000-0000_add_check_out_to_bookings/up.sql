@@ -0,0 +1,12 @@
+ALTER TABLE bookings
+    ADD COLUMN checked_out_at TIMESTAMPTZ DEFAULT NULL;
+
+-- Kiosk and front desk accounts check people in on their behalf
+INSERT INTO permissions (name)
+VALUES ('bookings:checkin') ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO roles_permissions (role_id, permission_id)
+SELECT r.id, p.id
+FROM roles r
+         JOIN permissions p ON p.name = 'bookings:checkin'
+WHERE r.name IN ('owner', 'mod') ON CONFLICT DO NOTHING;
@@ -0,0 +1,7 @@
+DROP TABLE IF EXISTS delay_request_approvals;
+DROP TABLE IF EXISTS delay_requests;
+DROP TYPE IF EXISTS approval_status;
+ALTER TABLE resources DROP COLUMN IF EXISTS cascade_delays;
+ALTER TABLE bookings
+    DROP COLUMN IF EXISTS original_booking_date,
+    DROP COLUMN IF EXISTS original_end_date;
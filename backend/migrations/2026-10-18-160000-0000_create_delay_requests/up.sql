@@ -0,0 +1,46 @@
+-- Times before the first delay, kept for reporting
+ALTER TABLE bookings
+    ADD COLUMN original_booking_date TIMESTAMPTZ DEFAULT NULL,
+    ADD COLUMN original_end_date     TIMESTAMPTZ DEFAULT NULL;
+
+-- Whether a delay may push the following bookings back, with their owners' approval
+ALTER TABLE resources
+    ADD COLUMN cascade_delays BOOLEAN NOT NULL DEFAULT FALSE;
+
+DO
+$$
+BEGIN
+  IF
+NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'approval_status') THEN
+CREATE TYPE approval_status AS ENUM ('pending', 'approved', 'rejected');
+END IF;
+END
+$$;
+
+-- A delay that also moves other people's bookings and waits for their approval
+CREATE TABLE delay_requests
+(
+    id           UUID PRIMARY KEY         DEFAULT gen_random_uuid(),
+    booking_id   UUID            NOT NULL REFERENCES bookings (id) ON DELETE CASCADE,
+    requested_by UUID            NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    minutes      INTEGER         NOT NULL CHECK (minutes > 0),
+    status       approval_status NOT NULL DEFAULT 'pending',
+    created_at   TIMESTAMPTZ     NOT NULL DEFAULT now(),
+    updated_at   TIMESTAMPTZ     NOT NULL DEFAULT now(),
+    resolved_at  TIMESTAMPTZ              DEFAULT NULL
+);
+
+SELECT diesel_manage_updated_at('delay_requests');
+
+-- One row per following booking the delay would move
+CREATE TABLE delay_request_approvals
+(
+    delay_request_id UUID            NOT NULL REFERENCES delay_requests (id) ON DELETE CASCADE,
+    booking_id       UUID            NOT NULL REFERENCES bookings (id) ON DELETE CASCADE,
+    owner_id         UUID            NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    status           approval_status NOT NULL DEFAULT 'pending',
+    responded_at     TIMESTAMPTZ              DEFAULT NULL,
+    PRIMARY KEY (delay_request_id, booking_id)
+);
+
+CREATE INDEX delay_request_approvals_owner_idx ON delay_request_approvals (owner_id, status);
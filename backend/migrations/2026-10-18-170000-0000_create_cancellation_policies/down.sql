@@ -0,0 +1,9 @@
+DROP TABLE IF EXISTS booking_cancellations;
+ALTER TABLE bookings
+    DROP COLUMN IF EXISTS cancelled_at,
+    DROP COLUMN IF EXISTS cancelled_by,
+    DROP COLUMN IF EXISTS cancellation_reason,
+    DROP COLUMN IF EXISTS cancellation_note,
+    DROP COLUMN IF EXISTS late_cancellation;
+DROP TABLE IF EXISTS cancellation_policies;
+DROP TYPE IF EXISTS cancellation_reason;
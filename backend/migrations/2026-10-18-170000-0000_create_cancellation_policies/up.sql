@@ -0,0 +1,46 @@
+DO
+$$
+BEGIN
+  IF
+NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'cancellation_reason') THEN
+CREATE TYPE cancellation_reason AS ENUM ('schedule_change', 'no_longer_needed', 'illness', 'weather', 'duplicate', 'other');
+END IF;
+END
+$$;
+
+-- Per-resource rules for cancelling; resources without a policy can be cancelled freely
+CREATE TABLE cancellation_policies
+(
+    resource_id         UUID PRIMARY KEY REFERENCES resources (id) ON DELETE CASCADE,
+    free_cancel_minutes INTEGER     NOT NULL DEFAULT 0 CHECK (free_cancel_minutes >= 0),
+    allow_late_cancel   BOOLEAN     NOT NULL DEFAULT TRUE,
+    staff_can_override  BOOLEAN     NOT NULL DEFAULT TRUE,
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at          TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+SELECT diesel_manage_updated_at('cancellation_policies');
+
+ALTER TABLE bookings
+    ADD COLUMN cancelled_at        TIMESTAMPTZ         DEFAULT NULL,
+    ADD COLUMN cancelled_by        UUID                DEFAULT NULL REFERENCES users (id) ON DELETE SET NULL,
+    ADD COLUMN cancellation_reason cancellation_reason DEFAULT NULL,
+    ADD COLUMN cancellation_note   TEXT                DEFAULT NULL,
+    ADD COLUMN late_cancellation   BOOLEAN NOT NULL    DEFAULT FALSE;
+
+-- Every cancellation, kept when the booking is reinstated or purged so the
+-- counts per user stay complete
+CREATE TABLE booking_cancellations
+(
+    id           UUID PRIMARY KEY             DEFAULT gen_random_uuid(),
+    booking_id   UUID                         DEFAULT NULL REFERENCES bookings (id) ON DELETE SET NULL,
+    -- Owner of the booking when it was cancelled
+    user_id      UUID                NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    cancelled_by UUID                         DEFAULT NULL REFERENCES users (id) ON DELETE SET NULL,
+    cancelled_at TIMESTAMPTZ         NOT NULL DEFAULT now(),
+    reason       cancellation_reason NOT NULL,
+    note         TEXT                         DEFAULT NULL,
+    late         BOOLEAN             NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX booking_cancellations_user_idx ON booking_cancellations (user_id, cancelled_at);
@@ -0,0 +1,4 @@
+DELETE FROM permissions WHERE name = 'booking_rules:manage';
+DROP TABLE IF EXISTS booking_rules;
+DROP TYPE IF EXISTS booking_rule_kind;
+ALTER TABLE resources DROP COLUMN IF EXISTS resource_type;
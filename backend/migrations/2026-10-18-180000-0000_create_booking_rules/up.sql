@@ -0,0 +1,41 @@
+ALTER TABLE resources
+    ADD COLUMN resource_type VARCHAR(50) DEFAULT NULL;
+
+DO
+$$
+BEGIN
+  IF
+NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'booking_rule_kind') THEN
+CREATE TYPE booking_rule_kind AS ENUM ('min_lead_minutes', 'max_lead_days', 'max_duration_minutes', 'max_active_per_week');
+END IF;
+END
+$$;
+
+-- A rule applies to one resource, to every resource of a type, or, with
+-- neither set, to every resource
+CREATE TABLE booking_rules
+(
+    id            UUID PRIMARY KEY           DEFAULT gen_random_uuid(),
+    resource_id   UUID REFERENCES resources (id) ON DELETE CASCADE,
+    resource_type VARCHAR(50),
+    kind          booking_rule_kind NOT NULL,
+    value         INTEGER           NOT NULL CHECK (value >= 0),
+    bypass_roles  TEXT[]            NOT NULL DEFAULT '{}',
+    created_at    TIMESTAMPTZ       NOT NULL DEFAULT now(),
+    updated_at    TIMESTAMPTZ       NOT NULL DEFAULT now(),
+    CHECK (resource_id IS NULL OR resource_type IS NULL)
+);
+
+CREATE INDEX booking_rules_resource_idx ON booking_rules (resource_id);
+CREATE INDEX booking_rules_resource_type_idx ON booking_rules (resource_type);
+
+SELECT diesel_manage_updated_at('booking_rules');
+
+INSERT INTO permissions (name)
+VALUES ('booking_rules:manage') ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO roles_permissions (role_id, permission_id)
+SELECT r.id, p.id
+FROM roles r
+         JOIN permissions p ON p.name = 'booking_rules:manage'
+WHERE r.name = 'owner' ON CONFLICT DO NOTHING;
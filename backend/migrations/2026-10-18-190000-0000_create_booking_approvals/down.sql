@@ -0,0 +1,5 @@
+-- Postgres cannot drop an enum value; 'withdrawn' stays in approval_status
+-- until the type itself is dropped.
+DELETE FROM permissions WHERE name IN ('bookings:approve', 'bookings:approve_escalated');
+DROP TABLE IF EXISTS booking_approvals;
+ALTER TABLE resources DROP COLUMN IF EXISTS requires_approval;
@@ -0,0 +1,37 @@
+-- New bookings on these resources wait for a manager's sign-off
+ALTER TABLE resources
+    ADD COLUMN requires_approval BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Closes a request whose booking was cancelled or deleted before anyone
+-- decided; not used by delay requests
+ALTER TYPE approval_status ADD VALUE IF NOT EXISTS 'withdrawn';
+
+CREATE TABLE booking_approvals
+(
+    booking_id   UUID PRIMARY KEY REFERENCES bookings (id) ON DELETE CASCADE,
+    status       approval_status NOT NULL DEFAULT 'pending',
+    requested_at TIMESTAMPTZ     NOT NULL DEFAULT now(),
+    decided_by   UUID                     DEFAULT NULL REFERENCES users (id) ON DELETE SET NULL,
+    decided_at   TIMESTAMPTZ              DEFAULT NULL,
+    comment      TEXT                     DEFAULT NULL,
+    escalated_at TIMESTAMPTZ              DEFAULT NULL
+);
+
+CREATE INDEX booking_approvals_pending_idx ON booking_approvals (requested_at) WHERE status = 'pending';
+
+INSERT INTO permissions (name)
+VALUES ('bookings:approve'),
+       ('bookings:approve_escalated') ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO roles_permissions (role_id, permission_id)
+SELECT r.id, p.id
+FROM roles r
+         JOIN permissions p ON p.name = 'bookings:approve'
+WHERE r.name IN ('owner', 'mod') ON CONFLICT DO NOTHING;
+
+-- Requests nobody handled in time go to the owners
+INSERT INTO roles_permissions (role_id, permission_id)
+SELECT r.id, p.id
+FROM roles r
+         JOIN permissions p ON p.name = 'bookings:approve_escalated'
+WHERE r.name = 'owner' ON CONFLICT DO NOTHING;
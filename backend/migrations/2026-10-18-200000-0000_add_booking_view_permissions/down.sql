@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS bookings_user_date_idx;
+DELETE FROM permissions WHERE name IN ('bookings:view_all', 'bookings:view_deleted');
@@ -0,0 +1,18 @@
+-- Seeing other people's bookings in GET /bookings, and soft-deleted ones
+INSERT INTO permissions (name)
+VALUES ('bookings:view_all'),
+       ('bookings:view_deleted') ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO roles_permissions (role_id, permission_id)
+SELECT r.id, p.id
+FROM roles r
+         JOIN permissions p ON p.name = 'bookings:view_all'
+WHERE r.name IN ('owner', 'mod', 'staff') ON CONFLICT DO NOTHING;
+
+INSERT INTO roles_permissions (role_id, permission_id)
+SELECT r.id, p.id
+FROM roles r
+         JOIN permissions p ON p.name = 'bookings:view_deleted'
+WHERE r.name IN ('owner', 'mod') ON CONFLICT DO NOTHING;
+
+CREATE INDEX IF NOT EXISTS bookings_user_date_idx ON bookings (user_id, booking_date, id);
@@ -0,0 +1,9 @@
+-- Listing users through GET /users
+INSERT INTO permissions (name)
+VALUES ('users:view') ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO roles_permissions (role_id, permission_id)
+SELECT r.id, p.id
+FROM roles r
+         JOIN permissions p ON p.name = 'users:view'
+WHERE r.name IN ('owner', 'mod') ON CONFLICT DO NOTHING;
@@ -0,0 +1,2 @@
+-- The seeded owner is not restored; it could never sign in.
+SELECT 1;
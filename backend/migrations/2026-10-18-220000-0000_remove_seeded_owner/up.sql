@@ -0,0 +1,9 @@
+-- The owner seeded by create_users never had a real password hash, so nobody
+-- could sign in as it. The first owner is now created with
+-- `backend admin bootstrap`. An account that has since been given a real
+-- password is left alone.
+DELETE
+FROM users
+WHERE username = 'owner'
+  AND email = 'owner@example.com'
+  AND password_hash NOT LIKE '$argon2%';
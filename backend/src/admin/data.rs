@@ -0,0 +1,128 @@
+//! Export and import of the application data as one JSON document:
+//!
+//! ```json
+//! { "version": 1, "exported_at": "...", "tables": { "users": [ ... ], ... } }
+//! ```
+//!
+//! Rows are converted by Postgres itself (`json_agg` and
+//! `json_populate_recordset`), so the document follows the schema of the
+//! database it came from. Import only adds rows: anything clashing with an
+//! existing key is skipped.
+
+use anyhow::{anyhow, bail};
+use chrono::Utc;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+const VERSION: u64 = 1;
+
+/// Every exported table, each after the tables it references.
+const TABLES: &[&str] = &[
+    "roles",
+    "permissions",
+    "roles_permissions",
+    "users",
+    "users_roles",
+    "resources",
+    "cancellation_policies",
+    "booking_rules",
+    "booking_series",
+    "bookings",
+    "booking_attendees",
+    "booking_cancellations",
+    "booking_approvals",
+    "delay_requests",
+    "delay_request_approvals",
+    "busy_blocks",
+    "calendar_feeds",
+    "waitlist_entries",
+    "notification_events",
+];
+
+/// Tables whose `id` comes from a sequence that must move past imported ids.
+const SERIAL_TABLES: &[&str] = &["roles", "permissions"];
+
+#[derive(QueryableByName)]
+struct JsonRows {
+    #[diesel(sql_type = Text)]
+    rows: String,
+}
+
+#[derive(Serialize)]
+pub struct TableCount {
+    pub table: &'static str,
+    pub rows: usize,
+}
+
+/// Reads every table from one consistent snapshot.
+pub fn export(conn: &mut PgConnection) -> anyhow::Result<Value> {
+    let tables = conn
+        .build_transaction()
+        .repeatable_read()
+        .read_only()
+        .run(|conn| {
+            let mut tables = Map::new();
+            for table in TABLES {
+                let found: JsonRows = diesel::sql_query(format!(
+                    "SELECT coalesce(json_agg(t), '[]')::text AS rows FROM {} t",
+                    table
+                ))
+                .get_result(conn)?;
+                let rows: Value = serde_json::from_str(&found.rows)?;
+                tables.insert(table.to_string(), rows);
+            }
+            Ok::<_, anyhow::Error>(tables)
+        })?;
+
+    Ok(json!({
+        "version": VERSION,
+        "exported_at": Utc::now(),
+        "tables": tables,
+    }))
+}
+
+/// Inserts the rows of an exported document in one transaction and reports
+/// how many rows of each table were new.
+pub fn import(conn: &mut PgConnection, document: &Value) -> anyhow::Result<Vec<TableCount>> {
+    if document["version"].as_u64() != Some(VERSION) {
+        bail!("Unsupported export version; expected {}", VERSION);
+    }
+    let tables = document["tables"]
+        .as_object()
+        .ok_or_else(|| anyhow!("Export has no \"tables\" object"))?;
+    if let Some(unknown) = tables.keys().find(|name| !TABLES.contains(&name.as_str())) {
+        bail!("Export contains unknown table {:?}", unknown);
+    }
+
+    conn.transaction(|conn| {
+        let mut counts = Vec::new();
+        for table in TABLES {
+            let Some(rows) = tables.get(*table) else {
+                continue;
+            };
+            let inserted = diesel::sql_query(format!(
+                "INSERT INTO {t} SELECT * FROM json_populate_recordset(NULL::{t}, $1::json) \
+                 ON CONFLICT DO NOTHING",
+                t = table
+            ))
+            .bind::<Text, _>(rows.to_string())
+            .execute(conn)?;
+            counts.push(TableCount {
+                table,
+                rows: inserted,
+            });
+        }
+        for table in SERIAL_TABLES {
+            diesel::sql_query(format!(
+                "SELECT setval(pg_get_serial_sequence('{t}', 'id'), \
+                 coalesce((SELECT max(id) FROM {t}), 1))",
+                t = table
+            ))
+            .execute(conn)?;
+        }
+        Ok(counts)
+    })
+}
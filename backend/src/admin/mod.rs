@@ -0,0 +1,440 @@
+//! `backend admin ...`: operational commands run against the configured
+//! database without going through the HTTP API.
+//!
+//! ```text
+//! backend admin bootstrap --username NAME --email EMAIL --first-name FIRST --last-name LAST
+//! backend admin create-user --username NAME --email EMAIL --first-name FIRST --last-name LAST [--role ROLE]
+//! backend admin users [--q TEXT] [--role ROLE] [--active BOOL] [--locked BOOL] [--deleted BOOL]
+//!                     [--limit N] [--cursor CURSOR]
+//! backend admin reset-password --user USER
+//! backend admin assign-role --user USER --role ROLE
+//! backend admin remove-role --user USER --role ROLE
+//! backend admin lock --user USER [--minutes N | --until RFC3339]
+//! backend admin unlock --user USER
+//! backend admin revoke-sessions --user USER
+//! backend admin purge [--older-than-days N]
+//! backend admin export [--file PATH]
+//! backend admin import [--file PATH]
+//! ```
+//!
+//! `USER` is an id, username or email. Passwords are read from the first line
+//! of standard input, so they never appear in the process list or shell
+//! history. Every command except `export` takes `--format table|json`.
+
+mod data;
+mod output;
+
+use crate::config::Config;
+use crate::errors::ApiError;
+use crate::models::{NewUser, User, UserBasic};
+use crate::users::{hashing, service, CreateUserRequest, ListUsersQuery};
+
+use crate::{bookings, resources, validation};
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use diesel::pg::PgConnection;
+use diesel::Connection;
+use output::Format;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{BufRead, IsTerminal, Read, Write};
+use std::str::FromStr;
+
+const COMMANDS: &str = "bootstrap, create-user, users, reset-password, assign-role, remove-role, \
+lock, unlock, revoke-sessions, purge, export or import";
+
+/// Columns shown for accounts in table output.
+const ACCOUNT_COLUMNS: &[&str] = &[
+    "id",
+    "username",
+    "email",
+    "roles",
+    "is_active",
+    "locked_until",
+    "deleted_at",
+];
+
+/// Soft-deleted rows younger than this are kept by `purge`.
+const DEFAULT_PURGE_DAYS: i64 = 30;
+
+pub fn command(config: &Config, args: &[String]) -> anyhow::Result<()> {
+    let Some((name, rest)) = args.split_first() else {
+        bail!("Missing admin command; expected {}", COMMANDS);
+    };
+    let options = Options::parse(rest)?;
+    let format = Format::parse(options.get("format"))?;
+    let mut conn = PgConnection::establish(&config.database.url)
+        .map_err(|e| anyhow!("Cannot connect to the database: {}", e))?;
+    let conn = &mut conn;
+
+    match name.as_str() {
+        "bootstrap" => bootstrap(conn, &options, config),
+        "create-user" => create_user(conn, &options, format, config),
+        "users" => list_users(conn, &options, format),
+        "reset-password" => reset_password(conn, &options, format, config),
+        "assign-role" | "remove-role" => {
+            let user = find_user(conn, &options)?;
+            let role = options.required("role")?;
+            let changed = if name == "assign-role" {
+                service::assign_role(conn, user.id, &role)
+            } else {
+                service::remove_role(conn, user.id, &role)
+            }
+            .map_err(describe)?;
+            if !changed {
+                eprintln!(
+                    "Unchanged: {} {} role {}",
+                    user.username,
+                    if name == "assign-role" {
+                        "already has"
+                    } else {
+                        "does not have"
+                    },
+                    role
+                );
+            }
+            print_account(conn, format, &user)
+        }
+        "lock" => {
+            let user = find_user(conn, &options)?;
+            let until = lock_until(&options)?;
+            let user = service::set_locked_until(conn, user.id, Some(until)).map_err(describe)?;
+            print_account(conn, format, &user)
+        }
+        "unlock" => {
+            let user = find_user(conn, &options)?;
+            let user = service::set_locked_until(conn, user.id, None).map_err(describe)?;
+            print_account(conn, format, &user)
+        }
+        "revoke-sessions" => {
+            let user = find_user(conn, &options)?;
+            let user = service::revoke_sessions(conn, user.id).map_err(describe)?;
+            print_account(conn, format, &user)
+        }
+        "purge" => purge(conn, &options, format),
+        "export" => export(conn, &options),
+        "import" => import(conn, &options, format),
+        other => bail!("Unknown admin command {:?}; expected {}", other, COMMANDS),
+    }
+}
+
+/// Creates the first owner account of a new installation.
+fn bootstrap(conn: &mut PgConnection, options: &Options, config: &Config) -> anyhow::Result<()> {
+    let mut request = CreateUserRequest {
+        username: options.required("username")?,
+        email: options.required("email")?,
+        first_name: options.required("first-name")?,
+        last_name: options.required("last-name")?,
+        password: read_password()?,
+    };
+    validation::validate(&mut request).map_err(describe)?;
+    service::ensure_password_allowed(
+        "password",
+        &request.password,
+        &[&request.username, &request.email],
+        &config.password,
+    )
+    .map_err(describe)?;
+
+    let password_hash = hashing::hash_password(&request.password, &config.hashing)
+        .map_err(|e| anyhow!("Password hashing failed: {}", e))?;
+    let user = service::bootstrap_owner(
+        conn,
+        NewUser {
+            first_name: request.first_name,
+            last_name: request.last_name,
+            username: request.username,
+            email: request.email,
+            password_hash,
+            token_version: 0,
+        },
+    )
+    .map_err(describe)?;
+
+    println!("Created owner {} ({})", user.username, user.id);
+    Ok(())
+}
+
+/// Creates an account, optionally with the role named by `--role`.
+fn create_user(
+    conn: &mut PgConnection,
+    options: &Options,
+    format: Format,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let mut request = CreateUserRequest {
+        username: options.required("username")?,
+        email: options.required("email")?,
+        first_name: options.required("first-name")?,
+        last_name: options.required("last-name")?,
+        password: read_password()?,
+    };
+    validation::validate(&mut request).map_err(describe)?;
+    service::ensure_password_allowed(
+        "password",
+        &request.password,
+        &[&request.username, &request.email],
+        &config.password,
+    )
+    .map_err(describe)?;
+
+    let password_hash = hashing::hash_password(&request.password, &config.hashing)
+        .map_err(|e| anyhow!("Password hashing failed: {}", e))?;
+    let role = options.get("role");
+    let user = conn.transaction(|conn| {
+        let user = service::insert_user(
+            conn,
+            NewUser {
+                first_name: request.first_name,
+                last_name: request.last_name,
+                username: request.username,
+                email: request.email,
+                password_hash,
+                token_version: 0,
+            },
+        )?;
+        if let Some(role) = role {
+            service::assign_role(conn, user.id, role)?;
+        }
+        Ok::<_, ApiError>(user)
+    });
+    print_account(conn, format, &user.map_err(describe)?)
+}
+
+fn list_users(conn: &mut PgConnection, options: &Options, format: Format) -> anyhow::Result<()> {
+    let query = ListUsersQuery {
+        q: options.get("q").map(str::to_string),
+        role: options.get("role").map(str::to_string),
+        active: options.value("active")?,
+        locked: options.value("locked")?,
+        deleted: options.value("deleted")?,
+        cursor: options.get("cursor").map(str::to_string),
+        limit: options.value("limit")?,
+    };
+    let page = service::list_users(conn, query).map_err(describe)?;
+    match format {
+        Format::Json => output::print_one(format, &[], &page),
+        Format::Table => {
+            output::print_rows(format, ACCOUNT_COLUMNS, &page.items)?;
+            if let Some(cursor) = page.next_cursor {
+                eprintln!("More users follow; pass --cursor {}", cursor);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Sets a new password read from standard input, ending every session.
+fn reset_password(
+    conn: &mut PgConnection,
+    options: &Options,
+    format: Format,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let user = find_user(conn, options)?;
+    let new_password = read_password()?;
+    service::ensure_password_allowed(
+        "password",
+        &new_password,
+        &[&user.username, &user.email],
+        &config.password,
+    )
+    .map_err(describe)?;
+
+    let new_hash = hashing::hash_password(&new_password, &config.hashing)
+        .map_err(|e| anyhow!("Password hashing failed: {}", e))?;
+    let user = service::set_password_hash(conn, user.id, new_hash).map_err(describe)?;
+    print_account(conn, format, &user)
+}
+
+/// `--until` as given, `--minutes` from now, or indefinitely.
+fn lock_until(options: &Options) -> anyhow::Result<DateTime<Utc>> {
+    if let Some(until) = options.get("until") {
+        return DateTime::parse_from_rfc3339(until)
+            .map(|t| t.with_timezone(&Utc))
+            .map_err(|e| anyhow!("--until must be an RFC 3339 timestamp: {}", e));
+    }
+    match options.value::<i64>("minutes")? {
+        Some(minutes) if minutes > 0 => Ok(Utc::now() + Duration::minutes(minutes)),
+        Some(_) => bail!("--minutes must be positive"),
+        None => Ok(NaiveDate::from_ymd_opt(9999, 12, 31)
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .expect("valid date")
+            .and_utc()),
+    }
+}
+
+#[derive(Serialize)]
+struct Purged {
+    kind: &'static str,
+    purged: usize,
+}
+
+/// Permanently deletes rows soft-deleted more than `--older-than-days` ago,
+/// dependants before the rows they belong to.
+fn purge(conn: &mut PgConnection, options: &Options, format: Format) -> anyhow::Result<()> {
+    let days = options
+        .value::<i64>("older-than-days")?
+        .unwrap_or(DEFAULT_PURGE_DAYS);
+    if days < 0 {
+        bail!("--older-than-days must not be negative");
+    }
+    let cutoff = Utc::now() - Duration::days(days);
+
+    let rows = conn
+        .transaction(|conn| {
+            Ok::<_, ApiError>(vec![
+                Purged {
+                    kind: "bookings",
+                    purged: bookings::service::purge_deleted_bookings(conn, cutoff)?,
+                },
+                Purged {
+                    kind: "series",
+                    purged: bookings::service::purge_deleted_series(conn, cutoff)?,
+                },
+                Purged {
+                    kind: "resources",
+                    purged: resources::service::purge_deleted_resources(conn, cutoff)?,
+                },
+                Purged {
+                    kind: "users",
+                    purged: service::purge_deleted_users(conn, cutoff)?,
+                },
+            ])
+        })
+        .map_err(describe)?;
+    output::print_rows(format, &["kind", "purged"], &rows)
+}
+
+/// Writes the export to `--file` or standard output.
+fn export(conn: &mut PgConnection, options: &Options) -> anyhow::Result<()> {
+    let document = data::export(conn)?;
+    let text = serde_json::to_string_pretty(&document)?;
+    eprintln!("The export contains password hashes; store it accordingly");
+    match options.get("file") {
+        Some(path) => {
+            std::fs::write(path, text + "\n").map_err(|e| anyhow!("Cannot write {}: {}", path, e))
+        }
+        None => {
+            println!("{}", text);
+            Ok(())
+        }
+    }
+}
+
+/// Reads an export from `--file` or standard input.
+fn import(conn: &mut PgConnection, options: &Options, format: Format) -> anyhow::Result<()> {
+    let text = match options.get("file") {
+        Some(path) => {
+            std::fs::read_to_string(path).map_err(|e| anyhow!("Cannot read {}: {}", path, e))?
+        }
+        None => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text)?;
+            text
+        }
+    };
+    let document = serde_json::from_str(&text).map_err(|e| anyhow!("Invalid export: {}", e))?;
+    let counts = data::import(conn, &document)?;
+    output::print_rows(format, &["table", "rows"], &counts)
+}
+
+/// The account named by `--user`.
+fn find_user(conn: &mut PgConnection, options: &Options) -> anyhow::Result<User> {
+    let key = options.required("user")?;
+    service::find_user(conn, &key).map_err(|e| match e {
+        ApiError::NotFound => anyhow!("No user with id, username or email {:?}", key),
+        other => describe(other),
+    })
+}
+
+fn print_account(conn: &mut PgConnection, format: Format, user: &User) -> anyhow::Result<()> {
+    let account = UserBasic {
+        id: user.id,
+        username: user.username.clone(),
+        email: user.email.clone(),
+        first_name: user.first_name.clone(),
+        last_name: user.last_name.clone(),
+        is_active: user.is_active,
+        locked_until: user.locked_until,
+        deleted_at: user.deleted_at,
+        roles: service::get_user_roles(conn, user.id)?,
+    };
+    output::print_one(format, ACCOUNT_COLUMNS, &account)
+}
+
+/// Reads a password from the first line of standard input, prompting when it
+/// is a terminal.
+fn read_password() -> anyhow::Result<String> {
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        eprint!("Password: ");
+        std::io::stderr().flush()?;
+    }
+    let mut line = String::new();
+    stdin.lock().read_line(&mut line)?;
+    let password = line.trim_end_matches(['\r', '\n']).to_string();
+    if password.is_empty() {
+        bail!("No password given on standard input");
+    }
+    Ok(password)
+}
+
+/// Spells out every rejected field, which the API reports in its body.
+fn describe(e: ApiError) -> anyhow::Error {
+    match e {
+        ApiError::Validation(errors) => anyhow!(
+            "{}",
+            errors
+                .iter()
+                .map(|e| format!("{}: {}", e.field, e.message))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+        ApiError::Internal(e) => e,
+        other => anyhow!("{}", other),
+    }
+}
+
+/// `--name value` options of a command.
+struct Options {
+    values: HashMap<String, String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> anyhow::Result<Options> {
+        let mut values = HashMap::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                bail!("Unexpected argument {:?}", arg);
+            };
+            let value = args
+                .next()
+                .ok_or_else(|| anyhow!("--{} needs a value", name))?;
+            values.insert(name.to_string(), value.clone());
+        }
+        Ok(Options { values })
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    fn required(&self, name: &str) -> anyhow::Result<String> {
+        self.values
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("--{} is required", name))
+    }
+
+    fn value<T: FromStr>(&self, name: &str) -> anyhow::Result<Option<T>> {
+        self.get(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| anyhow!("--{} has an invalid value {:?}", name, value))
+            })
+            .transpose()
+    }
+}
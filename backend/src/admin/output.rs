@@ -0,0 +1,91 @@
+//! Renders admin command results as an aligned table for people or as JSON
+//! for scripts (`--format json`).
+
+use anyhow::bail;
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Table,
+    Json,
+}
+
+impl Format {
+    pub fn parse(value: Option<&str>) -> anyhow::Result<Format> {
+        match value.unwrap_or("table") {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            other => bail!("Unknown format {:?}; expected table or json", other),
+        }
+    }
+}
+
+/// Prints `rows` as a JSON array, or as a table of the given columns.
+pub fn print_rows<T: Serialize>(
+    format: Format,
+    columns: &[&str],
+    rows: &[T],
+) -> anyhow::Result<()> {
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(rows)?),
+        Format::Table => {
+            let rows = rows
+                .iter()
+                .map(serde_json::to_value)
+                .collect::<Result<Vec<_>, _>>()?;
+            print_table(columns, &rows);
+        }
+    }
+    Ok(())
+}
+
+/// Prints one result as a JSON object, or as a one-row table.
+pub fn print_one<T: Serialize>(format: Format, columns: &[&str], row: &T) -> anyhow::Result<()> {
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(row)?),
+        Format::Table => print_table(columns, &[serde_json::to_value(row)?]),
+    }
+    Ok(())
+}
+
+fn print_table(columns: &[&str], rows: &[Value]) {
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| columns.iter().map(|c| cell(&row[*c])).collect())
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain([column.len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let line = |values: Vec<String>| {
+        let padded: Vec<String> = values
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{:<width$}", value, width = width))
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    line(columns.iter().map(|c| c.to_uppercase()).collect());
+    for row in cells {
+        line(row);
+    }
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => "-".into(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(cell).collect::<Vec<_>>().join(","),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,196 @@
+use crate::config::Config;
+use crate::errors::ApiError;
+use crate::models::BookingRuleKind;
+use crate::users::service::extract_bearer_token;
+use crate::validation::{nfc_trim, Validate, ValidatedJson, Validator};
+use crate::{services, DbPool};
+use actix_web::{delete, get, patch, post, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use uuid::Uuid;
+
+pub mod service;
+
+#[derive(Deserialize)]
+pub struct CreateBookingRuleRequest {
+    /// Set at most one of `resource_id` and `resource_type`; with neither the
+    /// rule applies to every resource.
+    pub resource_id: Option<Uuid>,
+    pub resource_type: Option<String>,
+    pub kind: BookingRuleKind,
+    pub value: i32,
+    /// Users with any of these roles are not held to the rule.
+    pub bypass_roles: Option<Vec<String>>,
+}
+
+impl Validate for CreateBookingRuleRequest {
+    fn normalize(&mut self) {
+        if let Some(ref mut kind) = self.resource_type {
+            nfc_trim(kind);
+        }
+        for role in self.bypass_roles.iter_mut().flatten() {
+            nfc_trim(role);
+        }
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            "resource_type",
+            self.resource_id.is_none() || self.resource_type.is_none(),
+            "Set either resource_id or resource_type, not both",
+        );
+        v.optional("resource_type", self.resource_type.as_deref())
+            .length(1, 50);
+        v.check("value", self.value >= 0, "Value must not be negative");
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UpdateBookingRuleRequest {
+    pub value: Option<i32>,
+    pub bypass_roles: Option<Vec<String>>,
+}
+
+impl Validate for UpdateBookingRuleRequest {
+    fn normalize(&mut self) {
+        for role in self.bypass_roles.iter_mut().flatten() {
+            nfc_trim(role);
+        }
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            "value",
+            self.value.is_none_or(|value| value >= 0),
+            "Value must not be negative",
+        );
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BookingRulesQuery {
+    pub resource_id: Option<Uuid>,
+    pub resource_type: Option<String>,
+}
+
+#[post("/booking-rules")]
+pub async fn create_booking_rule_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    body: ValidatedJson<CreateBookingRuleRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::create_rule(&mut conn, &user, body.into_inner())
+    })
+    .await
+    {
+        Ok(Ok(rule)) => HttpResponse::Created().json(rule),
+        Ok(Err(e)) => e.into_response("Error creating booking rule"),
+        Err(e) => ApiError::from(e).into_response("Error creating booking rule"),
+    }
+}
+
+#[get("/booking-rules")]
+pub async fn get_booking_rules_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    query: web::Query<BookingRulesQuery>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        services::authenticate(&mut conn, &token, &secret)?;
+        service::get_rules(&mut conn, query.into_inner())
+    })
+    .await
+    {
+        Ok(Ok(rules)) => HttpResponse::Ok().json(rules),
+        Ok(Err(e)) => e.into_response("Error fetching booking rules"),
+        Err(e) => ApiError::from(e).into_response("Error fetching booking rules"),
+    }
+}
+
+#[patch("/booking-rules/{id}")]
+pub async fn update_booking_rule_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    body: ValidatedJson<UpdateBookingRuleRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let rule_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::update_rule(&mut conn, &user, rule_id, body.into_inner())
+    })
+    .await
+    {
+        Ok(Ok(rule)) => HttpResponse::Ok().json(rule),
+        Ok(Err(e)) => e.into_response("Error updating booking rule"),
+        Err(e) => ApiError::from(e).into_response("Error updating booking rule"),
+    }
+}
+
+#[delete("/booking-rules/{id}")]
+pub async fn delete_booking_rule_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let rule_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::delete_rule(&mut conn, &user, rule_id)
+    })
+    .await
+    {
+        Ok(Ok(())) => HttpResponse::Ok().json(serde_json::json!({"success": true})),
+        Ok(Err(e)) => e.into_response("Error deleting booking rule"),
+        Err(e) => ApiError::from(e).into_response("Error deleting booking rule"),
+    }
+}
@@ -0,0 +1,329 @@
+//! Booking rules: limits such as lead time, duration and weekly quotas,
+//! configured per resource, per resource type or for every resource.
+//!
+//! `enforce_rules` runs wherever bookings are created or moved, calendar
+//! imports included, and reports every rule broken rather than the first one.
+//! Delays are not checked; they shift bookings that already exist.
+
+use crate::booking_rules::{BookingRulesQuery, CreateBookingRuleRequest, UpdateBookingRuleRequest};
+use crate::bookings::service::Slot;
+use crate::errors::ApiError;
+use crate::models::{
+    BookingRule, BookingRuleKind, BookingStatus, NewBookingRule, UpdateBookingRuleChangeset, User,
+};
+use crate::users::service::{get_user_roles, has_permission};
+use chrono::{DateTime, Datelike, Duration, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+pub struct RuleViolation {
+    pub rule_id: Uuid,
+    pub kind: BookingRuleKind,
+    pub value: i32,
+    /// Start of the first booking (or occurrence) that breaks the rule.
+    pub starts_at: DateTime<Utc>,
+    pub message: String,
+}
+
+fn ensure_can_manage(conn: &mut PgConnection, user: &User) -> Result<(), ApiError> {
+    if has_permission(conn, user.id, "booking_rules:manage")? {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden)
+    }
+}
+
+/// Checks that every role name exists, so a typo cannot silently bypass nothing.
+fn validate_roles(conn: &mut PgConnection, names: &[String]) -> Result<(), ApiError> {
+    use crate::schema::roles::dsl::*;
+
+    let known = roles
+        .filter(name.eq_any(names))
+        .select(name)
+        .load::<String>(conn)?;
+    match names.iter().find(|n| !known.contains(n)) {
+        Some(unknown) => Err(ApiError::Invalid(format!("Unknown role: {}", unknown))),
+        None => Ok(()),
+    }
+}
+
+pub fn create_rule(
+    conn: &mut PgConnection,
+    user: &User,
+    data: CreateBookingRuleRequest,
+) -> Result<BookingRule, ApiError> {
+    use crate::schema::booking_rules::dsl::*;
+    use crate::schema::resources::dsl as r_dsl;
+
+    ensure_can_manage(conn, user)?;
+    if let Some(resource) = data.resource_id {
+        r_dsl::resources
+            .find(resource)
+            .filter(r_dsl::deleted_at.is_null())
+            .select(r_dsl::id)
+            .first::<Uuid>(conn)
+            .optional()?
+            .ok_or(ApiError::NotFound)?;
+    }
+    let roles = data.bypass_roles.unwrap_or_default();
+    validate_roles(conn, &roles)?;
+
+    let new_rule = NewBookingRule {
+        resource_id: data.resource_id,
+        resource_type: data.resource_type,
+        kind: data.kind,
+        value: data.value,
+        bypass_roles: roles.into_iter().map(Some).collect(),
+    };
+
+    Ok(diesel::insert_into(booking_rules)
+        .values(&new_rule)
+        .get_result::<BookingRule>(conn)?)
+}
+
+pub fn get_rules(
+    conn: &mut PgConnection,
+    query: BookingRulesQuery,
+) -> Result<Vec<BookingRule>, ApiError> {
+    use crate::schema::booking_rules::dsl::*;
+
+    let mut rules = booking_rules.into_boxed();
+    if let Some(resource) = query.resource_id {
+        rules = rules.filter(resource_id.eq(resource));
+    }
+    if let Some(kind_of_resource) = query.resource_type {
+        rules = rules.filter(resource_type.eq(kind_of_resource));
+    }
+
+    Ok(rules.order(created_at.asc()).load::<BookingRule>(conn)?)
+}
+
+pub fn update_rule(
+    conn: &mut PgConnection,
+    user: &User,
+    rule_uuid: Uuid,
+    data: UpdateBookingRuleRequest,
+) -> Result<BookingRule, ApiError> {
+    use crate::schema::booking_rules::dsl::*;
+
+    ensure_can_manage(conn, user)?;
+    if let Some(ref roles) = data.bypass_roles {
+        validate_roles(conn, roles)?;
+    }
+
+    let changes = UpdateBookingRuleChangeset {
+        value: data.value,
+        bypass_roles: data
+            .bypass_roles
+            .map(|roles| roles.into_iter().map(Some).collect()),
+    };
+    if changes.value.is_none() && changes.bypass_roles.is_none() {
+        return booking_rules
+            .find(rule_uuid)
+            .first::<BookingRule>(conn)
+            .optional()?
+            .ok_or(ApiError::NotFound);
+    }
+
+    diesel::update(booking_rules.find(rule_uuid))
+        .set(&changes)
+        .get_result::<BookingRule>(conn)
+        .optional()?
+        .ok_or(ApiError::NotFound)
+}
+
+pub fn delete_rule(conn: &mut PgConnection, user: &User, rule_uuid: Uuid) -> Result<(), ApiError> {
+    use crate::schema::booking_rules::dsl::*;
+
+    ensure_can_manage(conn, user)?;
+    let deleted = diesel::delete(booking_rules.find(rule_uuid)).execute(conn)?;
+    if deleted == 0 {
+        return Err(ApiError::NotFound);
+    }
+    Ok(())
+}
+
+/// Rules on the resource itself, on its type, and on every resource.
+fn applicable_rules(
+    conn: &mut PgConnection,
+    resource_uuid: Uuid,
+) -> Result<Vec<BookingRule>, ApiError> {
+    use crate::schema::booking_rules::dsl::*;
+    use crate::schema::resources::dsl as r_dsl;
+
+    let kind_of_resource = r_dsl::resources
+        .find(resource_uuid)
+        .select(r_dsl::resource_type)
+        .first::<Option<String>>(conn)
+        .optional()?
+        .ok_or(ApiError::NotFound)?;
+
+    let mut rules = booking_rules
+        .filter(resource_id.eq(resource_uuid))
+        .or_filter(resource_id.is_null().and(resource_type.is_null()))
+        .into_boxed();
+    if let Some(t) = kind_of_resource {
+        rules = rules.or_filter(resource_type.eq(t));
+    }
+
+    Ok(rules.order(created_at.asc()).load::<BookingRule>(conn)?)
+}
+
+fn week_start(at: DateTime<Utc>) -> DateTime<Utc> {
+    let monday = at.date_naive() - Duration::days(at.weekday().num_days_from_monday() as i64);
+    monday.and_time(chrono::NaiveTime::MIN).and_utc()
+}
+
+/// Returns the start of the first new slot that takes the owner over the
+/// weekly quota, counting active bookings within the rule's scope.
+fn over_weekly_quota(
+    conn: &mut PgConnection,
+    rule: &BookingRule,
+    owner: Uuid,
+    slots: &[Slot],
+    exclude: &[Uuid],
+) -> QueryResult<Option<DateTime<Utc>>> {
+    use crate::schema::bookings::dsl::*;
+    use crate::schema::resources::dsl as r_dsl;
+
+    let mut weeks: BTreeMap<DateTime<Utc>, (i64, DateTime<Utc>)> = BTreeMap::new();
+    for (starts_at, _) in slots {
+        let entry = weeks
+            .entry(week_start(*starts_at))
+            .or_insert((0, *starts_at));
+        entry.0 += 1;
+        entry.1 = entry.1.min(*starts_at);
+    }
+
+    for (week, (added, first)) in weeks {
+        let mut active = bookings
+            .filter(user_id.eq(owner))
+            .filter(deleted_at.is_null())
+            .filter(status.eq_any([
+                BookingStatus::Pending,
+                BookingStatus::Confirmed,
+                BookingStatus::Delayed,
+            ]))
+            .filter(booking_date.ge(week))
+            .filter(booking_date.lt(week + Duration::days(7)))
+            .filter(id.ne_all(exclude))
+            .into_boxed();
+        match (rule.resource_id, &rule.resource_type) {
+            (Some(resource), _) => active = active.filter(resource_id.eq(resource)),
+            (None, Some(kind_of_resource)) => {
+                active = active.filter(
+                    resource_id.eq_any(
+                        r_dsl::resources
+                            .filter(r_dsl::resource_type.eq(kind_of_resource.clone()))
+                            .select(r_dsl::id.nullable()),
+                    ),
+                )
+            }
+            (None, None) => {}
+        }
+
+        let existing = active.count().get_result::<i64>(conn)?;
+        if existing + added > rule.value as i64 {
+            return Ok(Some(first));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Checks `slots` on the resource, booked for `owner` by `actor`, against
+/// every applicable rule the actor's roles do not bypass. Bookings in
+/// `exclude` (the ones being replaced) do not count towards quotas.
+pub fn enforce_rules(
+    conn: &mut PgConnection,
+    actor: &User,
+    owner: Uuid,
+    resource_uuid: Uuid,
+    slots: &[Slot],
+    exclude: &[Uuid],
+) -> Result<(), ApiError> {
+    if slots.is_empty() {
+        return Ok(());
+    }
+
+    let rules = applicable_rules(conn, resource_uuid)?;
+    if rules.is_empty() {
+        return Ok(());
+    }
+    let roles = get_user_roles(conn, actor.id)?;
+    let now = Utc::now();
+
+    let mut violations = Vec::new();
+    for rule in rules {
+        if rule
+            .bypass_roles
+            .iter()
+            .flatten()
+            .any(|r| roles.contains(r))
+        {
+            continue;
+        }
+
+        let limit = rule.value as i64;
+        let broken = match rule.kind {
+            BookingRuleKind::MinLeadMinutes => slots
+                .iter()
+                .find(|(s, _)| *s - now < Duration::minutes(limit))
+                .map(|(s, _)| {
+                    (
+                        *s,
+                        format!(
+                            "Bookings must be made at least {} minutes in advance",
+                            limit
+                        ),
+                    )
+                }),
+            BookingRuleKind::MaxLeadDays => slots
+                .iter()
+                .find(|(s, _)| *s - now > Duration::days(limit))
+                .map(|(s, _)| {
+                    (
+                        *s,
+                        format!("Bookings can be made at most {} days in advance", limit),
+                    )
+                }),
+            BookingRuleKind::MaxDurationMinutes => slots
+                .iter()
+                .find(|(s, e)| *e - *s > Duration::minutes(limit))
+                .map(|(s, _)| {
+                    (
+                        *s,
+                        format!("Bookings must not be longer than {} minutes", limit),
+                    )
+                }),
+            BookingRuleKind::MaxActivePerWeek => {
+                over_weekly_quota(conn, &rule, owner, slots, exclude)?.map(|s| {
+                    (
+                        s,
+                        format!("At most {} active bookings per week are allowed", limit),
+                    )
+                })
+            }
+        };
+
+        if let Some((starts_at, message)) = broken {
+            violations.push(RuleViolation {
+                rule_id: rule.id,
+                kind: rule.kind,
+                value: rule.value,
+                starts_at,
+                message,
+            });
+        }
+    }
+
+    if violations.is_empty() {
+        return Ok(());
+    }
+    Err(ApiError::RuleViolations(
+        serde_json::to_value(violations).map_err(anyhow::Error::from)?,
+    ))
+}
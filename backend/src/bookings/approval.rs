@@ -0,0 +1,323 @@
+//! Approval of bookings on resources that need a manager's sign-off.
+//!
+//! New bookings on a resource with `requires_approval` start as `Pending`
+//! with a pending row in `booking_approvals`. Anyone with `bookings:approve`
+//! can approve them (to `Confirmed`) or reject them (to `Cancelled`), except
+//! their own. A rejection keeps its reason on the approval and leaves the
+//! booking's cancellation fields empty, so it is not counted as the owner
+//! cancelling.
+//! Requests nobody handles before the escalation deadline are flagged and
+//! sent to everyone with `bookings:approve_escalated`. A request is withdrawn
+//! when its booking is cancelled, deleted or regenerated before a decision.
+
+use crate::bookings::service::lock_booking;
+use crate::bookings::{ApprovalQueueQuery, ApproveBookingRequest, RejectBookingRequest};
+use crate::config::BookingsConfig;
+use crate::errors::ApiError;
+use crate::models::{
+    ApprovalStatus, Booking, BookingApproval, BookingStatus, NewBookingApproval, User,
+};
+use crate::notifications::service::notify;
+use crate::users::service::{has_permission, users_with_permission};
+use crate::waitlist::service::promote_waitlist;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+pub struct PendingApproval {
+    #[serde(flatten)]
+    pub approval: BookingApproval,
+    pub booking: Booking,
+}
+
+/// How long a request may wait before it is escalated.
+pub fn escalation_deadline(config: &BookingsConfig) -> Duration {
+    Duration::hours(config.approval_escalation_hours)
+}
+
+pub fn requires_approval(conn: &mut PgConnection, resource: Option<Uuid>) -> QueryResult<bool> {
+    use crate::schema::resources::dsl::*;
+
+    let Some(resource) = resource else {
+        return Ok(false);
+    };
+    resources
+        .find(resource)
+        .select(requires_approval)
+        .first::<bool>(conn)
+}
+
+fn ensure_can_approve(conn: &mut PgConnection, user: &User) -> Result<(), ApiError> {
+    if has_permission(conn, user.id, "bookings:approve")? {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden)
+    }
+}
+
+/// Whether the booking is waiting for a decision.
+pub fn awaiting_approval(conn: &mut PgConnection, booking_uuid: Uuid) -> QueryResult<bool> {
+    use crate::schema::booking_approvals::dsl::*;
+
+    Ok(booking_approvals
+        .find(booking_uuid)
+        .filter(status.eq(ApprovalStatus::Pending))
+        .count()
+        .get_result::<i64>(conn)?
+        > 0)
+}
+
+/// Puts the `Pending` bookings in the approval queue (again, for bookings
+/// that were decided before) and tells the approvers.
+pub fn request_approval(conn: &mut PgConnection, pending: &[Booking]) -> QueryResult<()> {
+    use crate::schema::booking_approvals::dsl::*;
+
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let rows: Vec<NewBookingApproval> = pending
+        .iter()
+        .map(|b| NewBookingApproval { booking_id: b.id })
+        .collect();
+    diesel::insert_into(booking_approvals)
+        .values(&rows)
+        .on_conflict(booking_id)
+        .do_update()
+        .set((
+            status.eq(ApprovalStatus::Pending),
+            requested_at.eq(Utc::now()),
+            decided_by.eq(None::<Uuid>),
+            decided_at.eq(None::<chrono::DateTime<Utc>>),
+            comment.eq(None::<String>),
+            escalated_at.eq(None::<chrono::DateTime<Utc>>),
+        ))
+        .execute(conn)?;
+
+    let first = &pending[0];
+    let payload = serde_json::json!({
+        "booking_ids": pending.iter().map(|b| b.id).collect::<Vec<_>>(),
+        "resource_id": first.resource_id,
+        "user_id": first.user_id,
+        "title": first.title,
+        "booking_date": first.booking_date,
+    });
+    for approver in users_with_permission(conn, "bookings:approve")? {
+        if approver != first.user_id {
+            notify(
+                conn,
+                approver,
+                "booking.approval_requested",
+                payload.clone(),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Closes the pending requests of bookings that are going away; `by` is the
+/// user responsible, if any.
+pub fn withdraw_approvals(
+    conn: &mut PgConnection,
+    booking_ids: &[Uuid],
+    by: Option<Uuid>,
+) -> QueryResult<usize> {
+    use crate::schema::booking_approvals::dsl::*;
+
+    diesel::update(
+        booking_approvals
+            .filter(booking_id.eq_any(booking_ids))
+            .filter(status.eq(ApprovalStatus::Pending)),
+    )
+    .set((
+        status.eq(ApprovalStatus::Withdrawn),
+        decided_by.eq(by),
+        decided_at.eq(Some(Utc::now())),
+    ))
+    .execute(conn)
+}
+
+/// Pending requests on other users' bookings, oldest first; escalated ones
+/// only with `escalated=true`.
+pub fn get_approval_queue(
+    conn: &mut PgConnection,
+    user: &User,
+    query: ApprovalQueueQuery,
+) -> Result<Vec<PendingApproval>, ApiError> {
+    use crate::schema::booking_approvals::dsl::*;
+    use crate::schema::bookings::dsl as b_dsl;
+
+    ensure_can_approve(conn, user)?;
+
+    let mut queue = booking_approvals
+        .inner_join(b_dsl::bookings)
+        .filter(status.eq(ApprovalStatus::Pending))
+        .filter(b_dsl::status.eq(BookingStatus::Pending))
+        .filter(b_dsl::deleted_at.is_null())
+        .filter(b_dsl::user_id.ne(user.id))
+        .into_boxed();
+    if let Some(resource) = query.resource_id {
+        queue = queue.filter(b_dsl::resource_id.eq(resource));
+    }
+    match query.escalated {
+        Some(true) => queue = queue.filter(escalated_at.is_not_null()),
+        Some(false) => queue = queue.filter(escalated_at.is_null()),
+        None => {}
+    }
+
+    Ok(queue
+        .order(requested_at.asc())
+        .load::<(BookingApproval, Booking)>(conn)?
+        .into_iter()
+        .map(|(approval, booking)| PendingApproval { approval, booking })
+        .collect())
+}
+
+fn decide(
+    conn: &mut PgConnection,
+    user: &User,
+    booking_uuid: Uuid,
+    outcome: ApprovalStatus,
+    text: Option<String>,
+    config: &BookingsConfig,
+) -> Result<Booking, ApiError> {
+    use crate::schema::booking_approvals::dsl as a_dsl;
+    use crate::schema::bookings::dsl::*;
+
+    ensure_can_approve(conn, user)?;
+
+    conn.transaction(|conn| {
+        let booking = lock_booking(conn, booking_uuid)?;
+        if booking.user_id == user.id {
+            return Err(ApiError::Forbidden);
+        }
+        let approval = a_dsl::booking_approvals
+            .find(booking.id)
+            .first::<BookingApproval>(conn)
+            .optional()?;
+        if approval.is_none_or(|a| a.status != ApprovalStatus::Pending)
+            || booking.status != BookingStatus::Pending
+        {
+            return Err(ApiError::Invalid("Booking is not awaiting approval".into()));
+        }
+
+        let now = Utc::now();
+        diesel::update(a_dsl::booking_approvals.find(booking.id))
+            .set((
+                a_dsl::status.eq(outcome),
+                a_dsl::decided_by.eq(Some(user.id)),
+                a_dsl::decided_at.eq(Some(now)),
+                a_dsl::comment.eq(&text),
+            ))
+            .execute(conn)?;
+
+        let decided = if outcome == ApprovalStatus::Approved {
+            diesel::update(bookings.find(booking.id))
+                .set(status.eq(BookingStatus::Confirmed))
+                .get_result::<Booking>(conn)?
+        } else {
+            let rejected = diesel::update(bookings.find(booking.id))
+                .set(status.eq(BookingStatus::Cancelled))
+                .get_result::<Booking>(conn)?;
+            if let Some(resource) = rejected.resource_id {
+                promote_waitlist(conn, resource, config)?;
+            }
+            rejected
+        };
+
+        notify(
+            conn,
+            decided.user_id,
+            if outcome == ApprovalStatus::Approved {
+                "booking.approved"
+            } else {
+                "booking.rejected"
+            },
+            serde_json::json!({
+                "booking_id": decided.id,
+                "title": decided.title,
+                "booking_date": decided.booking_date,
+                "comment": text,
+            }),
+        )?;
+
+        Ok(decided)
+    })
+}
+
+pub fn approve_booking(
+    conn: &mut PgConnection,
+    user: &User,
+    booking_uuid: Uuid,
+    data: ApproveBookingRequest,
+    config: &BookingsConfig,
+) -> Result<Booking, ApiError> {
+    decide(
+        conn,
+        user,
+        booking_uuid,
+        ApprovalStatus::Approved,
+        data.comment,
+        config,
+    )
+}
+
+pub fn reject_booking(
+    conn: &mut PgConnection,
+    user: &User,
+    booking_uuid: Uuid,
+    data: RejectBookingRequest,
+    config: &BookingsConfig,
+) -> Result<Booking, ApiError> {
+    decide(
+        conn,
+        user,
+        booking_uuid,
+        ApprovalStatus::Rejected,
+        Some(data.reason),
+        config,
+    )
+}
+
+/// Flags requests that have waited past the deadline and tells everyone with
+/// `bookings:approve_escalated`. Each request is escalated once.
+pub fn escalate_overdue_approvals(
+    conn: &mut PgConnection,
+    config: &BookingsConfig,
+) -> Result<usize, ApiError> {
+    use crate::schema::booking_approvals::dsl::*;
+    use crate::schema::bookings::dsl as b_dsl;
+
+    let now = Utc::now();
+    let waiting = b_dsl::bookings
+        .filter(b_dsl::status.eq(BookingStatus::Pending))
+        .filter(b_dsl::deleted_at.is_null())
+        .select(b_dsl::id);
+    let overdue = diesel::update(
+        booking_approvals
+            .filter(status.eq(ApprovalStatus::Pending))
+            .filter(escalated_at.is_null())
+            .filter(requested_at.le(now - escalation_deadline(config)))
+            .filter(booking_id.eq_any(waiting)),
+    )
+    .set(escalated_at.eq(Some(now)))
+    .returning(booking_id)
+    .get_results::<Uuid>(conn)?;
+
+    if !overdue.is_empty() {
+        let payload = serde_json::json!({ "booking_ids": overdue });
+        for recipient in users_with_permission(conn, "bookings:approve_escalated")? {
+            notify(
+                conn,
+                recipient,
+                "booking.approval_escalated",
+                payload.clone(),
+            )?;
+        }
+    }
+
+    Ok(overdue.len())
+}
@@ -0,0 +1,252 @@
+//! Cancelling bookings.
+//!
+//! Cancellations go through here rather than a status edit so that they carry
+//! a reason and are checked against the resource's cancellation policy.
+//! Cancelling after the policy's cut-off flags the booking as a late
+//! cancellation; staff can waive the cut-off where the policy allows it.
+//! Every cancellation is also recorded in `booking_cancellations`, which the
+//! per-user counts are taken from, so reinstating a booking does not erase
+//! it.
+
+use crate::bookings::approval::withdraw_approvals;
+use crate::bookings::service::{ensure_allowed, load_booking, load_series, notify_participants};
+use crate::bookings::{CancelBookingRequest, CancelSeriesRequest};
+use crate::config::BookingsConfig;
+use crate::errors::ApiError;
+use crate::models::{Booking, BookingStatus, CancellationPolicy, NewBookingCancellation, User};
+use crate::users::service::has_permission;
+use crate::waitlist::service::promote_waitlist;
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+pub struct CancellationStats {
+    pub user_id: Uuid,
+    pub since: Option<DateTime<Utc>>,
+    pub cancellations: i64,
+    pub late_cancellations: i64,
+    pub last_late_cancellation_at: Option<DateTime<Utc>>,
+}
+
+fn load_policy(
+    conn: &mut PgConnection,
+    resource: Option<Uuid>,
+) -> QueryResult<Option<CancellationPolicy>> {
+    use crate::schema::cancellation_policies::dsl::*;
+
+    let Some(resource) = resource else {
+        return Ok(None);
+    };
+    cancellation_policies
+        .find(resource)
+        .first::<CancellationPolicy>(conn)
+        .optional()
+}
+
+/// Returns whether cancelling now counts as late, or an error if the policy
+/// does not allow it.
+fn is_late(
+    conn: &mut PgConnection,
+    user: &User,
+    booking: &Booking,
+    policy: Option<&CancellationPolicy>,
+    override_policy: bool,
+) -> Result<bool, ApiError> {
+    let Some(policy) = policy else {
+        return Ok(false);
+    };
+    let cutoff = booking.booking_date - Duration::minutes(policy.free_cancel_minutes as i64);
+    if Utc::now() < cutoff {
+        return Ok(false);
+    }
+
+    if override_policy {
+        if !policy.staff_can_override {
+            return Err(ApiError::Invalid(
+                "The cancellation policy of this resource cannot be overridden".into(),
+            ));
+        }
+        if !has_permission(conn, user.id, "bookings:edit")? {
+            return Err(ApiError::Forbidden);
+        }
+        return Ok(false);
+    }
+
+    if !policy.allow_late_cancel {
+        return Err(ApiError::Invalid(format!(
+            "Bookings on this resource can only be cancelled until {}",
+            cutoff.to_rfc3339()
+        )));
+    }
+    Ok(true)
+}
+
+fn cancel_one(
+    conn: &mut PgConnection,
+    user: &User,
+    booking: Booking,
+    policy: Option<&CancellationPolicy>,
+    data: &CancelBookingRequest,
+) -> Result<Booking, ApiError> {
+    use crate::schema::booking_cancellations;
+    use crate::schema::bookings::dsl::*;
+
+    match booking.status {
+        BookingStatus::Cancelled => {
+            return Err(ApiError::Invalid("Booking is already cancelled".into()));
+        }
+        BookingStatus::Completed | BookingStatus::NoShow => {
+            return Err(ApiError::Invalid(
+                "Only upcoming bookings can be cancelled".into(),
+            ));
+        }
+        _ => {}
+    }
+
+    let late = is_late(
+        conn,
+        user,
+        &booking,
+        policy,
+        data.override_policy.unwrap_or(false),
+    )?;
+
+    let cancelled = diesel::update(bookings.find(booking.id))
+        .set((
+            status.eq(BookingStatus::Cancelled),
+            cancelled_at.eq(Some(Utc::now())),
+            cancelled_by.eq(Some(user.id)),
+            cancellation_reason.eq(Some(data.reason)),
+            cancellation_note.eq(&data.note),
+            late_cancellation.eq(late),
+        ))
+        .get_result::<Booking>(conn)?;
+    diesel::insert_into(booking_cancellations::table)
+        .values(&NewBookingCancellation {
+            booking_id: Some(cancelled.id),
+            user_id: cancelled.user_id,
+            cancelled_by: Some(user.id),
+            reason: data.reason,
+            note: data.note.clone(),
+            late,
+        })
+        .execute(conn)?;
+    withdraw_approvals(conn, &[cancelled.id], Some(user.id))?;
+
+    notify_participants(
+        conn,
+        user.id,
+        &cancelled,
+        "booking.cancelled",
+        serde_json::json!({
+            "booking_id": cancelled.id,
+            "booking_date": cancelled.booking_date,
+            "reason": cancelled.cancellation_reason,
+            "note": cancelled.cancellation_note,
+        }),
+    )?;
+
+    Ok(cancelled)
+}
+
+pub fn cancel_booking(
+    conn: &mut PgConnection,
+    user: &User,
+    booking_uuid: Uuid,
+    data: CancelBookingRequest,
+    config: &BookingsConfig,
+) -> Result<Booking, ApiError> {
+    conn.transaction(|conn| {
+        let booking = load_booking(conn, booking_uuid)?;
+        ensure_allowed(conn, user, booking.user_id, "bookings:edit")?;
+
+        let policy = load_policy(conn, booking.resource_id)?;
+        let cancelled = cancel_one(conn, user, booking, policy.as_ref(), &data)?;
+
+        if let Some(resource) = cancelled.resource_id {
+            promote_waitlist(conn, resource, config)?;
+        }
+        Ok(cancelled)
+    })
+}
+
+/// Cancels every upcoming occurrence of the series starting at or after
+/// `from` (default: now). Each one is checked against the policy on its own.
+pub fn cancel_series(
+    conn: &mut PgConnection,
+    user: &User,
+    series_uuid: Uuid,
+    data: CancelSeriesRequest,
+    config: &BookingsConfig,
+) -> Result<Vec<Booking>, ApiError> {
+    use crate::schema::bookings::dsl::*;
+
+    conn.transaction(|conn| {
+        let series = load_series(conn, series_uuid)?;
+        ensure_allowed(conn, user, series.user_id, "bookings:edit")?;
+
+        let occurrences = bookings
+            .filter(series_id.eq(series.id))
+            .filter(deleted_at.is_null())
+            .filter(booking_date.ge(data.from.unwrap_or_else(Utc::now)))
+            .filter(status.eq_any([
+                BookingStatus::Pending,
+                BookingStatus::Confirmed,
+                BookingStatus::Delayed,
+            ]))
+            .order(booking_date.asc())
+            .load::<Booking>(conn)?;
+
+        let policy = load_policy(conn, Some(series.resource_id))?;
+        let cancelled = occurrences
+            .into_iter()
+            .map(|b| cancel_one(conn, user, b, policy.as_ref(), &data.cancellation))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !cancelled.is_empty() {
+            promote_waitlist(conn, series.resource_id, config)?;
+        }
+        Ok(cancelled)
+    })
+}
+
+/// Cancellation counts for the user; only the user themself and staff with
+/// `bookings:edit` can see them.
+pub fn get_cancellation_stats(
+    conn: &mut PgConnection,
+    user: &User,
+    user_uuid: Uuid,
+    since: Option<DateTime<Utc>>,
+) -> Result<CancellationStats, ApiError> {
+    use crate::schema::booking_cancellations::dsl::*;
+    use diesel::dsl::count_star;
+
+    ensure_allowed(conn, user, user_uuid, "bookings:edit")?;
+
+    let mut all = booking_cancellations
+        .filter(user_id.eq(user_uuid))
+        .into_boxed();
+    let mut late_only = booking_cancellations
+        .filter(user_id.eq(user_uuid))
+        .filter(late.eq(true))
+        .into_boxed();
+    if let Some(since) = since {
+        all = all.filter(cancelled_at.ge(since));
+        late_only = late_only.filter(cancelled_at.ge(since));
+    }
+
+    let cancellations = all.count().get_result::<i64>(conn)?;
+    let (late_cancellations, last_late_cancellation_at) = late_only
+        .select((count_star(), diesel::dsl::max(cancelled_at)))
+        .first::<(i64, Option<DateTime<Utc>>)>(conn)?;
+
+    Ok(CancellationStats {
+        user_id: user_uuid,
+        since,
+        cancellations,
+        late_cancellations,
+        last_late_cancellation_at,
+    })
+}
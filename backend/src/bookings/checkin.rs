@@ -0,0 +1,232 @@
+//! Check-in and check-out of bookings on resources with check-in enabled.
+//!
+//! A booking can be checked in manually by its owner or by anyone with
+//! `bookings:checkin`, or by a kiosk scanning the short-lived code from
+//! `check_in_code`. The code is a JWT with its own audience, so it cannot be
+//! used as a session token and vice versa.
+
+use crate::bookings::service::no_show_grace;
+use crate::config::{BookingsConfig, Config};
+use crate::errors::ApiError;
+use crate::models::{Booking, BookingStatus, User};
+use crate::users::service::has_permission;
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const CHECKIN_AUDIENCE: &str = "booking-checkin";
+
+#[derive(Serialize, Deserialize)]
+struct CheckInClaims {
+    sub: Uuid,
+    aud: String,
+    exp: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckInCode {
+    pub booking_id: Uuid,
+    pub code: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Returns the `(opens, closes)` check-in window of a booking. It closes when
+/// the booking would become a no-show.
+pub fn check_in_window(
+    booking: &Booking,
+    config: &BookingsConfig,
+) -> (DateTime<Utc>, DateTime<Utc>) {
+    let before = Duration::minutes(config.checkin_opens_minutes_before);
+    (
+        booking.booking_date - before,
+        booking.booking_date + no_show_grace(config),
+    )
+}
+
+/// Loads the booking under a row lock and checks that its resource uses check-in.
+fn lock_checkin_booking(conn: &mut PgConnection, booking_uuid: Uuid) -> Result<Booking, ApiError> {
+    use crate::schema::bookings::dsl::*;
+    use crate::schema::resources::dsl as r_dsl;
+
+    let booking = bookings
+        .find(booking_uuid)
+        .filter(deleted_at.is_null())
+        .for_update()
+        .first::<Booking>(conn)
+        .optional()?
+        .ok_or(ApiError::NotFound)?;
+
+    let enabled = match booking.resource_id {
+        Some(resource) => r_dsl::resources
+            .find(resource)
+            .select(r_dsl::checkin_enabled)
+            .first::<bool>(conn)?,
+        None => false,
+    };
+    if !enabled {
+        return Err(ApiError::Invalid(
+            "Check-in is not enabled for this resource".into(),
+        ));
+    }
+
+    Ok(booking)
+}
+
+fn ensure_can_check_in(
+    conn: &mut PgConnection,
+    user: &User,
+    booking: &Booking,
+) -> Result<(), ApiError> {
+    if user.id == booking.user_id || has_permission(conn, user.id, "bookings:checkin")? {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden)
+    }
+}
+
+/// Issues a short-lived code for the booking owner to show at a kiosk.
+pub fn check_in_code(
+    conn: &mut PgConnection,
+    user: &User,
+    booking_uuid: Uuid,
+    config: &Config,
+) -> Result<CheckInCode, ApiError> {
+    let booking = conn.transaction(|conn| lock_checkin_booking(conn, booking_uuid))?;
+    if booking.user_id != user.id {
+        return Err(ApiError::Forbidden);
+    }
+
+    let expires_at = Utc::now() + Duration::seconds(config.bookings.checkin_code_ttl_secs);
+    let claims = CheckInClaims {
+        sub: booking.id,
+        aud: CHECKIN_AUDIENCE.into(),
+        exp: expires_at.timestamp(),
+    };
+    let code = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(config.auth.jwt_secret.as_ref()),
+    )
+    .map_err(anyhow::Error::from)?;
+
+    Ok(CheckInCode {
+        booking_id: booking.id,
+        code,
+        expires_at,
+    })
+}
+
+fn record_check_in(
+    conn: &mut PgConnection,
+    booking: Booking,
+    config: &Config,
+) -> Result<Booking, ApiError> {
+    use crate::schema::bookings::dsl::*;
+
+    if booking.checked_in_at.is_some() {
+        return Ok(booking);
+    }
+    if !matches!(
+        booking.status,
+        BookingStatus::Confirmed | BookingStatus::Delayed
+    ) {
+        return Err(ApiError::Invalid(
+            "Only confirmed bookings can be checked in".into(),
+        ));
+    }
+
+    let now = Utc::now();
+    let (opens, closes) = check_in_window(&booking, &config.bookings);
+    if now < opens {
+        return Err(ApiError::Invalid(format!(
+            "Check-in opens at {}",
+            opens.to_rfc3339()
+        )));
+    }
+    if now > closes {
+        return Err(ApiError::Invalid("The check-in window has closed".into()));
+    }
+
+    let new_status = if now > booking.booking_date && config.features.checkin_mark_late_as_delayed {
+        BookingStatus::Delayed
+    } else {
+        booking.status
+    };
+
+    Ok(diesel::update(bookings.find(booking.id))
+        .set((checked_in_at.eq(Some(now)), status.eq(new_status)))
+        .get_result::<Booking>(conn)?)
+}
+
+/// Checks a booking in. Checking in twice returns the booking unchanged.
+pub fn check_in(
+    conn: &mut PgConnection,
+    user: &User,
+    booking_uuid: Uuid,
+    config: &Config,
+) -> Result<Booking, ApiError> {
+    conn.transaction(|conn| {
+        let booking = lock_checkin_booking(conn, booking_uuid)?;
+        ensure_can_check_in(conn, user, &booking)?;
+        record_check_in(conn, booking, config)
+    })
+}
+
+/// Checks in the booking named by a scanned code. Only accounts with
+/// `bookings:checkin` (kiosks, front desk) may scan.
+pub fn check_in_with_code(
+    conn: &mut PgConnection,
+    user: &User,
+    code: &str,
+    config: &Config,
+) -> Result<Booking, ApiError> {
+    if !has_permission(conn, user.id, "bookings:checkin")? {
+        return Err(ApiError::Forbidden);
+    }
+
+    let mut validation = Validation::default();
+    validation.set_audience(&[CHECKIN_AUDIENCE]);
+    let claims = decode::<CheckInClaims>(
+        code,
+        &DecodingKey::from_secret(config.auth.jwt_secret.as_ref()),
+        &validation,
+    )
+    .map_err(|_| ApiError::Invalid("Invalid or expired check-in code".into()))?
+    .claims;
+
+    conn.transaction(|conn| {
+        let booking = lock_checkin_booking(conn, claims.sub)?;
+        record_check_in(conn, booking, config)
+    })
+}
+
+/// Checks a booking out, completing it. Checking out twice returns the
+/// booking unchanged.
+pub fn check_out(
+    conn: &mut PgConnection,
+    user: &User,
+    booking_uuid: Uuid,
+) -> Result<Booking, ApiError> {
+    use crate::schema::bookings::dsl::*;
+
+    conn.transaction(|conn| {
+        let booking = lock_checkin_booking(conn, booking_uuid)?;
+        ensure_can_check_in(conn, user, &booking)?;
+
+        if booking.checked_out_at.is_some() {
+            return Ok(booking);
+        }
+        if booking.checked_in_at.is_none() {
+            return Err(ApiError::Invalid("Booking has not been checked in".into()));
+        }
+
+        Ok(diesel::update(bookings.find(booking.id))
+            .set((
+                checked_out_at.eq(Some(Utc::now())),
+                status.eq(BookingStatus::Completed),
+            ))
+            .get_result::<Booking>(conn)?)
+    })
+}
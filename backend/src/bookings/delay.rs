@@ -0,0 +1,447 @@
+//! Delaying bookings.
+//!
+//! A delay shifts a booking back by a number of minutes and marks it
+//! `Delayed`, keeping the times it had before its first delay for reporting.
+//! When the new slot runs into the bookings that follow it on a resource with
+//! `cascade_delays`, the whole run of following bookings moves by the same
+//! amount, but only once every other owner in that run has approved the
+//! delay request.
+
+use crate::bookings::service::{
+    ensure_allowed, find_conflicts, load_booking, lock_resource, notify_participants,
+    BookingConflict, Slot,
+};
+use crate::bookings::DelayBookingRequest;
+use crate::config::BookingsConfig;
+use crate::errors::ApiError;
+use crate::models::{
+    ApprovalStatus, Booking, BookingStatus, DelayApproval, DelayRequest, NewDelayApproval,
+    NewDelayRequest, User,
+};
+use crate::notifications::service::notify;
+use crate::waitlist::service::promote_waitlist;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Longest single delay.
+pub const MAX_DELAY_MINUTES: i32 = 24 * 60;
+
+#[derive(Debug, Serialize)]
+pub struct DelayRequestDetails {
+    #[serde(flatten)]
+    pub request: DelayRequest,
+    pub approvals: Vec<DelayApproval>,
+}
+
+pub enum DelayOutcome {
+    /// The delay was applied; the delayed booking comes first.
+    Applied(Vec<Booking>),
+    /// Nothing moves until the other owners approve.
+    Requested(DelayRequestDetails),
+}
+
+fn conflict_error(conflicts: Vec<BookingConflict>) -> ApiError {
+    match serde_json::to_value(conflicts) {
+        Ok(value) => ApiError::Conflict(
+            "The resource is already booked for the requested time".into(),
+            value,
+        ),
+        Err(e) => ApiError::Internal(e.into()),
+    }
+}
+
+fn shifted(bookings: &[Booking], delta: Duration) -> Vec<Slot> {
+    bookings
+        .iter()
+        .map(|b| (b.booking_date + delta, b.end_date + delta))
+        .collect()
+}
+
+/// The bookings after `booking` that the delay pushes into each other, in order.
+fn following_run(
+    conn: &mut PgConnection,
+    booking: &Booking,
+    resource: Uuid,
+    delta: Duration,
+) -> QueryResult<Vec<Booking>> {
+    use crate::schema::bookings::dsl::*;
+
+    let later = bookings
+        .filter(resource_id.eq(resource))
+        .filter(deleted_at.is_null())
+        .filter(status.ne(BookingStatus::Cancelled))
+        .filter(booking_date.ge(booking.booking_date))
+        .filter(id.ne(booking.id))
+        .order(booking_date.asc())
+        .load::<Booking>(conn)?;
+
+    let mut run_end = booking.end_date + delta;
+    let mut run = Vec::new();
+    for other in later {
+        if other.booking_date >= run_end {
+            break;
+        }
+        run_end = run_end.max(other.end_date + delta);
+        run.push(other);
+    }
+
+    Ok(run)
+}
+
+/// Moves every booking by `minutes`. Callers must hold the resource lock and
+/// have checked for conflicts.
+fn apply_delay(
+    conn: &mut PgConnection,
+    actor: Uuid,
+    to_move: &[Booking],
+    minutes: i32,
+    config: &BookingsConfig,
+) -> Result<Vec<Booking>, ApiError> {
+    use crate::schema::bookings::dsl::*;
+
+    let delta = Duration::minutes(minutes as i64);
+    let mut moved = Vec::with_capacity(to_move.len());
+    for booking in to_move {
+        let new_status = match booking.status {
+            BookingStatus::Confirmed => BookingStatus::Delayed,
+            other => other,
+        };
+        let updated = diesel::update(bookings.find(booking.id))
+            .set((
+                booking_date.eq(booking.booking_date + delta),
+                end_date.eq(booking.end_date + delta),
+                original_booking_date
+                    .eq(booking.original_booking_date.or(Some(booking.booking_date))),
+                original_end_date.eq(booking.original_end_date.or(Some(booking.end_date))),
+                status.eq(new_status),
+                is_override.eq(booking.is_override || booking.series_id.is_some()),
+            ))
+            .get_result::<Booking>(conn)?;
+        notify_participants(
+            conn,
+            actor,
+            &updated,
+            "booking.delayed",
+            serde_json::json!({
+                "booking_id": updated.id,
+                "minutes": minutes,
+                "booking_date": updated.booking_date,
+                "end_date": updated.end_date,
+                "original_booking_date": updated.original_booking_date,
+                "original_end_date": updated.original_end_date,
+            }),
+        )?;
+        moved.push(updated);
+    }
+
+    if let Some(resource) = to_move.first().and_then(|b| b.resource_id) {
+        promote_waitlist(conn, resource, config)?;
+    }
+
+    Ok(moved)
+}
+
+fn load_details(
+    conn: &mut PgConnection,
+    request: DelayRequest,
+) -> QueryResult<DelayRequestDetails> {
+    let approvals = DelayApproval::belonging_to(&request).load::<DelayApproval>(conn)?;
+    Ok(DelayRequestDetails { request, approvals })
+}
+
+/// Delays a booking by `minutes`. If following bookings are in the way and
+/// the resource cascades delays, they are moved along with it, straight away
+/// when the requester owns them all, otherwise once their owners approve.
+pub fn delay_booking(
+    conn: &mut PgConnection,
+    user: &User,
+    booking_uuid: Uuid,
+    data: DelayBookingRequest,
+    config: &BookingsConfig,
+) -> Result<DelayOutcome, ApiError> {
+    use crate::schema::delay_request_approvals::dsl as a_dsl;
+    use crate::schema::delay_requests::dsl::*;
+
+    let delta = Duration::minutes(data.minutes as i64);
+
+    conn.transaction(|conn| {
+        let booking = load_booking(conn, booking_uuid)?;
+        ensure_allowed(conn, user, booking.user_id, "bookings:edit")?;
+
+        if !matches!(
+            booking.status,
+            BookingStatus::Confirmed | BookingStatus::Delayed
+        ) {
+            return Err(ApiError::Invalid(
+                "Only confirmed bookings can be delayed".into(),
+            ));
+        }
+        if booking.end_date <= Utc::now() {
+            return Err(ApiError::Invalid("Booking has already ended".into()));
+        }
+
+        let pending = delay_requests
+            .filter(booking_id.eq(booking.id))
+            .filter(status.eq(ApprovalStatus::Pending))
+            .count()
+            .get_result::<i64>(conn)?;
+        if pending > 0 {
+            return Err(ApiError::Invalid(
+                "A delay of this booking is already awaiting approval".into(),
+            ));
+        }
+
+        let Some(resource_uuid) = booking.resource_id else {
+            let moved = apply_delay(conn, user.id, &[booking], data.minutes, config)?;
+            return Ok(DelayOutcome::Applied(moved));
+        };
+        let resource = lock_resource(conn, resource_uuid)?;
+
+        let conflicts = find_conflicts(
+            conn,
+            resource_uuid,
+            &shifted(std::slice::from_ref(&booking), delta),
+            &[booking.id],
+        )?;
+        if conflicts.is_empty() {
+            let moved = apply_delay(conn, user.id, &[booking], data.minutes, config)?;
+            return Ok(DelayOutcome::Applied(moved));
+        }
+        if !resource.cascade_delays || !data.cascade.unwrap_or(true) {
+            return Err(conflict_error(conflicts));
+        }
+
+        let run = following_run(conn, &booking, resource_uuid, delta)?;
+        let mut to_move = vec![booking];
+        to_move.extend(run);
+        let ids: Vec<Uuid> = to_move.iter().map(|b| b.id).collect();
+        let conflicts = find_conflicts(conn, resource_uuid, &shifted(&to_move, delta), &ids)?;
+        if !conflicts.is_empty() {
+            return Err(conflict_error(conflicts));
+        }
+
+        if to_move[1..].iter().all(|b| b.user_id == user.id) {
+            let moved = apply_delay(conn, user.id, &to_move, data.minutes, config)?;
+            return Ok(DelayOutcome::Applied(moved));
+        }
+
+        let request = diesel::insert_into(delay_requests)
+            .values(&NewDelayRequest {
+                booking_id: to_move[0].id,
+                requested_by: user.id,
+                minutes: data.minutes,
+            })
+            .get_result::<DelayRequest>(conn)?;
+
+        let approvals: Vec<NewDelayApproval> = to_move[1..]
+            .iter()
+            .map(|b| NewDelayApproval {
+                delay_request_id: request.id,
+                booking_id: b.id,
+                owner_id: b.user_id,
+                status: if b.user_id == user.id {
+                    ApprovalStatus::Approved
+                } else {
+                    ApprovalStatus::Pending
+                },
+            })
+            .collect();
+        diesel::insert_into(a_dsl::delay_request_approvals)
+            .values(&approvals)
+            .execute(conn)?;
+
+        for approval in approvals
+            .iter()
+            .filter(|a| a.status == ApprovalStatus::Pending)
+        {
+            notify(
+                conn,
+                approval.owner_id,
+                "booking.delay_requested",
+                serde_json::json!({
+                    "delay_request_id": request.id,
+                    "booking_id": approval.booking_id,
+                    "delayed_booking_id": request.booking_id,
+                    "minutes": request.minutes,
+                }),
+            )?;
+        }
+
+        Ok(DelayOutcome::Requested(load_details(conn, request)?))
+    })
+}
+
+/// Delay requests the user made or is asked to approve, newest first.
+pub fn get_delay_requests(
+    conn: &mut PgConnection,
+    user: &User,
+) -> Result<Vec<DelayRequestDetails>, ApiError> {
+    use crate::schema::delay_request_approvals::dsl as a_dsl;
+    use crate::schema::delay_requests::dsl::*;
+
+    let asked = a_dsl::delay_request_approvals
+        .filter(a_dsl::owner_id.eq(user.id))
+        .select(a_dsl::delay_request_id);
+
+    let requests = delay_requests
+        .filter(requested_by.eq(user.id).or(id.eq_any(asked)))
+        .order(created_at.desc())
+        .load::<DelayRequest>(conn)?;
+
+    let approvals = DelayApproval::belonging_to(&requests)
+        .load::<DelayApproval>(conn)?
+        .grouped_by(&requests);
+
+    Ok(requests
+        .into_iter()
+        .zip(approvals)
+        .map(|(request, approvals)| DelayRequestDetails { request, approvals })
+        .collect())
+}
+
+/// Closes the request and tells the requester how it ended.
+fn resolve(
+    conn: &mut PgConnection,
+    request: &DelayRequest,
+    outcome: ApprovalStatus,
+    event_kind: &str,
+    detail: serde_json::Value,
+) -> QueryResult<DelayRequest> {
+    use crate::schema::delay_requests::dsl::*;
+
+    let resolved = diesel::update(delay_requests.find(request.id))
+        .set((status.eq(outcome), resolved_at.eq(Some(Utc::now()))))
+        .get_result::<DelayRequest>(conn)?;
+
+    let mut payload = serde_json::json!({
+        "delay_request_id": request.id,
+        "booking_id": request.booking_id,
+        "minutes": request.minutes,
+    });
+    if let (Some(map), serde_json::Value::Object(extra)) = (payload.as_object_mut(), detail) {
+        map.extend(extra);
+    }
+    notify(conn, request.requested_by, event_kind, payload)?;
+
+    Ok(resolved)
+}
+
+/// Records the user's answer for their bookings in the request. The delay is
+/// applied once every owner has approved; one rejection rejects it. If the
+/// slots are no longer free by then, the request is rejected as failed.
+pub fn respond_to_delay(
+    conn: &mut PgConnection,
+    user: &User,
+    request_uuid: Uuid,
+    approve: bool,
+    config: &BookingsConfig,
+) -> Result<DelayRequestDetails, ApiError> {
+    use crate::schema::bookings::dsl as b_dsl;
+    use crate::schema::delay_request_approvals::dsl as a_dsl;
+    use crate::schema::delay_requests::dsl::*;
+
+    conn.transaction(|conn| {
+        let request = delay_requests
+            .find(request_uuid)
+            .for_update()
+            .first::<DelayRequest>(conn)
+            .optional()?
+            .ok_or(ApiError::NotFound)?;
+
+        let answer = if approve {
+            ApprovalStatus::Approved
+        } else {
+            ApprovalStatus::Rejected
+        };
+        let answered = diesel::update(
+            a_dsl::delay_request_approvals
+                .filter(a_dsl::delay_request_id.eq(request.id))
+                .filter(a_dsl::owner_id.eq(user.id)),
+        )
+        .set((
+            a_dsl::status.eq(answer),
+            a_dsl::responded_at.eq(Some(Utc::now())),
+        ))
+        .execute(conn)?;
+        if answered == 0 {
+            return Err(ApiError::Forbidden);
+        }
+        if request.status != ApprovalStatus::Pending {
+            return Err(ApiError::Invalid(
+                "Delay request has already been resolved".into(),
+            ));
+        }
+
+        if !approve {
+            let resolved = resolve(
+                conn,
+                &request,
+                ApprovalStatus::Rejected,
+                "booking.delay_rejected",
+                serde_json::json!({ "rejected_by": user.id }),
+            )?;
+            return Ok(load_details(conn, resolved)?);
+        }
+
+        let approvals = DelayApproval::belonging_to(&request).load::<DelayApproval>(conn)?;
+        if approvals
+            .iter()
+            .any(|a| a.status != ApprovalStatus::Approved)
+        {
+            return Ok(DelayRequestDetails { request, approvals });
+        }
+
+        let delayed = load_booking(conn, request.booking_id)?;
+        let resource_uuid = delayed.resource_id.ok_or(ApiError::NotFound)?;
+        lock_resource(conn, resource_uuid)?;
+
+        let mut ids = vec![delayed.id];
+        ids.extend(approvals.iter().map(|a| a.booking_id));
+        let mut to_move = b_dsl::bookings
+            .filter(b_dsl::id.eq_any(&ids))
+            .filter(b_dsl::deleted_at.is_null())
+            .filter(b_dsl::status.ne(BookingStatus::Cancelled))
+            .order(b_dsl::booking_date.asc())
+            .load::<Booking>(conn)?;
+        to_move.sort_by_key(|b| b.id != delayed.id);
+
+        let delta = Duration::minutes(request.minutes as i64);
+        let still_delayable = to_move.first().is_some_and(|b| {
+            b.id == delayed.id
+                && matches!(b.status, BookingStatus::Confirmed | BookingStatus::Delayed)
+        });
+        let moved_ids: Vec<Uuid> = to_move.iter().map(|b| b.id).collect();
+        let conflicts = find_conflicts(conn, resource_uuid, &shifted(&to_move, delta), &moved_ids)?;
+
+        let resolved = if !still_delayable || !conflicts.is_empty() {
+            resolve(
+                conn,
+                &request,
+                ApprovalStatus::Rejected,
+                "booking.delay_failed",
+                serde_json::json!({
+                    "conflicts": serde_json::to_value(conflicts).map_err(anyhow::Error::from)?,
+                }),
+            )?
+        } else {
+            apply_delay(
+                conn,
+                request.requested_by,
+                &to_move,
+                request.minutes,
+                config,
+            )?;
+            resolve(
+                conn,
+                &request,
+                ApprovalStatus::Approved,
+                "booking.delay_approved",
+                serde_json::json!({}),
+            )?
+        };
+
+        Ok(load_details(conn, resolved)?)
+    })
+}
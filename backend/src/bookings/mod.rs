@@ -0,0 +1,1337 @@
+use crate::bookings::delay::DelayOutcome;
+use crate::bookings::rrule::RecurrenceRule;
+use crate::bookings::service::{check_booking_text, check_duration, check_slot};
+use crate::config::Config;
+use crate::errors::ApiError;
+use crate::models::{AttendeeStatus, BookingStatus, CancellationReason};
+use crate::users::service::extract_bearer_token;
+use crate::validation::{self, nfc, nfc_trim, Validate, ValidatedJson, Validator};
+use crate::{services, DbPool};
+use actix_web::{delete, get, patch, post, web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use uuid::Uuid;
+
+pub mod approval;
+pub mod cancellation;
+pub mod checkin;
+pub mod delay;
+pub mod rrule;
+pub mod search;
+pub mod service;
+
+#[derive(Deserialize)]
+pub struct CreateBookingRequest {
+    pub resource_id: Uuid,
+    pub title: String,
+    pub description: Option<String>,
+    pub booking_date: DateTime<Utc>,
+    pub end_date: DateTime<Utc>,
+}
+
+impl Validate for CreateBookingRequest {
+    fn normalize(&mut self) {
+        nfc_trim(&mut self.title);
+        if let Some(ref mut text) = self.description {
+            nfc(text);
+        }
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        check_booking_text(v, Some(&self.title), self.description.as_deref());
+        check_slot(v, "end_date", (self.booking_date, self.end_date));
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SearchBookingsQuery {
+    /// Comma-separated, e.g. `Confirmed,Delayed`.
+    pub status: Option<String>,
+    /// Bookings overlapping `[from, to)`.
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub resource_id: Option<Uuid>,
+    /// Owner; anyone other than the caller needs `bookings:view_all`.
+    pub user_id: Option<Uuid>,
+    /// Case-insensitive text matched against title and description.
+    pub q: Option<String>,
+    /// `booking_date` (default), `created_at`, `updated_at` or `title`;
+    /// prefix with `-` for descending order.
+    pub sort: Option<String>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+    /// Requires `bookings:view_deleted`.
+    pub include_deleted: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateBookingRequest {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub booking_date: Option<DateTime<Utc>>,
+    pub end_date: Option<DateTime<Utc>>,
+    pub status: Option<BookingStatus>,
+}
+
+impl Validate for UpdateBookingRequest {
+    fn normalize(&mut self) {
+        if let Some(ref mut text) = self.title {
+            nfc_trim(text);
+        }
+        if let Some(ref mut text) = self.description {
+            nfc(text);
+        }
+    }
+
+    /// A move that gives only one end is checked against the booking's other
+    /// end by the service.
+    fn validate(&self, v: &mut Validator) {
+        check_booking_text(v, self.title.as_deref(), self.description.as_deref());
+        if let (Some(starts_at), Some(ends_at)) = (self.booking_date, self.end_date) {
+            check_slot(v, "end_date", (starts_at, ends_at));
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateHoldRequest {
+    pub resource_id: Uuid,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub booking_date: DateTime<Utc>,
+    pub end_date: DateTime<Utc>,
+}
+
+impl Validate for CreateHoldRequest {
+    fn normalize(&mut self) {
+        if let Some(ref mut text) = self.title {
+            nfc_trim(text);
+        }
+        if let Some(ref mut text) = self.description {
+            nfc(text);
+        }
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        check_booking_text(v, self.title.as_deref(), self.description.as_deref());
+        check_slot(v, "end_date", (self.booking_date, self.end_date));
+    }
+}
+
+/// Details filled in during checkout; applied when the hold is confirmed.
+#[derive(Deserialize, Default)]
+pub struct ConfirmHoldRequest {
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+impl Validate for ConfirmHoldRequest {
+    fn normalize(&mut self) {
+        if let Some(ref mut text) = self.title {
+            nfc_trim(text);
+        }
+        if let Some(ref mut text) = self.description {
+            nfc(text);
+        }
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        check_booking_text(v, self.title.as_deref(), self.description.as_deref());
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ApprovalQueueQuery {
+    pub resource_id: Option<Uuid>,
+    /// Only escalated (`true`) or only not yet escalated (`false`) requests.
+    pub escalated: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct ApproveBookingRequest {
+    pub comment: Option<String>,
+}
+
+impl Validate for ApproveBookingRequest {
+    fn normalize(&mut self) {
+        if let Some(ref mut text) = self.comment {
+            nfc_trim(text);
+        }
+        self.comment.take_if(|c| c.is_empty());
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        v.optional("comment", self.comment.as_deref())
+            .length(1, 1000);
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RejectBookingRequest {
+    pub reason: String,
+}
+
+impl Validate for RejectBookingRequest {
+    fn normalize(&mut self) {
+        nfc_trim(&mut self.reason);
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        v.field("reason", &self.reason).not_blank().length(1, 1000);
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ScanCheckInRequest {
+    pub code: String,
+}
+
+impl Validate for ScanCheckInRequest {
+    fn normalize(&mut self) {
+        nfc_trim(&mut self.code);
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        v.field("code", &self.code).not_blank();
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CancelBookingRequest {
+    pub reason: CancellationReason,
+    /// Free text; required when the reason is `Other`.
+    pub note: Option<String>,
+    /// Staff only: waive the resource's cancellation cut-off.
+    pub override_policy: Option<bool>,
+}
+
+impl Validate for CancelBookingRequest {
+    fn normalize(&mut self) {
+        if let Some(ref mut text) = self.note {
+            nfc_trim(text);
+        }
+        self.note.take_if(|n| n.is_empty());
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            "note",
+            self.reason != CancellationReason::Other || self.note.is_some(),
+            "A note is required when the reason is Other",
+        );
+        v.optional("note", self.note.as_deref()).length(1, 1000);
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CancelSeriesRequest {
+    #[serde(flatten)]
+    pub cancellation: CancelBookingRequest,
+    /// Cancel the occurrences starting at or after this time; defaults to now.
+    pub from: Option<DateTime<Utc>>,
+}
+
+impl Validate for CancelSeriesRequest {
+    fn normalize(&mut self) {
+        self.cancellation.normalize();
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        self.cancellation.validate(v);
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CancellationStatsQuery {
+    pub since: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct DelayBookingRequest {
+    pub minutes: i32,
+    /// Push following bookings back too, where the resource allows it.
+    /// Defaults to true.
+    pub cascade: Option<bool>,
+}
+
+impl Validate for DelayBookingRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            "minutes",
+            (1..=delay::MAX_DELAY_MINUTES).contains(&self.minutes),
+            &format!(
+                "Delay must be between 1 and {} minutes",
+                delay::MAX_DELAY_MINUTES
+            ),
+        );
+    }
+}
+
+#[derive(Deserialize)]
+pub struct InviteAttendeeRequest {
+    pub user_id: Uuid,
+}
+
+impl Validate for InviteAttendeeRequest {
+    fn validate(&self, _v: &mut Validator) {}
+}
+
+#[derive(Deserialize)]
+pub struct AttendanceRequest {
+    pub status: AttendeeStatus,
+}
+
+impl Validate for AttendanceRequest {
+    fn validate(&self, _v: &mut Validator) {}
+}
+
+#[derive(Deserialize)]
+pub struct CreateSeriesRequest {
+    pub resource_id: Uuid,
+    pub title: String,
+    pub description: Option<String>,
+    pub rrule: String,
+    pub dtstart: DateTime<Utc>,
+    pub duration_minutes: i32,
+    pub exdates: Option<Vec<DateTime<Utc>>>,
+    /// IANA time zone the rule is expanded in, e.g. `Europe/Oslo`; UTC when
+    /// omitted.
+    pub timezone: Option<String>,
+}
+
+impl Validate for CreateSeriesRequest {
+    fn normalize(&mut self) {
+        nfc_trim(&mut self.title);
+        if let Some(ref mut text) = self.description {
+            nfc(text);
+        }
+        self.rrule = self.rrule.trim().to_string();
+        if let Some(ref mut tz) = self.timezone {
+            *tz = tz.trim().to_string();
+        }
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        check_booking_text(v, Some(&self.title), self.description.as_deref());
+        v.field("rrule", &self.rrule).custom(check_rrule);
+        v.optional("timezone", self.timezone.as_deref())
+            .custom(check_timezone);
+        check_duration(v, Some(self.duration_minutes));
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EditScope {
+    This,
+    Following,
+    All,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateSeriesRequest {
+    pub scope: EditScope,
+    /// Original start (RECURRENCE-ID) of the occurrence the edit applies from.
+    pub occurrence: Option<DateTime<Utc>>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub rrule: Option<String>,
+    pub dtstart: Option<DateTime<Utc>>,
+    pub duration_minutes: Option<i32>,
+    pub exdates: Option<Vec<DateTime<Utc>>>,
+    pub timezone: Option<String>,
+    pub status: Option<BookingStatus>,
+}
+
+impl Validate for UpdateSeriesRequest {
+    fn normalize(&mut self) {
+        if let Some(ref mut text) = self.title {
+            nfc_trim(text);
+        }
+        if let Some(ref mut text) = self.description {
+            nfc(text);
+        }
+        if let Some(ref mut rule) = self.rrule {
+            *rule = rule.trim().to_string();
+        }
+        if let Some(ref mut tz) = self.timezone {
+            *tz = tz.trim().to_string();
+        }
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        check_booking_text(v, self.title.as_deref(), self.description.as_deref());
+        v.optional("rrule", self.rrule.as_deref())
+            .custom(check_rrule);
+        v.optional("timezone", self.timezone.as_deref())
+            .custom(check_timezone);
+        check_duration(v, self.duration_minutes);
+    }
+}
+
+fn check_rrule(rule: &str) -> Result<(), String> {
+    rule.parse::<RecurrenceRule>().map(drop)
+}
+
+fn check_timezone(name: &str) -> Result<(), String> {
+    name.parse::<Tz>()
+        .map(drop)
+        .map_err(|_| format!("Unknown time zone: {}", name))
+}
+
+#[get("/bookings")]
+pub async fn search_bookings_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    query: web::Query<SearchBookingsQuery>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        search::search_bookings(&mut conn, &user, query.into_inner())
+    })
+    .await
+    {
+        Ok(Ok(page)) => HttpResponse::Ok().json(page),
+        Ok(Err(e)) => e.into_response("Error searching bookings"),
+        Err(e) => ApiError::from(e).into_response("Error searching bookings"),
+    }
+}
+
+#[post("/bookings")]
+pub async fn create_booking_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    body: ValidatedJson<CreateBookingRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::create_booking(&mut conn, &user, body.into_inner())
+    })
+    .await
+    {
+        Ok(Ok(booking)) => HttpResponse::Created().json(booking),
+        Ok(Err(e)) => e.into_response("Error creating booking"),
+        Err(e) => ApiError::from(e).into_response("Error creating booking"),
+    }
+}
+
+#[post("/bookings/holds")]
+pub async fn create_hold_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    body: ValidatedJson<CreateHoldRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::create_hold(&mut conn, &user, body.into_inner(), &config.bookings)
+    })
+    .await
+    {
+        Ok(Ok(booking)) => HttpResponse::Created().json(booking),
+        Ok(Err(e)) => e.into_response("Error creating hold"),
+        Err(e) => ApiError::from(e).into_response("Error creating hold"),
+    }
+}
+
+#[post("/bookings/{id}/confirm")]
+pub async fn confirm_hold_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    body: Option<web::Json<ConfirmHoldRequest>>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let booking_id = path.into_inner();
+    // An optional body cannot be a `ValidatedJson`: failing to extract
+    // would silently leave it out.
+    let mut details = body.map(|b| b.into_inner()).unwrap_or_default();
+    if let Err(e) = validation::validate(&mut details) {
+        return e.into_response("Error confirming hold");
+    }
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::confirm_hold(&mut conn, &user, booking_id, details)
+    })
+    .await
+    {
+        Ok(Ok(booking)) => HttpResponse::Ok().json(booking),
+        Ok(Err(e)) => e.into_response("Error confirming hold"),
+        Err(e) => ApiError::from(e).into_response("Error confirming hold"),
+    }
+}
+
+#[get("/bookings/approvals")]
+pub async fn get_approval_queue_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    query: web::Query<ApprovalQueueQuery>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        approval::get_approval_queue(&mut conn, &user, query.into_inner())
+    })
+    .await
+    {
+        Ok(Ok(queue)) => HttpResponse::Ok().json(queue),
+        Ok(Err(e)) => e.into_response("Error fetching approval queue"),
+        Err(e) => ApiError::from(e).into_response("Error fetching approval queue"),
+    }
+}
+
+#[post("/bookings/{id}/approve")]
+pub async fn approve_booking_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    body: Option<web::Json<ApproveBookingRequest>>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let booking_id = path.into_inner();
+    let mut details = body.map(|b| b.into_inner()).unwrap_or_default();
+    if let Err(e) = validation::validate(&mut details) {
+        return e.into_response("Error approving booking");
+    }
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        approval::approve_booking(&mut conn, &user, booking_id, details, &config.bookings)
+    })
+    .await
+    {
+        Ok(Ok(booking)) => HttpResponse::Ok().json(booking),
+        Ok(Err(e)) => e.into_response("Error approving booking"),
+        Err(e) => ApiError::from(e).into_response("Error approving booking"),
+    }
+}
+
+#[post("/bookings/{id}/reject")]
+pub async fn reject_booking_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    body: ValidatedJson<RejectBookingRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let booking_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        approval::reject_booking(
+            &mut conn,
+            &user,
+            booking_id,
+            body.into_inner(),
+            &config.bookings,
+        )
+    })
+    .await
+    {
+        Ok(Ok(booking)) => HttpResponse::Ok().json(booking),
+        Ok(Err(e)) => e.into_response("Error rejecting booking"),
+        Err(e) => ApiError::from(e).into_response("Error rejecting booking"),
+    }
+}
+
+#[post("/bookings/check-in/scan")]
+pub async fn scan_check_in_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    body: ValidatedJson<ScanCheckInRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        checkin::check_in_with_code(&mut conn, &user, &body.code, &config)
+    })
+    .await
+    {
+        Ok(Ok(booking)) => HttpResponse::Ok().json(booking),
+        Ok(Err(e)) => e.into_response("Error checking in"),
+        Err(e) => ApiError::from(e).into_response("Error checking in"),
+    }
+}
+
+#[get("/bookings/{id}/check-in-code")]
+pub async fn check_in_code_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let booking_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        checkin::check_in_code(&mut conn, &user, booking_id, &config)
+    })
+    .await
+    {
+        Ok(Ok(code)) => HttpResponse::Ok().json(code),
+        Ok(Err(e)) => e.into_response("Error issuing check-in code"),
+        Err(e) => ApiError::from(e).into_response("Error issuing check-in code"),
+    }
+}
+
+#[post("/bookings/{id}/check-in")]
+pub async fn check_in_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let booking_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        checkin::check_in(&mut conn, &user, booking_id, &config)
+    })
+    .await
+    {
+        Ok(Ok(booking)) => HttpResponse::Ok().json(booking),
+        Ok(Err(e)) => e.into_response("Error checking in"),
+        Err(e) => ApiError::from(e).into_response("Error checking in"),
+    }
+}
+
+#[post("/bookings/{id}/check-out")]
+pub async fn check_out_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let booking_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        checkin::check_out(&mut conn, &user, booking_id)
+    })
+    .await
+    {
+        Ok(Ok(booking)) => HttpResponse::Ok().json(booking),
+        Ok(Err(e)) => e.into_response("Error checking out"),
+        Err(e) => ApiError::from(e).into_response("Error checking out"),
+    }
+}
+
+#[post("/bookings/{id}/cancel")]
+pub async fn cancel_booking_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    body: ValidatedJson<CancelBookingRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let booking_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        cancellation::cancel_booking(
+            &mut conn,
+            &user,
+            booking_id,
+            body.into_inner(),
+            &config.bookings,
+        )
+    })
+    .await
+    {
+        Ok(Ok(booking)) => HttpResponse::Ok().json(booking),
+        Ok(Err(e)) => e.into_response("Error cancelling booking"),
+        Err(e) => ApiError::from(e).into_response("Error cancelling booking"),
+    }
+}
+
+#[post("/bookings/series/{id}/cancel")]
+pub async fn cancel_series_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    body: ValidatedJson<CancelSeriesRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let series_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        cancellation::cancel_series(
+            &mut conn,
+            &user,
+            series_id,
+            body.into_inner(),
+            &config.bookings,
+        )
+    })
+    .await
+    {
+        Ok(Ok(cancelled)) => HttpResponse::Ok().json(cancelled),
+        Ok(Err(e)) => e.into_response("Error cancelling series"),
+        Err(e) => ApiError::from(e).into_response("Error cancelling series"),
+    }
+}
+
+#[get("/users/{id}/cancellations")]
+pub async fn get_cancellation_stats_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    query: web::Query<CancellationStatsQuery>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let user_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        cancellation::get_cancellation_stats(&mut conn, &user, user_id, query.since)
+    })
+    .await
+    {
+        Ok(Ok(stats)) => HttpResponse::Ok().json(stats),
+        Ok(Err(e)) => e.into_response("Error fetching cancellation stats"),
+        Err(e) => ApiError::from(e).into_response("Error fetching cancellation stats"),
+    }
+}
+
+#[post("/bookings/{id}/delay")]
+pub async fn delay_booking_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    body: ValidatedJson<DelayBookingRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let booking_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        delay::delay_booking(
+            &mut conn,
+            &user,
+            booking_id,
+            body.into_inner(),
+            &config.bookings,
+        )
+    })
+    .await
+    {
+        Ok(Ok(DelayOutcome::Applied(moved))) => HttpResponse::Ok().json(moved),
+        Ok(Ok(DelayOutcome::Requested(request))) => HttpResponse::Accepted().json(request),
+        Ok(Err(e)) => e.into_response("Error delaying booking"),
+        Err(e) => ApiError::from(e).into_response("Error delaying booking"),
+    }
+}
+
+#[get("/user/delay-requests")]
+pub async fn get_delay_requests_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        delay::get_delay_requests(&mut conn, &user)
+    })
+    .await
+    {
+        Ok(Ok(requests)) => HttpResponse::Ok().json(requests),
+        Ok(Err(e)) => e.into_response("Error fetching delay requests"),
+        Err(e) => ApiError::from(e).into_response("Error fetching delay requests"),
+    }
+}
+
+#[post("/delay-requests/{id}/approve")]
+pub async fn approve_delay_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let request_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        delay::respond_to_delay(&mut conn, &user, request_id, true, &config.bookings)
+    })
+    .await
+    {
+        Ok(Ok(request)) => HttpResponse::Ok().json(request),
+        Ok(Err(e)) => e.into_response("Error approving delay"),
+        Err(e) => ApiError::from(e).into_response("Error approving delay"),
+    }
+}
+
+#[post("/delay-requests/{id}/reject")]
+pub async fn reject_delay_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let request_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        delay::respond_to_delay(&mut conn, &user, request_id, false, &config.bookings)
+    })
+    .await
+    {
+        Ok(Ok(request)) => HttpResponse::Ok().json(request),
+        Ok(Err(e)) => e.into_response("Error rejecting delay"),
+        Err(e) => ApiError::from(e).into_response("Error rejecting delay"),
+    }
+}
+
+#[post("/bookings/series")]
+pub async fn create_series_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    body: ValidatedJson<CreateSeriesRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::create_series(&mut conn, &user, body.into_inner())
+    })
+    .await
+    {
+        Ok(Ok((series, occurrences))) => HttpResponse::Created().json(serde_json::json!({
+            "series": series,
+            "occurrences": occurrences,
+        })),
+        Ok(Err(e)) => e.into_response("Error creating booking series"),
+        Err(e) => ApiError::from(e).into_response("Error creating booking series"),
+    }
+}
+
+#[get("/bookings/series/{id}")]
+pub async fn get_series_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let series_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::get_series(&mut conn, &user, series_id)
+    })
+    .await
+    {
+        Ok(Ok((series, occurrences))) => HttpResponse::Ok().json(serde_json::json!({
+            "series": series,
+            "occurrences": occurrences,
+        })),
+        Ok(Err(e)) => e.into_response("Error fetching booking series"),
+        Err(e) => ApiError::from(e).into_response("Error fetching booking series"),
+    }
+}
+
+#[patch("/bookings/series/{id}")]
+pub async fn update_series_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    body: ValidatedJson<UpdateSeriesRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let series_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::update_series(
+            &mut conn,
+            &user,
+            series_id,
+            body.into_inner(),
+            &config.bookings,
+        )
+    })
+    .await
+    {
+        Ok(Ok((series, occurrences))) => HttpResponse::Ok().json(serde_json::json!({
+            "series": series,
+            "occurrences": occurrences,
+        })),
+        Ok(Err(e)) => e.into_response("Error updating booking series"),
+        Err(e) => ApiError::from(e).into_response("Error updating booking series"),
+    }
+}
+
+#[get("/user/bookings")]
+pub async fn get_user_bookings_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::get_user_bookings(&mut conn, &user)
+    })
+    .await
+    {
+        Ok(Ok(bookings)) => HttpResponse::Ok().json(bookings),
+        Ok(Err(e)) => e.into_response("Error fetching bookings"),
+        Err(e) => ApiError::from(e).into_response("Error fetching bookings"),
+    }
+}
+
+#[get("/bookings/{id}/attendees")]
+pub async fn get_attendees_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let booking_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::get_attendees(&mut conn, &user, booking_id)
+    })
+    .await
+    {
+        Ok(Ok(attendees)) => HttpResponse::Ok().json(attendees),
+        Ok(Err(e)) => e.into_response("Error fetching attendees"),
+        Err(e) => ApiError::from(e).into_response("Error fetching attendees"),
+    }
+}
+
+#[post("/bookings/{id}/attendees")]
+pub async fn invite_attendee_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    body: ValidatedJson<InviteAttendeeRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let booking_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::invite_attendee(&mut conn, &user, booking_id, body.into_inner())
+    })
+    .await
+    {
+        Ok(Ok(attendee)) => HttpResponse::Created().json(attendee),
+        Ok(Err(e)) => e.into_response("Error inviting attendee"),
+        Err(e) => ApiError::from(e).into_response("Error inviting attendee"),
+    }
+}
+
+#[patch("/bookings/{id}/attendance")]
+pub async fn respond_to_booking_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    body: ValidatedJson<AttendanceRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let booking_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::respond_to_booking(&mut conn, &user, booking_id, body.into_inner())
+    })
+    .await
+    {
+        Ok(Ok(attendee)) => HttpResponse::Ok().json(attendee),
+        Ok(Err(e)) => e.into_response("Error updating attendance"),
+        Err(e) => ApiError::from(e).into_response("Error updating attendance"),
+    }
+}
+
+#[delete("/bookings/{id}/attendees/{user_id}")]
+pub async fn remove_attendee_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<(Uuid, Uuid)>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let (booking_id, attendee_id) = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::remove_attendee(&mut conn, &user, booking_id, attendee_id)
+    })
+    .await
+    {
+        Ok(Ok(())) => HttpResponse::Ok().json(serde_json::json!({"success": true})),
+        Ok(Err(e)) => e.into_response("Error removing attendee"),
+        Err(e) => ApiError::from(e).into_response("Error removing attendee"),
+    }
+}
+
+#[get("/bookings/{id}")]
+pub async fn get_booking_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let booking_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::get_booking(&mut conn, &user, booking_id)
+    })
+    .await
+    {
+        Ok(Ok(booking)) => HttpResponse::Ok().json(booking),
+        Ok(Err(e)) => e.into_response("Error fetching booking"),
+        Err(e) => ApiError::from(e).into_response("Error fetching booking"),
+    }
+}
+
+#[patch("/bookings/{id}")]
+pub async fn update_booking_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    body: ValidatedJson<UpdateBookingRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let booking_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::update_booking(
+            &mut conn,
+            &user,
+            booking_id,
+            body.into_inner(),
+            &config.bookings,
+        )
+    })
+    .await
+    {
+        Ok(Ok(booking)) => HttpResponse::Ok().json(booking),
+        Ok(Err(e)) => e.into_response("Error updating booking"),
+        Err(e) => ApiError::from(e).into_response("Error updating booking"),
+    }
+}
+
+#[delete("/bookings/{id}")]
+pub async fn delete_booking_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let booking_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::delete_booking(&mut conn, &user, booking_id, &config.bookings)
+    })
+    .await
+    {
+        Ok(Ok(())) => HttpResponse::Ok().json(serde_json::json!({"success": true})),
+        Ok(Err(e)) => e.into_response("Error deleting booking"),
+        Err(e) => ApiError::from(e).into_response("Error deleting booking"),
+    }
+}
@@ -0,0 +1,672 @@
+//! A subset of RFC 5545 recurrence rules: `DAILY`, `WEEKLY` and `MONTHLY`
+//! frequencies with `INTERVAL`, `BYDAY`, `BYMONTHDAY`, `BYSETPOS`, `WKST`,
+//! `UNTIL` and `COUNT`. Everything else is rejected as unsupported.
+
+use chrono::{
+    DateTime, Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+    TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use std::fmt;
+use std::str::FromStr;
+
+/// Upper bound on the number of occurrences a single rule may expand to.
+pub const MAX_OCCURRENCES: usize = 500;
+
+/// Upper bound on the number of periods walked while expanding, so rules
+/// that rarely (or never) match cannot loop forever.
+const MAX_PERIODS: u32 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+/// A `BYDAY` entry such as `MO`, `2TU` or `-1FR`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeekdayNum {
+    pub ordinal: Option<i8>,
+    pub weekday: Weekday,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    pub freq: Frequency,
+    pub interval: u32,
+    pub by_day: Vec<WeekdayNum>,
+    pub by_month_day: Vec<i8>,
+    pub by_set_pos: Vec<i16>,
+    pub week_start: Weekday,
+    pub until: Option<DateTime<Utc>>,
+    pub count: Option<u32>,
+}
+
+pub fn format_ical_datetime(dt: &DateTime<Utc>) -> String {
+    dt.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Parses `YYYYMMDDTHHMMSSZ`, a floating `YYYYMMDDTHHMMSS` (taken as UTC) or
+/// a plain `YYYYMMDD` date (taken as the start of that day in UTC).
+pub fn parse_ical_datetime(value: &str) -> Result<DateTime<Utc>, String> {
+    let value = value.trim();
+    let naive = if let Some(stripped) = value.strip_suffix('Z') {
+        NaiveDateTime::parse_from_str(stripped, "%Y%m%dT%H%M%S")
+    } else if value.len() == 8 {
+        NaiveDate::parse_from_str(value, "%Y%m%d")
+            .map(|d| d.and_hms_opt(0, 0, 0).expect("midnight is a valid time"))
+    } else {
+        NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+    }
+    .map_err(|_| format!("Invalid date-time: {}", value))?;
+
+    Ok(Utc.from_utc_datetime(&naive))
+}
+
+fn parse_weekday(value: &str) -> Result<Weekday, String> {
+    match value {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        other => Err(format!("Invalid weekday: {}", other)),
+    }
+}
+
+fn weekday_code(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn parse_weekday_num(value: &str) -> Result<WeekdayNum, String> {
+    let split = value.len().saturating_sub(2);
+    let (ordinal, day) = value.split_at(split);
+    let ordinal = if ordinal.is_empty() {
+        None
+    } else {
+        let n: i8 = ordinal
+            .trim_start_matches('+')
+            .parse()
+            .map_err(|_| format!("Invalid BYDAY value: {}", value))?;
+        if n == 0 || !(-5..=5).contains(&n) {
+            return Err(format!("Invalid BYDAY value: {}", value));
+        }
+        Some(n)
+    };
+
+    Ok(WeekdayNum {
+        ordinal,
+        weekday: parse_weekday(day)?,
+    })
+}
+
+fn parse_list<T: FromStr>(
+    value: &str,
+    name: &str,
+    valid: impl Fn(&T) -> bool,
+) -> Result<Vec<T>, String> {
+    value
+        .split(',')
+        .map(|v| {
+            v.trim_start_matches('+')
+                .parse::<T>()
+                .ok()
+                .filter(|n| valid(n))
+                .ok_or_else(|| format!("Invalid {} value: {}", name, v))
+        })
+        .collect()
+}
+
+impl FromStr for RecurrenceRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let s = s.strip_prefix("RRULE:").unwrap_or(s);
+
+        let mut freq = None;
+        let mut interval = 1;
+        let mut by_day = Vec::new();
+        let mut by_month_day = Vec::new();
+        let mut by_set_pos = Vec::new();
+        let mut week_start = Weekday::Mon;
+        let mut until = None;
+        let mut count = None;
+
+        for part in s.split(';').filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Malformed RRULE part: {}", part))?;
+            let value = value.to_ascii_uppercase();
+
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    freq = Some(match value.as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        other => return Err(format!("Unsupported FREQ: {}", other)),
+                    })
+                }
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .ok()
+                        .filter(|i| *i > 0)
+                        .ok_or_else(|| format!("Invalid INTERVAL value: {}", value))?
+                }
+                "COUNT" => {
+                    count = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|c| *c > 0)
+                            .ok_or_else(|| format!("Invalid COUNT value: {}", value))?,
+                    )
+                }
+                "UNTIL" => until = Some(parse_ical_datetime(&value)?),
+                "BYDAY" => {
+                    by_day = value
+                        .split(',')
+                        .map(parse_weekday_num)
+                        .collect::<Result<_, _>>()?
+                }
+                "BYMONTHDAY" => {
+                    by_month_day = parse_list(&value, "BYMONTHDAY", |d: &i8| {
+                        *d != 0 && (-31..=31).contains(d)
+                    })?
+                }
+                "BYSETPOS" => {
+                    by_set_pos = parse_list(&value, "BYSETPOS", |p: &i16| {
+                        *p != 0 && (-366..=366).contains(p)
+                    })?
+                }
+                "WKST" => week_start = parse_weekday(&value)?,
+                other => return Err(format!("Unsupported RRULE part: {}", other)),
+            }
+        }
+
+        let freq = freq.ok_or("RRULE must specify FREQ")?;
+
+        if until.is_some() && count.is_some() {
+            return Err("RRULE must not specify both UNTIL and COUNT".into());
+        }
+
+        if freq != Frequency::Monthly && by_day.iter().any(|d| d.ordinal.is_some()) {
+            return Err("Numbered BYDAY values are only supported with FREQ=MONTHLY".into());
+        }
+
+        if freq == Frequency::Weekly && !by_month_day.is_empty() {
+            return Err("BYMONTHDAY is not allowed with FREQ=WEEKLY".into());
+        }
+
+        Ok(RecurrenceRule {
+            freq,
+            interval,
+            by_day,
+            by_month_day,
+            by_set_pos,
+            week_start,
+            until,
+            count,
+        })
+    }
+}
+
+impl fmt::Display for RecurrenceRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let freq = match self.freq {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+        };
+        write!(f, "FREQ={}", freq)?;
+
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+
+        if !self.by_day.is_empty() {
+            let days: Vec<String> = self
+                .by_day
+                .iter()
+                .map(|d| match d.ordinal {
+                    Some(n) => format!("{}{}", n, weekday_code(d.weekday)),
+                    None => weekday_code(d.weekday).to_string(),
+                })
+                .collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+
+        if !self.by_month_day.is_empty() {
+            let days: Vec<String> = self.by_month_day.iter().map(|d| d.to_string()).collect();
+            write!(f, ";BYMONTHDAY={}", days.join(","))?;
+        }
+
+        if !self.by_set_pos.is_empty() {
+            let pos: Vec<String> = self.by_set_pos.iter().map(|p| p.to_string()).collect();
+            write!(f, ";BYSETPOS={}", pos.join(","))?;
+        }
+
+        if self.week_start != Weekday::Mon {
+            write!(f, ";WKST={}", weekday_code(self.week_start))?;
+        }
+
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", format_ical_datetime(&until))?;
+        }
+
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    NaiveDate::from_ymd_opt(next_year, next_month, 1)
+        .and_then(|d| d.pred_opt())
+        .map(|d| d.day())
+        .unwrap_or(28)
+}
+
+/// The instant a local time in `tz` stands for. A time that occurs twice
+/// (clocks going back) is the first one; a time skipped by clocks going
+/// forward is read with the offset in force before the change, as RFC 5545
+/// says, which moves it later by the length of the gap.
+fn resolve_local(tz: Tz, local: NaiveDateTime) -> DateTime<Utc> {
+    match tz.from_local_datetime(&local) {
+        LocalResult::Single(dt) | LocalResult::Ambiguous(dt, _) => dt.to_utc(),
+        LocalResult::None => {
+            let before = tz
+                .offset_from_utc_datetime(&(local - Duration::days(1)))
+                .fix();
+            Utc.from_utc_datetime(&(local - Duration::seconds(before.local_minus_utc() as i64)))
+        }
+    }
+}
+
+impl RecurrenceRule {
+    /// Expands the rule into occurrence start times, beginning at `dtstart`.
+    ///
+    /// Days and times are worked out in `tz`, so occurrences keep their wall
+    /// clock time across daylight saving changes, and then converted to UTC.
+    /// Like most implementations (and unlike a strict reading of RFC 5545),
+    /// `dtstart` is only returned when it matches the rule itself. Rules must
+    /// be bounded by `UNTIL` or `COUNT` and may not exceed `MAX_OCCURRENCES`.
+    pub fn occurrences(
+        &self,
+        dtstart: DateTime<Utc>,
+        tz: Tz,
+    ) -> Result<Vec<DateTime<Utc>>, String> {
+        if self.until.is_none() && self.count.is_none() {
+            return Err("RRULE must be bounded by UNTIL or COUNT".into());
+        }
+
+        let local_start = dtstart.with_timezone(&tz).naive_local();
+        let time = local_start.time();
+        let start_date = local_start.date();
+        let mut occurrences = Vec::new();
+
+        for period in 0..MAX_PERIODS {
+            let Some((period_start, days)) = self.period_days(start_date, period) else {
+                break;
+            };
+
+            if let Some(until) = self.until
+                && resolve_local(tz, period_start.and_time(NaiveTime::MIN)) > until
+            {
+                break;
+            }
+
+            for day in days {
+                let occurrence = resolve_local(tz, day.and_time(time));
+                if occurrence < dtstart {
+                    continue;
+                }
+                if let Some(until) = self.until
+                    && occurrence > until
+                {
+                    return Ok(occurrences);
+                }
+                if occurrences.len() == MAX_OCCURRENCES {
+                    return Err(format!(
+                        "Recurrence rule expands to more than {} occurrences",
+                        MAX_OCCURRENCES
+                    ));
+                }
+
+                occurrences.push(occurrence);
+
+                if self.count == Some(occurrences.len() as u32) {
+                    return Ok(occurrences);
+                }
+            }
+        }
+
+        Ok(occurrences)
+    }
+
+    /// Returns the first day of the `period`-th period after `start` together
+    /// with the sorted candidate days it contributes.
+    fn period_days(&self, start: NaiveDate, period: u32) -> Option<(NaiveDate, Vec<NaiveDate>)> {
+        let step = self.interval.checked_mul(period)?;
+
+        let (period_start, mut days) = match self.freq {
+            Frequency::Daily => {
+                let day = start.checked_add_signed(Duration::days(step as i64))?;
+                let matches_day = self.by_day.is_empty()
+                    || self.by_day.iter().any(|d| d.weekday == day.weekday());
+                let matches_month_day = self.by_month_day.is_empty()
+                    || self.by_month_day.iter().any(|md| {
+                        let dim = days_in_month(day.year(), day.month()) as i32;
+                        let md = *md as i32;
+                        let resolved = if md > 0 { md } else { dim + md + 1 };
+                        resolved == day.day() as i32
+                    });
+
+                let days = if matches_day && matches_month_day {
+                    vec![day]
+                } else {
+                    Vec::new()
+                };
+                (day, days)
+            }
+            Frequency::Weekly => {
+                let offset = (7 + start.weekday().num_days_from_monday()
+                    - self.week_start.num_days_from_monday())
+                    % 7;
+                let week = start
+                    .checked_sub_signed(Duration::days(offset as i64))?
+                    .checked_add_signed(Duration::weeks(step as i64))?;
+
+                let weekdays: Vec<Weekday> = if self.by_day.is_empty() {
+                    vec![start.weekday()]
+                } else {
+                    self.by_day.iter().map(|d| d.weekday).collect()
+                };
+
+                let days = weekdays
+                    .into_iter()
+                    .filter_map(|wd| {
+                        let delta = (7 + wd.num_days_from_monday()
+                            - self.week_start.num_days_from_monday())
+                            % 7;
+                        week.checked_add_signed(Duration::days(delta as i64))
+                    })
+                    .collect();
+                (week, days)
+            }
+            Frequency::Monthly => {
+                let month_index = start.year() as i64 * 12 + start.month0() as i64 + step as i64;
+                let year = i32::try_from(month_index.div_euclid(12)).ok()?;
+                let month = month_index.rem_euclid(12) as u32 + 1;
+                let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+                let dim = days_in_month(year, month);
+
+                let month_days: Vec<NaiveDate> = self
+                    .by_month_day
+                    .iter()
+                    .filter_map(|md| {
+                        let md = *md as i32;
+                        let day = if md > 0 { md } else { dim as i32 + md + 1 };
+                        if day < 1 {
+                            return None;
+                        }
+                        NaiveDate::from_ymd_opt(year, month, day as u32)
+                    })
+                    .collect();
+
+                let weekday_days: Vec<NaiveDate> = self
+                    .by_day
+                    .iter()
+                    .flat_map(|wdn| {
+                        let all: Vec<NaiveDate> = (1..=dim)
+                            .filter_map(|d| NaiveDate::from_ymd_opt(year, month, d))
+                            .filter(|d| d.weekday() == wdn.weekday)
+                            .collect();
+                        match wdn.ordinal {
+                            None => all,
+                            Some(n) if n > 0 => {
+                                all.get(n as usize - 1).copied().into_iter().collect()
+                            }
+                            Some(n) => all
+                                .len()
+                                .checked_sub(n.unsigned_abs() as usize)
+                                .and_then(|i| all.get(i).copied())
+                                .into_iter()
+                                .collect(),
+                        }
+                    })
+                    .collect();
+
+                let days = match (self.by_month_day.is_empty(), self.by_day.is_empty()) {
+                    (false, false) => month_days
+                        .into_iter()
+                        .filter(|d| weekday_days.contains(d))
+                        .collect(),
+                    (false, true) => month_days,
+                    (true, false) => weekday_days,
+                    (true, true) => NaiveDate::from_ymd_opt(year, month, start.day())
+                        .into_iter()
+                        .collect(),
+                };
+                (first, days)
+            }
+        };
+
+        days.sort();
+        days.dedup();
+
+        if !self.by_set_pos.is_empty() {
+            let len = days.len() as i16;
+            let mut selected: Vec<NaiveDate> = self
+                .by_set_pos
+                .iter()
+                .filter_map(|pos| {
+                    let index = if *pos > 0 { pos - 1 } else { len + pos };
+                    usize::try_from(index)
+                        .ok()
+                        .and_then(|i| days.get(i).copied())
+                })
+                .collect();
+            selected.sort();
+            selected.dedup();
+            days = selected;
+        }
+
+        Some((period_start, days))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        parse_ical_datetime(value).unwrap()
+    }
+
+    fn expand(rule: &str, dtstart: &str) -> Result<Vec<DateTime<Utc>>, String> {
+        rule.parse::<RecurrenceRule>()?
+            .occurrences(utc(dtstart), Tz::UTC)
+    }
+
+    fn formatted(occurrences: &[DateTime<Utc>]) -> Vec<String> {
+        occurrences.iter().map(format_ical_datetime).collect()
+    }
+
+    #[test]
+    fn weekly_by_day() {
+        // 2026-01-05 is a Monday.
+        let found = expand("FREQ=WEEKLY;BYDAY=MO,WE,FR;COUNT=5", "20260105T090000Z").unwrap();
+        assert_eq!(
+            formatted(&found),
+            [
+                "20260105T090000Z",
+                "20260107T090000Z",
+                "20260109T090000Z",
+                "20260112T090000Z",
+                "20260114T090000Z",
+            ]
+        );
+    }
+
+    #[test]
+    fn weekly_interval_skips_weeks() {
+        let found = expand("FREQ=WEEKLY;INTERVAL=2;COUNT=3", "20260106T100000Z").unwrap();
+        assert_eq!(
+            formatted(&found),
+            ["20260106T100000Z", "20260120T100000Z", "20260203T100000Z"]
+        );
+    }
+
+    #[test]
+    fn dtstart_is_only_returned_when_it_matches() {
+        // Starts on a Tuesday but the rule only allows Wednesdays.
+        let found = expand("FREQ=WEEKLY;BYDAY=WE;COUNT=2", "20260106T090000Z").unwrap();
+        assert_eq!(formatted(&found), ["20260107T090000Z", "20260114T090000Z"]);
+    }
+
+    #[test]
+    fn monthly_by_set_pos_picks_last_weekday() {
+        let found = expand(
+            "FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1;COUNT=3",
+            "20260101T120000Z",
+        )
+        .unwrap();
+        assert_eq!(
+            formatted(&found),
+            ["20260130T120000Z", "20260227T120000Z", "20260331T120000Z"]
+        );
+    }
+
+    #[test]
+    fn monthly_numbered_by_day() {
+        let found = expand("FREQ=MONTHLY;BYDAY=2TU;COUNT=2", "20260101T080000Z").unwrap();
+        assert_eq!(formatted(&found), ["20260113T080000Z", "20260210T080000Z"]);
+    }
+
+    #[test]
+    fn monthly_negative_month_day() {
+        let found = expand("FREQ=MONTHLY;BYMONTHDAY=-1;COUNT=3", "20260101T080000Z").unwrap();
+        assert_eq!(
+            formatted(&found),
+            ["20260131T080000Z", "20260228T080000Z", "20260331T080000Z"]
+        );
+    }
+
+    #[test]
+    fn until_is_inclusive() {
+        let found = expand("FREQ=DAILY;UNTIL=20260103T090000Z", "20260101T090000Z").unwrap();
+        assert_eq!(
+            formatted(&found),
+            ["20260101T090000Z", "20260102T090000Z", "20260103T090000Z"]
+        );
+
+        let found = expand("FREQ=DAILY;UNTIL=20260103T085959Z", "20260101T090000Z").unwrap();
+        assert_eq!(found.len(), 2);
+    }
+
+    #[test]
+    fn count_limits_occurrences() {
+        let found = expand("FREQ=DAILY;COUNT=4", "20260101T090000Z").unwrap();
+        assert_eq!(found.len(), 4);
+        assert_eq!(found[3], utc("20260104T090000Z"));
+    }
+
+    #[test]
+    fn unbounded_rules_are_rejected() {
+        assert!(expand("FREQ=DAILY", "20260101T090000Z").is_err());
+    }
+
+    #[test]
+    fn occurrences_are_capped() {
+        let limit = format!("FREQ=DAILY;COUNT={}", MAX_OCCURRENCES);
+        assert_eq!(
+            expand(&limit, "20260101T090000Z").unwrap().len(),
+            MAX_OCCURRENCES
+        );
+
+        let over = format!("FREQ=DAILY;COUNT={}", MAX_OCCURRENCES + 1);
+        assert!(expand(&over, "20260101T090000Z").is_err());
+        assert!(expand("FREQ=DAILY;UNTIL=20300101T000000Z", "20260101T090000Z").is_err());
+    }
+
+    #[test]
+    fn rules_that_never_match_stop() {
+        let found = expand(
+            "FREQ=MONTHLY;BYMONTHDAY=31;BYDAY=2MO;COUNT=1",
+            "20260101T090000Z",
+        );
+        assert_eq!(found, Ok(Vec::new()));
+    }
+
+    #[test]
+    fn keeps_wall_clock_time_across_daylight_saving() {
+        // Oslo leaves summer time on 2026-10-25: 09:00 is 07:00Z before and
+        // 08:00Z after.
+        let rule: RecurrenceRule = "FREQ=WEEKLY;COUNT=3".parse().unwrap();
+        let found = rule
+            .occurrences(utc("20261018T070000Z"), "Europe/Oslo".parse().unwrap())
+            .unwrap();
+        assert_eq!(
+            formatted(&found),
+            ["20261018T070000Z", "20261025T080000Z", "20261101T080000Z"]
+        );
+    }
+
+    #[test]
+    fn skipped_local_times_move_later() {
+        // 02:30 does not exist in Oslo on 2026-03-29.
+        let rule: RecurrenceRule = "FREQ=DAILY;COUNT=3".parse().unwrap();
+        let found = rule
+            .occurrences(utc("20260328T013000Z"), "Europe/Oslo".parse().unwrap())
+            .unwrap();
+        assert_eq!(
+            formatted(&found),
+            ["20260328T013000Z", "20260329T013000Z", "20260330T003000Z"]
+        );
+    }
+
+    #[test]
+    fn parse_rejects_unsupported_parts() {
+        assert!("FREQ=YEARLY;COUNT=2".parse::<RecurrenceRule>().is_err());
+        assert!("FREQ=DAILY;BYHOUR=9;COUNT=2"
+            .parse::<RecurrenceRule>()
+            .is_err());
+        assert!("FREQ=WEEKLY;BYDAY=1MO;COUNT=2"
+            .parse::<RecurrenceRule>()
+            .is_err());
+        assert!("FREQ=DAILY;COUNT=2;UNTIL=20260101T000000Z"
+            .parse::<RecurrenceRule>()
+            .is_err());
+        assert!("COUNT=2".parse::<RecurrenceRule>().is_err());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let text =
+            "FREQ=MONTHLY;INTERVAL=2;BYDAY=-1FR,MO;BYSETPOS=1;WKST=SU;UNTIL=20261231T000000Z";
+        let rule: RecurrenceRule = format!("RRULE:{}", text).parse().unwrap();
+        assert_eq!(rule.to_string(), text);
+        assert_eq!(rule.to_string().parse::<RecurrenceRule>(), Ok(rule));
+    }
+}
@@ -0,0 +1,232 @@
+//! Listing and searching bookings.
+//!
+//! Results are paged with an opaque keyset cursor holding the sort value and
+//! id of the last row returned, so pages stay stable while bookings are
+//! inserted or changed concurrently. Without `bookings:view_all` callers only
+//! see their own bookings; soft-deleted rows need `bookings:view_deleted` and
+//! an explicit `include_deleted=true`.
+
+use crate::bookings::SearchBookingsQuery;
+use crate::errors::ApiError;
+use crate::models::{Booking, BookingStatus, User};
+use crate::services::like_pattern;
+use crate::users::service::has_permission;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 200;
+
+#[derive(Debug, Serialize)]
+pub struct BookingPage {
+    pub items: Vec<Booking>,
+    /// Pass as `cursor` to fetch the next page; absent on the last page.
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SortKey {
+    BookingDate,
+    CreatedAt,
+    UpdatedAt,
+    Title,
+}
+
+/// What a cursor encodes: the sort it belongs to and the last row's position.
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    sort: SortKey,
+    desc: bool,
+    value: serde_json::Value,
+    id: Uuid,
+}
+
+fn parse_sort(sort: Option<&str>) -> Result<(SortKey, bool), ApiError> {
+    let sort = sort.unwrap_or("booking_date");
+    let (desc, field) = match sort.strip_prefix('-') {
+        Some(field) => (true, field),
+        None => (false, sort),
+    };
+    let key = match field {
+        "booking_date" => SortKey::BookingDate,
+        "created_at" => SortKey::CreatedAt,
+        "updated_at" => SortKey::UpdatedAt,
+        "title" => SortKey::Title,
+        _ => {
+            return Err(ApiError::Invalid(
+                "sort must be one of booking_date, created_at, updated_at or title, optionally prefixed with '-'".into(),
+            ))
+        }
+    };
+    Ok((key, desc))
+}
+
+fn parse_statuses(list: &str) -> Result<Vec<BookingStatus>, ApiError> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            serde_json::from_value::<BookingStatus>(serde_json::Value::String(s.into()))
+                .map_err(|_| ApiError::Invalid(format!("Unknown status: {}", s)))
+        })
+        .collect()
+}
+
+fn encode_cursor(cursor: &Cursor) -> Result<String, ApiError> {
+    Ok(hex::encode(
+        serde_json::to_vec(cursor).map_err(anyhow::Error::from)?,
+    ))
+}
+
+fn decode_cursor(token: &str) -> Result<Cursor, ApiError> {
+    hex::decode(token)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<Cursor>(&bytes).ok())
+        .ok_or_else(|| ApiError::Invalid("Invalid cursor".into()))
+}
+
+fn cursor_value<T: serde::de::DeserializeOwned>(cursor: &Cursor) -> Result<T, ApiError> {
+    serde_json::from_value(cursor.value.clone())
+        .map_err(|_| ApiError::Invalid("Invalid cursor".into()))
+}
+
+/// Orders by `$column` then `id`, and skips everything up to the cursor.
+macro_rules! keyset {
+    ($query:expr, $column:expr, $after:expr, $desc:expr) => {{
+        use crate::schema::bookings::dsl::id;
+
+        let mut query = $query;
+        if let Some((value, after_id)) = $after {
+            query = if $desc {
+                query.filter(
+                    $column
+                        .lt(value.clone())
+                        .or($column.eq(value).and(id.lt(after_id))),
+                )
+            } else {
+                query.filter(
+                    $column
+                        .gt(value.clone())
+                        .or($column.eq(value).and(id.gt(after_id))),
+                )
+            };
+        }
+        if $desc {
+            query.order(($column.desc(), id.desc()))
+        } else {
+            query.order(($column.asc(), id.asc()))
+        }
+    }};
+}
+
+pub fn search_bookings(
+    conn: &mut PgConnection,
+    user: &User,
+    query: SearchBookingsQuery,
+) -> Result<BookingPage, ApiError> {
+    use crate::schema::bookings::dsl::*;
+
+    let view_all = has_permission(conn, user.id, "bookings:view_all")?;
+    if query.user_id.is_some_and(|owner| owner != user.id) && !view_all {
+        return Err(ApiError::Forbidden);
+    }
+    let with_deleted = query.include_deleted.unwrap_or(false);
+    if with_deleted && !has_permission(conn, user.id, "bookings:view_deleted")? {
+        return Err(ApiError::Forbidden);
+    }
+
+    let (key, desc) = parse_sort(query.sort.as_deref())?;
+    let cursor = query.cursor.as_deref().map(decode_cursor).transpose()?;
+    if cursor
+        .as_ref()
+        .is_some_and(|c| c.sort != key || c.desc != desc)
+    {
+        return Err(ApiError::Invalid(
+            "The cursor belongs to a different sort order".into(),
+        ));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let mut found = bookings.into_boxed();
+    if !view_all {
+        found = found.filter(user_id.eq(user.id));
+    }
+    if let Some(owner) = query.user_id {
+        found = found.filter(user_id.eq(owner));
+    }
+    if !with_deleted {
+        found = found.filter(deleted_at.is_null());
+    }
+    if let Some(ref list) = query.status {
+        found = found.filter(status.eq_any(parse_statuses(list)?));
+    }
+    if let Some(resource) = query.resource_id {
+        found = found.filter(resource_id.eq(resource));
+    }
+    if let Some(from) = query.from {
+        found = found.filter(end_date.gt(from));
+    }
+    if let Some(to) = query.to {
+        found = found.filter(booking_date.lt(to));
+    }
+    if let Some(text) = query.q.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+        let pattern = like_pattern(text);
+        found = found.filter(title.ilike(pattern.clone()).or(description.ilike(pattern)));
+    }
+
+    let found = match key {
+        SortKey::BookingDate => {
+            let after = match &cursor {
+                Some(c) => Some((cursor_value::<DateTime<Utc>>(c)?, c.id)),
+                None => None,
+            };
+            keyset!(found, booking_date, after, desc)
+        }
+        SortKey::CreatedAt => {
+            let after = match &cursor {
+                Some(c) => Some((cursor_value::<DateTime<Utc>>(c)?, c.id)),
+                None => None,
+            };
+            keyset!(found, created_at, after, desc)
+        }
+        SortKey::UpdatedAt => {
+            let after = match &cursor {
+                Some(c) => Some((cursor_value::<DateTime<Utc>>(c)?, c.id)),
+                None => None,
+            };
+            keyset!(found, updated_at, after, desc)
+        }
+        SortKey::Title => {
+            let after = match &cursor {
+                Some(c) => Some((cursor_value::<String>(c)?, c.id)),
+                None => None,
+            };
+            keyset!(found, title, after, desc)
+        }
+    };
+
+    let mut items = found.limit(limit + 1).load::<Booking>(conn)?;
+    let next_cursor = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        let last = items.last().expect("page is not empty");
+        let value = match key {
+            SortKey::BookingDate => serde_json::json!(last.booking_date),
+            SortKey::CreatedAt => serde_json::json!(last.created_at),
+            SortKey::UpdatedAt => serde_json::json!(last.updated_at),
+            SortKey::Title => serde_json::json!(last.title),
+        };
+        Some(encode_cursor(&Cursor {
+            sort: key,
+            desc,
+            value,
+            id: last.id,
+        })?)
+    } else {
+        None
+    };
+
+    Ok(BookingPage { items, next_cursor })
+}
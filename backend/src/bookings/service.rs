@@ -0,0 +1,1771 @@
+use crate::booking_rules::service::enforce_rules;
+use crate::bookings::approval::{
+    awaiting_approval, request_approval, requires_approval, withdraw_approvals,
+};
+use crate::bookings::rrule::RecurrenceRule;
+use crate::bookings::{
+    AttendanceRequest, ConfirmHoldRequest, CreateBookingRequest, CreateHoldRequest,
+    CreateSeriesRequest, EditScope, InviteAttendeeRequest, UpdateBookingRequest,
+    UpdateSeriesRequest,
+};
+use crate::config::BookingsConfig;
+use crate::errors::ApiError;
+use crate::metrics;
+use crate::models::{
+    AttendeeStatus, Booking, BookingAttendee, BookingSeries, BookingStatus, CancellationReason,
+    NewBooking, NewBookingAttendee, NewBookingSeries, Resource, UpdateBookingChangeset,
+    UpdateSeriesChangeset, User,
+};
+use crate::notifications::service::notify;
+use crate::users::service::has_permission;
+use crate::validation::Validator;
+use crate::waitlist::service::promote_waitlist;
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Longest duration a single booking or occurrence may span.
+const MAX_DURATION_MINUTES: i32 = 24 * 60;
+
+const DEFAULT_HOLD_TITLE: &str = "Tentative hold";
+
+pub type Slot = (DateTime<Utc>, DateTime<Utc>);
+
+#[derive(Debug, Serialize)]
+pub struct BookingConflict {
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conflicting_booking_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conflicting_busy_block_id: Option<Uuid>,
+}
+
+/// Rules for the title and description of anything that becomes a booking.
+pub fn check_booking_text(v: &mut Validator, title: Option<&str>, description: Option<&str>) {
+    v.optional("title", title).not_blank().length(1, 255);
+    v.optional("description", description).length(0, 10_000);
+}
+
+/// Rules for a booking's time span, reported against the `end` field.
+pub fn check_slot(v: &mut Validator, end: &'static str, (starts_at, ends_at): Slot) {
+    v.check(end, ends_at > starts_at, "Booking must end after it starts");
+    v.check(
+        end,
+        ends_at - starts_at <= Duration::minutes(MAX_DURATION_MINUTES as i64),
+        "Booking must not be longer than 24 hours",
+    );
+}
+
+/// Rules for the length of series occurrences.
+pub fn check_duration(v: &mut Validator, minutes: Option<i32>) {
+    v.check(
+        "duration_minutes",
+        minutes.is_none_or(|m| (1..=MAX_DURATION_MINUTES).contains(&m)),
+        "Duration must be between 1 minute and 24 hours",
+    );
+}
+
+/// Checks a slot put together from an edit and the booking's current times,
+/// which the request alone cannot.
+fn ensure_valid_slot(slot: Slot) -> Result<(), ApiError> {
+    let mut v = Validator::default();
+    check_slot(&mut v, "end_date", slot);
+    v.finish()
+}
+
+/// Loads the resource and takes a row lock on it, serialising every booking
+/// write against the same resource until the surrounding transaction ends.
+pub fn lock_resource(conn: &mut PgConnection, resource_uuid: Uuid) -> Result<Resource, ApiError> {
+    use crate::schema::resources::dsl::*;
+
+    resources
+        .find(resource_uuid)
+        .filter(deleted_at.is_null())
+        .for_update()
+        .first::<Resource>(conn)
+        .optional()?
+        .ok_or(ApiError::NotFound)
+}
+
+/// Returns every active booking and busy block on the resource overlapping
+/// one of `slots`, ignoring the bookings in `exclude` (typically the ones
+/// being replaced).
+pub fn find_conflicts(
+    conn: &mut PgConnection,
+    resource_uuid: Uuid,
+    slots: &[Slot],
+    exclude: &[Uuid],
+) -> QueryResult<Vec<BookingConflict>> {
+    use crate::schema::bookings::dsl::*;
+    use crate::schema::busy_blocks::dsl as bb_dsl;
+
+    let (Some(min_start), Some(max_end)) = (
+        slots.iter().map(|(s, _)| *s).min(),
+        slots.iter().map(|(_, e)| *e).max(),
+    ) else {
+        return Ok(Vec::new());
+    };
+
+    let existing = bookings
+        .filter(resource_id.eq(resource_uuid))
+        .filter(deleted_at.is_null())
+        .filter(status.ne(BookingStatus::Cancelled))
+        .filter(booking_date.lt(max_end))
+        .filter(end_date.gt(min_start))
+        .filter(id.ne_all(exclude))
+        .select((id, booking_date, end_date))
+        .load::<(Uuid, DateTime<Utc>, DateTime<Utc>)>(conn)?;
+
+    let blocks = bb_dsl::busy_blocks
+        .filter(bb_dsl::resource_id.eq(resource_uuid))
+        .filter(bb_dsl::starts_at.lt(max_end))
+        .filter(bb_dsl::ends_at.gt(min_start))
+        .select((bb_dsl::id, bb_dsl::starts_at, bb_dsl::ends_at))
+        .load::<(Uuid, DateTime<Utc>, DateTime<Utc>)>(conn)?;
+
+    let mut conflicts = Vec::new();
+    for (starts_at, ends_at) in slots {
+        for (other_id, other_start, other_end) in &existing {
+            if other_start < ends_at && other_end > starts_at {
+                conflicts.push(BookingConflict {
+                    starts_at: *starts_at,
+                    ends_at: *ends_at,
+                    conflicting_booking_id: Some(*other_id),
+                    conflicting_busy_block_id: None,
+                });
+            }
+        }
+        for (block_id, block_start, block_end) in &blocks {
+            if block_start < ends_at && block_end > starts_at {
+                conflicts.push(BookingConflict {
+                    starts_at: *starts_at,
+                    ends_at: *ends_at,
+                    conflicting_booking_id: None,
+                    conflicting_busy_block_id: Some(*block_id),
+                });
+            }
+        }
+    }
+
+    Ok(conflicts)
+}
+
+fn ensure_no_conflicts(
+    conn: &mut PgConnection,
+    resource_uuid: Uuid,
+    slots: &[Slot],
+    exclude: &[Uuid],
+) -> Result<(), ApiError> {
+    let conflicts = find_conflicts(conn, resource_uuid, slots, exclude)?;
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+
+    Err(ApiError::Conflict(
+        "The resource is already booked for the requested time".into(),
+        serde_json::to_value(conflicts).map_err(anyhow::Error::from)?,
+    ))
+}
+
+/// Owners may always act on their own bookings; anybody else needs `permission`.
+pub fn ensure_allowed(
+    conn: &mut PgConnection,
+    user: &User,
+    owner_id: Uuid,
+    permission: &str,
+) -> Result<(), ApiError> {
+    if user.id == owner_id || has_permission(conn, user.id, permission)? {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden)
+    }
+}
+
+pub fn load_booking(conn: &mut PgConnection, booking_uuid: Uuid) -> Result<Booking, ApiError> {
+    use crate::schema::bookings::dsl::*;
+
+    bookings
+        .find(booking_uuid)
+        .filter(deleted_at.is_null())
+        .first::<Booking>(conn)
+        .optional()?
+        .ok_or(ApiError::NotFound)
+}
+
+pub fn create_booking(
+    conn: &mut PgConnection,
+    user: &User,
+    data: CreateBookingRequest,
+) -> Result<Booking, ApiError> {
+    use crate::schema::bookings::dsl::*;
+
+    let slot = (data.booking_date, data.end_date);
+
+    conn.transaction(|conn| {
+        let resource = lock_resource(conn, data.resource_id)?;
+        enforce_rules(conn, user, user.id, data.resource_id, &[slot], &[])?;
+        ensure_no_conflicts(conn, data.resource_id, &[slot], &[])?;
+
+        let new_booking = NewBooking {
+            user_id: user.id,
+            resource_id: Some(data.resource_id),
+            title: data.title,
+            description: data.description,
+            booking_date: data.booking_date,
+            end_date: data.end_date,
+            status: if resource.requires_approval {
+                BookingStatus::Pending
+            } else {
+                BookingStatus::Confirmed
+            },
+            series_id: None,
+            recurrence_id: None,
+            hold_expires_at: None,
+        };
+
+        let created = diesel::insert_into(bookings)
+            .values(&new_booking)
+            .get_result::<Booking>(conn)?;
+        metrics::bookings_created(std::slice::from_ref(&created));
+        if resource.requires_approval {
+            request_approval(conn, std::slice::from_ref(&created))?;
+        }
+        Ok(created)
+    })
+}
+
+fn find_attendee(
+    conn: &mut PgConnection,
+    booking_uuid: Uuid,
+    user_uuid: Uuid,
+) -> QueryResult<Option<BookingAttendee>> {
+    use crate::schema::booking_attendees::dsl::*;
+
+    booking_attendees
+        .find((booking_uuid, user_uuid))
+        .first::<BookingAttendee>(conn)
+        .optional()
+}
+
+/// Notifies the owner and every attendee who has not declined, except `actor`.
+pub fn notify_participants(
+    conn: &mut PgConnection,
+    actor: Uuid,
+    booking: &Booking,
+    event_kind: &str,
+    payload: serde_json::Value,
+) -> QueryResult<()> {
+    use crate::schema::booking_attendees::dsl::*;
+
+    let mut recipients = booking_attendees
+        .filter(booking_id.eq(booking.id))
+        .filter(status.ne(AttendeeStatus::Declined))
+        .select(user_id)
+        .load::<Uuid>(conn)?;
+    recipients.push(booking.user_id);
+    recipients.retain(|r| *r != actor);
+    recipients.sort();
+    recipients.dedup();
+
+    for recipient in recipients {
+        notify(conn, recipient, event_kind, payload.clone())?;
+    }
+
+    Ok(())
+}
+
+/// Like `ensure_allowed`, but also lets in anybody on the attendee list.
+fn ensure_can_view(
+    conn: &mut PgConnection,
+    user: &User,
+    booking: &Booking,
+) -> Result<(), ApiError> {
+    if find_attendee(conn, booking.id, user.id)?.is_some() {
+        return Ok(());
+    }
+    ensure_allowed(conn, user, booking.user_id, "bookings:edit")
+}
+
+/// How long a tentative hold keeps its slot.
+pub fn hold_period(config: &BookingsConfig) -> Duration {
+    Duration::minutes(config.hold_minutes)
+}
+
+/// Reserves a slot as a `Pending` booking that is cancelled automatically
+/// unless it is confirmed within the hold period.
+pub fn create_hold(
+    conn: &mut PgConnection,
+    user: &User,
+    data: CreateHoldRequest,
+    config: &BookingsConfig,
+) -> Result<Booking, ApiError> {
+    use crate::schema::bookings::dsl::*;
+
+    let slot = (data.booking_date, data.end_date);
+    let hold_title = data.title.unwrap_or_else(|| DEFAULT_HOLD_TITLE.into());
+
+    let now = Utc::now();
+    if data.booking_date <= now {
+        return Err(ApiError::Invalid("Cannot hold a slot in the past".into()));
+    }
+
+    conn.transaction(|conn| {
+        lock_resource(conn, data.resource_id)?;
+        enforce_rules(conn, user, user.id, data.resource_id, &[slot], &[])?;
+        ensure_no_conflicts(conn, data.resource_id, &[slot], &[])?;
+
+        let new_booking = NewBooking {
+            user_id: user.id,
+            resource_id: Some(data.resource_id),
+            title: hold_title,
+            description: data.description,
+            booking_date: data.booking_date,
+            end_date: data.end_date,
+            status: BookingStatus::Pending,
+            series_id: None,
+            recurrence_id: None,
+            hold_expires_at: Some(now + hold_period(config)),
+        };
+
+        let held = diesel::insert_into(bookings)
+            .values(&new_booking)
+            .get_result::<Booking>(conn)?;
+        metrics::bookings_created(std::slice::from_ref(&held));
+        Ok(held)
+    })
+}
+
+/// Turns a live hold into a confirmed booking. Confirming a hold that has
+/// already been confirmed returns it unchanged, so clients can safely retry.
+pub fn confirm_hold(
+    conn: &mut PgConnection,
+    user: &User,
+    booking_uuid: Uuid,
+    data: ConfirmHoldRequest,
+) -> Result<Booking, ApiError> {
+    use crate::schema::bookings::dsl::*;
+
+    conn.transaction(|conn| {
+        let booking = lock_booking(conn, booking_uuid)?;
+        ensure_allowed(conn, user, booking.user_id, "bookings:edit")?;
+
+        let Some(expires_at) = booking.hold_expires_at else {
+            return Err(ApiError::Invalid("Booking is not a hold".into()));
+        };
+        if awaiting_approval(conn, booking.id)? {
+            return Ok(booking);
+        }
+        match booking.status {
+            BookingStatus::Confirmed => return Ok(booking),
+            BookingStatus::Pending if expires_at > Utc::now() => {}
+            BookingStatus::Pending | BookingStatus::Cancelled => {
+                return Err(ApiError::Conflict(
+                    "The hold has expired".into(),
+                    serde_json::json!({ "hold_expires_at": expires_at }),
+                ))
+            }
+            _ => return Err(ApiError::Invalid("Booking is no longer a hold".into())),
+        }
+
+        // On resources that need sign-off the hold stops expiring and waits
+        // for an approver instead.
+        let needs_approval = requires_approval(conn, booking.resource_id)?;
+        let changes = UpdateBookingChangeset {
+            title: data.title,
+            description: data.description,
+            status: Some(if needs_approval {
+                BookingStatus::Pending
+            } else {
+                BookingStatus::Confirmed
+            }),
+            ..Default::default()
+        };
+
+        let confirmed = diesel::update(bookings.find(booking.id))
+            .set(&changes)
+            .get_result::<Booking>(conn)?;
+        if needs_approval {
+            request_approval(conn, std::slice::from_ref(&confirmed))?;
+        }
+        Ok(confirmed)
+    })
+}
+
+/// Cancels every hold that has run out, frees the slots for the waitlist and
+/// tells the holders.
+pub fn reap_expired_holds(
+    conn: &mut PgConnection,
+    config: &BookingsConfig,
+) -> Result<usize, ApiError> {
+    use crate::schema::bookings::dsl::*;
+
+    conn.transaction(|conn| {
+        use crate::schema::booking_approvals::dsl as ba_dsl;
+
+        let expired = diesel::update(
+            bookings
+                .filter(status.eq(BookingStatus::Pending))
+                .filter(hold_expires_at.le(Utc::now()))
+                .filter(deleted_at.is_null())
+                .filter(diesel::dsl::not(diesel::dsl::exists(
+                    ba_dsl::booking_approvals.filter(ba_dsl::booking_id.eq(id)),
+                ))),
+        )
+        .set(status.eq(BookingStatus::Cancelled))
+        .returning((id, user_id, resource_id))
+        .get_results::<(Uuid, Uuid, Option<Uuid>)>(conn)?;
+
+        let mut freed: Vec<Uuid> = Vec::new();
+        for (booking_uuid, holder, resource) in &expired {
+            notify(
+                conn,
+                *holder,
+                "booking.hold_expired",
+                serde_json::json!({ "booking_id": booking_uuid, "resource_id": resource }),
+            )?;
+            freed.extend(resource);
+        }
+
+        freed.sort();
+        freed.dedup();
+        for resource in freed {
+            promote_waitlist(conn, resource, config)?;
+        }
+
+        Ok(expired.len())
+    })
+}
+
+/// How long after its start a booking on a check-in resource may go without
+/// a check-in before it becomes a no-show.
+pub fn no_show_grace(config: &BookingsConfig) -> Duration {
+    Duration::minutes(config.no_show_grace_minutes)
+}
+
+/// Marks bookings that have ended as `Completed`. On resources with
+/// check-in only checked-in bookings complete; the rest are left to
+/// `mark_no_shows`.
+pub fn complete_finished_bookings(conn: &mut PgConnection) -> Result<usize, ApiError> {
+    use crate::schema::bookings::dsl::*;
+    use crate::schema::resources::dsl as r_dsl;
+
+    let without_checkin = r_dsl::resources
+        .filter(r_dsl::checkin_enabled.eq(false))
+        .select(r_dsl::id.nullable());
+
+    Ok(diesel::update(
+        bookings
+            .filter(status.eq_any([BookingStatus::Confirmed, BookingStatus::Delayed]))
+            .filter(end_date.le(Utc::now()))
+            .filter(deleted_at.is_null())
+            .filter(
+                resource_id
+                    .is_null()
+                    .or(checked_in_at.is_not_null())
+                    .or(resource_id.eq_any(without_checkin)),
+            ),
+    )
+    .set(status.eq(BookingStatus::Completed))
+    .execute(conn)?)
+}
+
+/// Marks bookings on check-in resources that were not checked in within the
+/// grace period as `NoShow`, and tells their owners.
+pub fn mark_no_shows(conn: &mut PgConnection, config: &BookingsConfig) -> Result<usize, ApiError> {
+    use crate::schema::bookings::dsl::*;
+    use crate::schema::resources::dsl as r_dsl;
+
+    let with_checkin = r_dsl::resources
+        .filter(r_dsl::checkin_enabled.eq(true))
+        .select(r_dsl::id.nullable());
+
+    conn.transaction(|conn| {
+        let missed = diesel::update(
+            bookings
+                .filter(status.eq_any([BookingStatus::Confirmed, BookingStatus::Delayed]))
+                .filter(booking_date.le(Utc::now() - no_show_grace(config)))
+                .filter(checked_in_at.is_null())
+                .filter(deleted_at.is_null())
+                .filter(resource_id.eq_any(with_checkin)),
+        )
+        .set(status.eq(BookingStatus::NoShow))
+        .returning((id, user_id))
+        .get_results::<(Uuid, Uuid)>(conn)?;
+
+        for (booking_uuid, owner) in &missed {
+            notify(
+                conn,
+                *owner,
+                "booking.no_show",
+                serde_json::json!({ "booking_id": booking_uuid }),
+            )?;
+        }
+
+        Ok(missed.len())
+    })
+}
+
+pub fn get_booking(
+    conn: &mut PgConnection,
+    user: &User,
+    booking_uuid: Uuid,
+) -> Result<Booking, ApiError> {
+    let booking = load_booking(conn, booking_uuid)?;
+    ensure_can_view(conn, user, &booking)?;
+    Ok(booking)
+}
+
+/// Updates a single booking. For an occurrence of a series this is the
+/// "this occurrence" edit: the row is marked as an override so later
+/// series-wide edits leave it alone.
+///
+/// Owners may only change the status to reinstate a cancelled booking as
+/// `Confirmed`; other statuses are set by staff with `bookings:edit` or by the
+/// scheduler. Only bookings that have not started can be reinstated.
+pub fn update_booking(
+    conn: &mut PgConnection,
+    user: &User,
+    booking_uuid: Uuid,
+    data: UpdateBookingRequest,
+    config: &BookingsConfig,
+) -> Result<Booking, ApiError> {
+    use crate::schema::bookings::dsl::*;
+
+    conn.transaction(|conn| {
+        let booking = load_booking(conn, booking_uuid)?;
+        ensure_allowed(conn, user, booking.user_id, "bookings:edit")?;
+
+        let slot = (
+            data.booking_date.unwrap_or(booking.booking_date),
+            data.end_date.unwrap_or(booking.end_date),
+        );
+        ensure_valid_slot(slot)?;
+
+        if data.status == Some(BookingStatus::Cancelled) {
+            return Err(ApiError::Invalid(
+                "Use POST /bookings/{id}/cancel to cancel a booking".into(),
+            ));
+        }
+
+        if data.status.is_some() && awaiting_approval(conn, booking.id)? {
+            return Err(ApiError::Invalid("Booking is awaiting approval".into()));
+        }
+
+        let reschedules = slot != (booking.booking_date, booking.end_date);
+        let reactivates = booking.status == BookingStatus::Cancelled
+            && data.status.is_some_and(|s| s != BookingStatus::Cancelled);
+        if let Some(new_status) = data.status
+            && new_status != booking.status
+            && !(reactivates && new_status == BookingStatus::Confirmed)
+            && !has_permission(conn, user.id, "bookings:edit")?
+        {
+            return Err(ApiError::Forbidden);
+        }
+        if reactivates && slot.0 <= Utc::now() {
+            return Err(ApiError::Invalid(
+                "Only bookings that have not started can be reinstated".into(),
+            ));
+        }
+        // Reinstating a booking on a resource that needs sign-off puts it
+        // back in the queue unless an approver does it.
+        let needs_approval = reactivates
+            && requires_approval(conn, booking.resource_id)?
+            && !has_permission(conn, user.id, "bookings:approve")?;
+        if let Some(resource) = booking.resource_id
+            && (reschedules || reactivates)
+        {
+            lock_resource(conn, resource)?;
+            enforce_rules(
+                conn,
+                user,
+                booking.user_id,
+                resource,
+                &[slot],
+                &[booking.id],
+            )?;
+            ensure_no_conflicts(conn, resource, &[slot], &[booking.id])?;
+        }
+
+        let changes = UpdateBookingChangeset {
+            title: data.title,
+            description: data.description,
+            booking_date: data.booking_date,
+            end_date: data.end_date,
+            status: if needs_approval {
+                Some(BookingStatus::Pending)
+            } else {
+                data.status
+            },
+            is_override: booking.series_id.map(|_| true),
+        };
+
+        let mut updated = diesel::update(bookings.find(booking.id))
+            .set(&changes)
+            .get_result::<Booking>(conn)?;
+
+        // The cancellation stays in `booking_cancellations`; the booking itself
+        // is no longer cancelled.
+        if reactivates {
+            updated = diesel::update(bookings.find(booking.id))
+                .set((
+                    cancelled_at.eq(None::<DateTime<Utc>>),
+                    cancelled_by.eq(None::<Uuid>),
+                    cancellation_reason.eq(None::<CancellationReason>),
+                    cancellation_note.eq(None::<String>),
+                    late_cancellation.eq(false),
+                ))
+                .get_result::<Booking>(conn)?;
+        }
+        if needs_approval {
+            request_approval(conn, std::slice::from_ref(&updated))?;
+        }
+
+        if let Some(resource) = booking.resource_id
+            && reschedules
+        {
+            promote_waitlist(conn, resource, config)?;
+        }
+
+        Ok(updated)
+    })
+}
+
+/// Soft-deletes a booking. A booking that is still going ahead has to be
+/// cancelled first, so its cancellation policy applies, unless the user holds
+/// `bookings:delete`.
+pub fn delete_booking(
+    conn: &mut PgConnection,
+    user: &User,
+    booking_uuid: Uuid,
+    config: &BookingsConfig,
+) -> Result<(), ApiError> {
+    use crate::schema::bookings::dsl::*;
+
+    conn.transaction(|conn| {
+        let booking = load_booking(conn, booking_uuid)?;
+        ensure_allowed(conn, user, booking.user_id, "bookings:delete")?;
+
+        let going_ahead = booking.end_date > Utc::now()
+            && matches!(
+                booking.status,
+                BookingStatus::Pending | BookingStatus::Confirmed | BookingStatus::Delayed
+            );
+        if going_ahead && !has_permission(conn, user.id, "bookings:delete")? {
+            return Err(ApiError::Invalid(
+                "Cancel the booking with POST /bookings/{id}/cancel before deleting it".into(),
+            ));
+        }
+
+        diesel::update(bookings.find(booking.id))
+            .set(deleted_at.eq(Some(Utc::now())))
+            .execute(conn)?;
+        withdraw_approvals(conn, &[booking.id], Some(user.id))?;
+
+        if let Some(resource) = booking.resource_id
+            && booking.status != BookingStatus::Cancelled
+        {
+            promote_waitlist(conn, resource, config)?;
+        }
+
+        Ok(())
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserBooking {
+    #[serde(flatten)]
+    pub booking: Booking,
+    /// The user's answer when they attend rather than organise the booking.
+    pub attendance: Option<AttendeeStatus>,
+}
+
+/// Bookings the user organises plus those they are invited to or attend.
+/// Declined invitations are left out.
+pub fn get_user_bookings(
+    conn: &mut PgConnection,
+    user: &User,
+) -> Result<Vec<UserBooking>, ApiError> {
+    use crate::schema::booking_attendees::dsl as a_dsl;
+    use crate::schema::bookings::dsl::*;
+
+    let organised = bookings
+        .filter(user_id.eq(user.id))
+        .filter(deleted_at.is_null())
+        .load::<Booking>(conn)?;
+
+    let attended = bookings
+        .inner_join(a_dsl::booking_attendees)
+        .filter(a_dsl::user_id.eq(user.id))
+        .filter(a_dsl::status.ne(AttendeeStatus::Declined))
+        .filter(deleted_at.is_null())
+        .select((bookings::all_columns(), a_dsl::status))
+        .load::<(Booking, AttendeeStatus)>(conn)?;
+
+    let mut result: Vec<UserBooking> = organised
+        .into_iter()
+        .map(|booking| UserBooking {
+            booking,
+            attendance: None,
+        })
+        .chain(
+            attended
+                .into_iter()
+                .map(|(booking, attendance)| UserBooking {
+                    booking,
+                    attendance: Some(attendance),
+                }),
+        )
+        .collect();
+    result.sort_by_key(|b| b.booking.booking_date);
+
+    Ok(result)
+}
+
+/// Loads the booking and takes a row lock on it so that attendee changes on
+/// the same booking are serialised and capacity cannot be exceeded.
+pub fn lock_booking(conn: &mut PgConnection, booking_uuid: Uuid) -> Result<Booking, ApiError> {
+    use crate::schema::bookings::dsl::*;
+
+    bookings
+        .find(booking_uuid)
+        .filter(deleted_at.is_null())
+        .for_update()
+        .first::<Booking>(conn)
+        .optional()?
+        .ok_or(ApiError::NotFound)
+}
+
+/// The organiser always holds one place; every accepted attendee takes another.
+fn ensure_capacity(conn: &mut PgConnection, booking: &Booking) -> Result<(), ApiError> {
+    use crate::schema::booking_attendees::dsl::*;
+    use crate::schema::resources::dsl as r_dsl;
+
+    let Some(resource) = booking.resource_id else {
+        return Ok(());
+    };
+    let Some(limit) = r_dsl::resources
+        .find(resource)
+        .select(r_dsl::capacity)
+        .first::<Option<i32>>(conn)?
+    else {
+        return Ok(());
+    };
+
+    let accepted = booking_attendees
+        .filter(booking_id.eq(booking.id))
+        .filter(status.eq(AttendeeStatus::Accepted))
+        .count()
+        .get_result::<i64>(conn)?;
+
+    if accepted + 1 < limit as i64 {
+        return Ok(());
+    }
+
+    Err(ApiError::Conflict(
+        "The booking is full".into(),
+        serde_json::json!({ "capacity": limit, "attendees": accepted + 1 }),
+    ))
+}
+
+pub fn get_attendees(
+    conn: &mut PgConnection,
+    user: &User,
+    booking_uuid: Uuid,
+) -> Result<Vec<BookingAttendee>, ApiError> {
+    use crate::schema::booking_attendees::dsl::*;
+
+    let booking = load_booking(conn, booking_uuid)?;
+    ensure_can_view(conn, user, &booking)?;
+
+    Ok(booking_attendees
+        .filter(booking_id.eq(booking.id))
+        .order(created_at.asc())
+        .load::<BookingAttendee>(conn)?)
+}
+
+/// Invites a user to the booking. Invitations do not take a place until they
+/// are accepted; re-inviting someone who declined resets their answer.
+pub fn invite_attendee(
+    conn: &mut PgConnection,
+    user: &User,
+    booking_uuid: Uuid,
+    data: InviteAttendeeRequest,
+) -> Result<BookingAttendee, ApiError> {
+    use crate::schema::booking_attendees::dsl::*;
+    use crate::schema::users::dsl as u_dsl;
+
+    conn.transaction(|conn| {
+        let booking = lock_booking(conn, booking_uuid)?;
+        ensure_allowed(conn, user, booking.user_id, "bookings:edit")?;
+
+        if booking.status == BookingStatus::Cancelled {
+            return Err(ApiError::Invalid("Booking is cancelled".into()));
+        }
+        if data.user_id == booking.user_id {
+            return Err(ApiError::Invalid(
+                "The organiser cannot be invited to their own booking".into(),
+            ));
+        }
+
+        let invitee_exists = u_dsl::users
+            .find(data.user_id)
+            .filter(u_dsl::deleted_at.is_null())
+            .select(u_dsl::id)
+            .first::<Uuid>(conn)
+            .optional()?
+            .is_some();
+        if !invitee_exists {
+            return Err(ApiError::Invalid("User does not exist".into()));
+        }
+
+        match find_attendee(conn, booking.id, data.user_id)? {
+            Some(existing) if existing.status != AttendeeStatus::Declined => Ok(existing),
+            Some(_) => Ok(
+                diesel::update(booking_attendees.find((booking.id, data.user_id)))
+                    .set((
+                        status.eq(AttendeeStatus::Invited),
+                        invited_by.eq(Some(user.id)),
+                        responded_at.eq(None::<chrono::DateTime<Utc>>),
+                    ))
+                    .get_result::<BookingAttendee>(conn)?,
+            ),
+            None => {
+                let new_attendee = NewBookingAttendee {
+                    booking_id: booking.id,
+                    user_id: data.user_id,
+                    status: AttendeeStatus::Invited,
+                    invited_by: Some(user.id),
+                    responded_at: None,
+                };
+                Ok(diesel::insert_into(booking_attendees)
+                    .values(&new_attendee)
+                    .get_result::<BookingAttendee>(conn)?)
+            }
+        }
+    })
+}
+
+/// Accepts or declines an invitation to a booking for the current user, who
+/// may change their answer later. Only the organiser adds people, so without
+/// an invitation the booking is not found. The capacity check runs under the
+/// booking's row lock, so concurrent accepts cannot overbook it.
+pub fn respond_to_booking(
+    conn: &mut PgConnection,
+    user: &User,
+    booking_uuid: Uuid,
+    data: AttendanceRequest,
+) -> Result<BookingAttendee, ApiError> {
+    use crate::schema::booking_attendees::dsl::*;
+
+    if data.status == AttendeeStatus::Invited {
+        return Err(ApiError::Invalid(
+            "Attendance must be accepted or declined".into(),
+        ));
+    }
+
+    conn.transaction(|conn| {
+        let booking = lock_booking(conn, booking_uuid)?;
+
+        if booking.user_id == user.id {
+            return Err(ApiError::Invalid(
+                "The organiser always attends their own booking".into(),
+            ));
+        }
+        if booking.status == BookingStatus::Cancelled {
+            return Err(ApiError::Invalid("Booking is cancelled".into()));
+        }
+
+        let attendee = find_attendee(conn, booking.id, user.id)?.ok_or(ApiError::NotFound)?;
+        if attendee.status == data.status {
+            return Ok(attendee);
+        }
+
+        if data.status == AttendeeStatus::Accepted {
+            ensure_capacity(conn, &booking)?;
+        }
+
+        Ok(
+            diesel::update(booking_attendees.find((booking.id, user.id)))
+                .set((status.eq(data.status), responded_at.eq(Some(Utc::now()))))
+                .get_result::<BookingAttendee>(conn)?,
+        )
+    })
+}
+
+/// Removes someone from the booking. Attendees may remove themselves; the
+/// organiser (or `bookings:edit`) may remove anybody.
+pub fn remove_attendee(
+    conn: &mut PgConnection,
+    user: &User,
+    booking_uuid: Uuid,
+    attendee_uuid: Uuid,
+) -> Result<(), ApiError> {
+    use crate::schema::booking_attendees::dsl::*;
+
+    let booking = load_booking(conn, booking_uuid)?;
+    if attendee_uuid != user.id {
+        ensure_allowed(conn, user, booking.user_id, "bookings:edit")?;
+    }
+
+    let removed =
+        diesel::delete(booking_attendees.find((booking.id, attendee_uuid))).execute(conn)?;
+    if removed == 0 {
+        return Err(ApiError::NotFound);
+    }
+
+    Ok(())
+}
+
+fn parse_rule(rule: &str) -> Result<RecurrenceRule, ApiError> {
+    rule.parse::<RecurrenceRule>().map_err(ApiError::Invalid)
+}
+
+fn parse_tz(name: &str) -> Result<Tz, ApiError> {
+    name.parse::<Tz>()
+        .map_err(|_| ApiError::Invalid(format!("Unknown time zone: {}", name)))
+}
+
+/// Expands a series definition in its time zone into occurrence slots,
+/// dropping `exdates`.
+fn expand_series(
+    rule: &RecurrenceRule,
+    dtstart: DateTime<Utc>,
+    tz: Tz,
+    duration_minutes: i32,
+    exdates: &[DateTime<Utc>],
+) -> Result<Vec<Slot>, ApiError> {
+    let duration = Duration::minutes(duration_minutes as i64);
+    let slots: Vec<Slot> = rule
+        .occurrences(dtstart, tz)
+        .map_err(ApiError::Invalid)?
+        .into_iter()
+        .filter(|start| !exdates.contains(start))
+        .map(|start| (start, start + duration))
+        .collect();
+
+    if slots.is_empty() {
+        return Err(ApiError::Invalid(
+            "Recurrence rule does not produce any occurrences".into(),
+        ));
+    }
+
+    if slots.windows(2).any(|w| w[0].1 > w[1].0) {
+        return Err(ApiError::Invalid(
+            "Occurrences of the series overlap each other".into(),
+        ));
+    }
+
+    Ok(slots)
+}
+
+fn materialize_occurrences(
+    conn: &mut PgConnection,
+    series: &BookingSeries,
+    slots: &[Slot],
+    initial_status: BookingStatus,
+) -> QueryResult<Vec<Booking>> {
+    use crate::schema::bookings::dsl::*;
+
+    let rows: Vec<NewBooking> = slots
+        .iter()
+        .map(|(starts_at, ends_at)| NewBooking {
+            user_id: series.user_id,
+            resource_id: Some(series.resource_id),
+            title: series.title.clone(),
+            description: series.description.clone(),
+            booking_date: *starts_at,
+            end_date: *ends_at,
+            status: initial_status,
+            series_id: Some(series.id),
+            recurrence_id: Some(*starts_at),
+            hold_expires_at: None,
+        })
+        .collect();
+
+    let created = diesel::insert_into(bookings)
+        .values(&rows)
+        .get_results::<Booking>(conn)?;
+    metrics::bookings_created(&created);
+    Ok(created)
+}
+
+fn load_occurrences(conn: &mut PgConnection, series_uuid: Uuid) -> QueryResult<Vec<Booking>> {
+    use crate::schema::bookings::dsl::*;
+
+    bookings
+        .filter(series_id.eq(series_uuid))
+        .filter(deleted_at.is_null())
+        .order(booking_date.asc())
+        .load::<Booking>(conn)
+}
+
+fn exdates_of(series: &BookingSeries) -> Vec<DateTime<Utc>> {
+    series.exdates.iter().flatten().copied().collect()
+}
+
+pub fn create_series(
+    conn: &mut PgConnection,
+    user: &User,
+    data: CreateSeriesRequest,
+) -> Result<(BookingSeries, Vec<Booking>), ApiError> {
+    use crate::schema::booking_series::dsl::*;
+
+    let rule = parse_rule(&data.rrule)?;
+    let tz = data
+        .timezone
+        .as_deref()
+        .map(parse_tz)
+        .transpose()?
+        .unwrap_or(Tz::UTC);
+    let excluded = data.exdates.unwrap_or_default();
+    let slots = expand_series(&rule, data.dtstart, tz, data.duration_minutes, &excluded)?;
+
+    conn.transaction(|conn| {
+        let resource = lock_resource(conn, data.resource_id)?;
+        enforce_rules(conn, user, user.id, data.resource_id, &slots, &[])?;
+        ensure_no_conflicts(conn, data.resource_id, &slots, &[])?;
+
+        let new_series = NewBookingSeries {
+            user_id: user.id,
+            resource_id: data.resource_id,
+            title: data.title,
+            description: data.description,
+            rrule: rule.to_string(),
+            dtstart: data.dtstart,
+            duration_minutes: data.duration_minutes,
+            exdates: excluded.into_iter().map(Some).collect(),
+            timezone: tz.name().to_string(),
+        };
+
+        let series = diesel::insert_into(booking_series)
+            .values(&new_series)
+            .get_result::<BookingSeries>(conn)?;
+        let occurrences = if resource.requires_approval {
+            let pending = materialize_occurrences(conn, &series, &slots, BookingStatus::Pending)?;
+            request_approval(conn, &pending)?;
+            pending
+        } else {
+            materialize_occurrences(conn, &series, &slots, BookingStatus::Confirmed)?
+        };
+
+        Ok((series, occurrences))
+    })
+}
+
+pub fn load_series(conn: &mut PgConnection, series_uuid: Uuid) -> Result<BookingSeries, ApiError> {
+    use crate::schema::booking_series::dsl::*;
+
+    booking_series
+        .find(series_uuid)
+        .filter(deleted_at.is_null())
+        .for_update()
+        .first::<BookingSeries>(conn)
+        .optional()?
+        .ok_or(ApiError::NotFound)
+}
+
+pub fn get_series(
+    conn: &mut PgConnection,
+    user: &User,
+    series_uuid: Uuid,
+) -> Result<(BookingSeries, Vec<Booking>), ApiError> {
+    conn.transaction(|conn| {
+        let series = load_series(conn, series_uuid)?;
+        ensure_allowed(conn, user, series.user_id, "bookings:edit")?;
+        let occurrences = load_occurrences(conn, series.id)?;
+        Ok((series, occurrences))
+    })
+}
+
+/// Applies an edit to a series using one of three scopes:
+///
+/// * `this` edits the single occurrence starting at `occurrence` and marks it
+///   as an override.
+/// * `following` splits the series at `occurrence`; the old series ends just
+///   before it and a new series carries the edit from there on.
+/// * `all` edits the whole series.
+///
+/// Series-wide edits only touch upcoming occurrences that are still going
+/// ahead and were not edited on their own; past, cancelled, checked-in and
+/// overridden occurrences are left as they are. Timing edits (rule, start,
+/// duration, exceptions) move those upcoming occurrences onto the new slots,
+/// after checking each slot against the rules and for conflicts, and tell
+/// their participants.
+pub fn update_series(
+    conn: &mut PgConnection,
+    user: &User,
+    series_uuid: Uuid,
+    data: UpdateSeriesRequest,
+    config: &BookingsConfig,
+) -> Result<(BookingSeries, Vec<Booking>), ApiError> {
+    if data.status == Some(BookingStatus::Cancelled) {
+        return Err(ApiError::Invalid(
+            "Use POST /bookings/series/{id}/cancel to cancel occurrences".into(),
+        ));
+    }
+
+    conn.transaction(|conn| {
+        let series = load_series(conn, series_uuid)?;
+        ensure_allowed(conn, user, series.user_id, "bookings:edit")?;
+        lock_resource(conn, series.resource_id)?;
+
+        let frees_time = changes_timing(&data);
+        let series = match data.scope {
+            EditScope::This => update_single_occurrence(conn, user, series, data, config)?,
+            EditScope::Following => {
+                let cutoff = data.occurrence.ok_or_else(|| {
+                    ApiError::Invalid("occurrence is required for the 'following' scope".into())
+                })?;
+                if cutoff > series.dtstart {
+                    split_series(conn, user, series, cutoff, data)?
+                } else {
+                    update_whole_series(conn, user, series, data)?
+                }
+            }
+            EditScope::All => update_whole_series(conn, user, series, data)?,
+        };
+
+        if frees_time {
+            promote_waitlist(conn, series.resource_id, config)?;
+        }
+
+        let occurrences = load_occurrences(conn, series.id)?;
+        Ok((series, occurrences))
+    })
+}
+
+fn changes_timing(data: &UpdateSeriesRequest) -> bool {
+    data.rrule.is_some()
+        || data.dtstart.is_some()
+        || data.duration_minutes.is_some()
+        || data.exdates.is_some()
+        || data.timezone.is_some()
+}
+
+/// The "this occurrence" edit, made as an edit of the occurrence itself so
+/// status changes get the same checks as on any other booking.
+fn update_single_occurrence(
+    conn: &mut PgConnection,
+    user: &User,
+    series: BookingSeries,
+    data: UpdateSeriesRequest,
+    config: &BookingsConfig,
+) -> Result<BookingSeries, ApiError> {
+    use crate::schema::bookings::dsl::*;
+
+    let recurrence = data
+        .occurrence
+        .ok_or_else(|| ApiError::Invalid("occurrence is required for the 'this' scope".into()))?;
+
+    if data.rrule.is_some() || data.exdates.is_some() || data.timezone.is_some() {
+        return Err(ApiError::Invalid(
+            "rrule, exdates and timezone can only be changed for the 'following' or 'all' scopes"
+                .into(),
+        ));
+    }
+
+    let occurrence = bookings
+        .filter(series_id.eq(series.id))
+        .filter(recurrence_id.eq(recurrence))
+        .filter(deleted_at.is_null())
+        .first::<Booking>(conn)
+        .optional()?
+        .ok_or(ApiError::NotFound)?;
+
+    let starts_at = data.dtstart.unwrap_or(occurrence.booking_date);
+    let ends_at = match data.duration_minutes {
+        Some(minutes) => starts_at + Duration::minutes(minutes as i64),
+        None => starts_at + (occurrence.end_date - occurrence.booking_date),
+    };
+
+    update_booking(
+        conn,
+        user,
+        occurrence.id,
+        UpdateBookingRequest {
+            title: data.title,
+            description: data.description,
+            booking_date: Some(starts_at),
+            end_date: Some(ends_at),
+            status: data.status,
+        },
+        config,
+    )?;
+
+    Ok(series)
+}
+
+/// Whether a series edit may rewrite the occurrence: it is upcoming, has not
+/// been edited on its own and is still going ahead. Past, cancelled, finished
+/// and overridden occurrences keep their history.
+fn is_editable_occurrence(booking: &Booking, now: DateTime<Utc>) -> bool {
+    booking.booking_date > now
+        && !booking.is_override
+        && booking.checked_in_at.is_none()
+        && matches!(
+            booking.status,
+            BookingStatus::Pending | BookingStatus::Confirmed | BookingStatus::Delayed
+        )
+}
+
+/// Moves the occurrences in `moved` onto `target`. Editable occurrences (see
+/// [`is_editable_occurrence`]) are moved onto the slots of its rule (timing
+/// edits) or updated in place; the others are only re-parented.
+fn apply_to_occurrences(
+    conn: &mut PgConnection,
+    user: &User,
+    target: &BookingSeries,
+    moved: &[Booking],
+    data: &UpdateSeriesRequest,
+) -> Result<(), ApiError> {
+    use crate::schema::bookings::dsl::*;
+
+    let now = Utc::now();
+    let moved_ids: Vec<Uuid> = moved.iter().map(|b| b.id).collect();
+    let (editable, kept): (Vec<&Booking>, Vec<&Booking>) =
+        moved.iter().partition(|b| is_editable_occurrence(b, now));
+    let editable_ids: Vec<Uuid> = editable.iter().map(|b| b.id).collect();
+
+    if data.status.is_some() {
+        for booking in &editable {
+            if awaiting_approval(conn, booking.id)? {
+                return Err(ApiError::Invalid(format!(
+                    "Occurrence on {} is awaiting approval",
+                    booking.booking_date
+                )));
+            }
+        }
+    }
+
+    diesel::update(bookings.filter(id.eq_any(&moved_ids)))
+        .set(series_id.eq(Some(target.id)))
+        .execute(conn)?;
+
+    if changes_timing(data) {
+        let rule = parse_rule(&target.rrule)?;
+        let slots = expand_series(
+            &rule,
+            target.dtstart,
+            parse_tz(&target.timezone)?,
+            target.duration_minutes,
+            &exdates_of(target),
+        )?;
+        // Past slots are history, and a kept occurrence (cancelled or
+        // overridden) stands in for its own slot.
+        let taken: Vec<DateTime<Utc>> = kept.iter().filter_map(|b| b.recurrence_id).collect();
+        let upcoming: Vec<Slot> = slots
+            .into_iter()
+            .filter(|(s, _)| *s > now && !taken.contains(s))
+            .collect();
+        enforce_rules(
+            conn,
+            user,
+            target.user_id,
+            target.resource_id,
+            &upcoming,
+            &editable_ids,
+        )?;
+        ensure_no_conflicts(conn, target.resource_id, &upcoming, &editable_ids)?;
+
+        // The nth upcoming occurrence stays the nth one, so its attendees and
+        // approval follow it to the new time. Occurrences beyond the new rule
+        // go first, and the rest drop their slot before taking the new one, so
+        // no two rows hold the same slot on the way.
+        let mut editable = editable;
+        editable.sort_by_key(|b| b.booking_date);
+        let paired = editable.len().min(upcoming.len());
+        let (rescheduled, dropped) = editable.split_at(paired);
+
+        let dropped_ids: Vec<Uuid> = dropped.iter().map(|b| b.id).collect();
+        if !dropped_ids.is_empty() {
+            diesel::update(bookings.filter(id.eq_any(&dropped_ids)))
+                .set(deleted_at.eq(Some(now)))
+                .execute(conn)?;
+            withdraw_approvals(conn, &dropped_ids, Some(user.id))?;
+            for booking in dropped {
+                notify_participants(
+                    conn,
+                    user.id,
+                    booking,
+                    "booking.deleted",
+                    serde_json::json!({
+                        "booking_id": booking.id,
+                        "booking_date": booking.booking_date,
+                    }),
+                )?;
+            }
+        }
+
+        let rescheduled_ids: Vec<Uuid> = rescheduled.iter().map(|b| b.id).collect();
+        diesel::update(bookings.filter(id.eq_any(&rescheduled_ids)))
+            .set(recurrence_id.eq(None::<DateTime<Utc>>))
+            .execute(conn)?;
+        for (booking, &(starts_at, ends_at)) in rescheduled.iter().zip(&upcoming) {
+            let changes = UpdateBookingChangeset {
+                title: data.title.clone(),
+                description: data.description.clone(),
+                booking_date: Some(starts_at),
+                end_date: Some(ends_at),
+                status: data.status,
+                ..Default::default()
+            };
+            let updated = diesel::update(bookings.find(booking.id))
+                .set((&changes, recurrence_id.eq(Some(starts_at))))
+                .get_result::<Booking>(conn)?;
+            if (starts_at, ends_at) != (booking.booking_date, booking.end_date) {
+                notify_participants(
+                    conn,
+                    user.id,
+                    &updated,
+                    "booking.rescheduled",
+                    serde_json::json!({
+                        "booking_id": updated.id,
+                        "booking_date": updated.booking_date,
+                        "end_date": updated.end_date,
+                        "previous_booking_date": booking.booking_date,
+                        "previous_end_date": booking.end_date,
+                    }),
+                )?;
+            }
+        }
+
+        // Slots the series did not cover before need sign-off like those of a
+        // new series.
+        let added = &upcoming[paired..];
+        if added.is_empty() {
+            return Ok(());
+        }
+        if requires_approval(conn, Some(target.resource_id))? {
+            let pending = materialize_occurrences(conn, target, added, BookingStatus::Pending)?;
+            request_approval(conn, &pending)?;
+        } else {
+            materialize_occurrences(
+                conn,
+                target,
+                added,
+                data.status.unwrap_or(BookingStatus::Confirmed),
+            )?;
+        }
+        return Ok(());
+    }
+
+    let changes = UpdateBookingChangeset {
+        title: data.title.clone(),
+        description: data.description.clone(),
+        status: data.status,
+        ..Default::default()
+    };
+    if changes.title.is_some() || changes.description.is_some() || changes.status.is_some() {
+        diesel::update(bookings.filter(id.eq_any(&editable_ids)))
+            .set(&changes)
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+fn update_whole_series(
+    conn: &mut PgConnection,
+    user: &User,
+    series: BookingSeries,
+    data: UpdateSeriesRequest,
+) -> Result<BookingSeries, ApiError> {
+    use crate::schema::booking_series::dsl::*;
+
+    let rule = data.rrule.as_deref().map(parse_rule).transpose()?;
+    let changes = UpdateSeriesChangeset {
+        title: data.title.clone(),
+        description: data.description.clone(),
+        rrule: rule.map(|r| r.to_string()),
+        dtstart: data.dtstart,
+        duration_minutes: data.duration_minutes,
+        exdates: match (&data.exdates, data.dtstart) {
+            (Some(dates), _) => Some(dates.iter().copied().map(Some).collect()),
+            // Exceptions follow the series when it moves in time.
+            (None, Some(new_start)) => Some(
+                exdates_of(&series)
+                    .into_iter()
+                    .map(|d| Some(d + (new_start - series.dtstart)))
+                    .collect(),
+            ),
+            (None, None) => None,
+        },
+        timezone: data.timezone.clone(),
+    };
+
+    let moved = load_occurrences(conn, series.id)?;
+    let updated =
+        if changes_timing(&data) || changes.title.is_some() || changes.description.is_some() {
+            diesel::update(booking_series.find(series.id))
+                .set(&changes)
+                .get_result::<BookingSeries>(conn)?
+        } else {
+            series
+        };
+
+    apply_to_occurrences(conn, user, &updated, &moved, &data)?;
+    Ok(updated)
+}
+
+fn split_series(
+    conn: &mut PgConnection,
+    user: &User,
+    series: BookingSeries,
+    cutoff: DateTime<Utc>,
+    data: UpdateSeriesRequest,
+) -> Result<BookingSeries, ApiError> {
+    use crate::schema::booking_series::dsl::*;
+    use crate::schema::bookings::dsl as b_dsl;
+
+    let old_rule = parse_rule(&series.rrule)?;
+    let old_starts = old_rule
+        .occurrences(series.dtstart, parse_tz(&series.timezone)?)
+        .map_err(ApiError::Invalid)?;
+    if !old_starts.contains(&cutoff) {
+        return Err(ApiError::Invalid(
+            "occurrence is not part of the series".into(),
+        ));
+    }
+
+    let new_rule = match data.rrule.as_deref() {
+        Some(r) => parse_rule(r)?,
+        None => {
+            let mut inherited = old_rule.clone();
+            if let Some(total) = inherited.count {
+                let before = old_starts.iter().filter(|s| **s < cutoff).count() as u32;
+                inherited.count = Some(total - before);
+            }
+            inherited
+        }
+    };
+
+    let mut truncated = old_rule;
+    truncated.count = None;
+    truncated.until = Some(cutoff - Duration::seconds(1));
+
+    let old_exdates = exdates_of(&series);
+    let new_series = NewBookingSeries {
+        user_id: series.user_id,
+        resource_id: series.resource_id,
+        title: data.title.clone().unwrap_or_else(|| series.title.clone()),
+        description: data
+            .description
+            .clone()
+            .or_else(|| series.description.clone()),
+        rrule: new_rule.to_string(),
+        dtstart: data.dtstart.unwrap_or(cutoff),
+        duration_minutes: data.duration_minutes.unwrap_or(series.duration_minutes),
+        exdates: data
+            .exdates
+            .clone()
+            .unwrap_or_else(|| {
+                old_exdates
+                    .iter()
+                    .filter(|d| **d >= cutoff)
+                    .copied()
+                    .collect()
+            })
+            .into_iter()
+            .map(Some)
+            .collect(),
+        timezone: data
+            .timezone
+            .clone()
+            .unwrap_or_else(|| series.timezone.clone()),
+    };
+
+    let moved = b_dsl::bookings
+        .filter(b_dsl::series_id.eq(series.id))
+        .filter(b_dsl::recurrence_id.ge(cutoff))
+        .filter(b_dsl::deleted_at.is_null())
+        .load::<Booking>(conn)?;
+
+    let created = diesel::insert_into(booking_series)
+        .values(&new_series)
+        .get_result::<BookingSeries>(conn)?;
+
+    diesel::update(booking_series.find(series.id))
+        .set((
+            rrule.eq(truncated.to_string()),
+            exdates.eq(old_exdates
+                .into_iter()
+                .filter(|d| *d < cutoff)
+                .map(Some)
+                .collect::<Vec<_>>()),
+        ))
+        .execute(conn)?;
+
+    apply_to_occurrences(conn, user, &created, &moved, &data)?;
+    Ok(created)
+}
+
+/// Permanently deletes bookings soft-deleted before `cutoff`.
+pub fn purge_deleted_bookings(
+    conn: &mut PgConnection,
+    cutoff: DateTime<Utc>,
+) -> Result<usize, ApiError> {
+    use crate::schema::bookings::dsl::*;
+
+    Ok(diesel::delete(bookings.filter(deleted_at.lt(cutoff))).execute(conn)?)
+}
+
+/// Permanently deletes series soft-deleted before `cutoff`, with their
+/// occurrences.
+pub fn purge_deleted_series(
+    conn: &mut PgConnection,
+    cutoff: DateTime<Utc>,
+) -> Result<usize, ApiError> {
+    use crate::schema::booking_series::dsl::*;
+
+    Ok(diesel::delete(booking_series.filter(deleted_at.lt(cutoff))).execute(conn)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations::MIGRATIONS;
+    use crate::models::{NewResource, NewUser};
+    use chrono::{NaiveTime, TimeZone};
+    use diesel_migrations::MigrationHarness;
+    use std::sync::Once;
+
+    /// A connection to `TEST_DATABASE_URL` inside a transaction that is rolled
+    /// back when it drops, or `None` (and the test passes) when it is unset.
+    fn test_conn() -> Option<PgConnection> {
+        static MIGRATE: Once = Once::new();
+
+        let url = std::env::var("TEST_DATABASE_URL").ok()?;
+        let mut conn = PgConnection::establish(&url).expect("TEST_DATABASE_URL is reachable");
+        MIGRATE.call_once(|| {
+            conn.run_pending_migrations(MIGRATIONS)
+                .expect("migrations apply");
+        });
+        conn.begin_test_transaction().unwrap();
+        Some(conn)
+    }
+
+    fn new_user(conn: &mut PgConnection, name: &str) -> User {
+        let username = format!("{}-{}", name, Uuid::new_v4().simple());
+        diesel::insert_into(crate::schema::users::table)
+            .values(&NewUser {
+                first_name: name.into(),
+                last_name: "Test".into(),
+                email: format!("{}@example.com", username),
+                username,
+                password_hash: String::new(),
+                token_version: 0,
+            })
+            .get_result(conn)
+            .unwrap()
+    }
+
+    fn new_resource(conn: &mut PgConnection) -> Resource {
+        diesel::insert_into(crate::schema::resources::table)
+            .values(&NewResource {
+                name: format!("Room {}", Uuid::new_v4().simple()),
+                description: None,
+                capacity: Some(4),
+                checkin_enabled: false,
+                cascade_delays: false,
+                resource_type: None,
+                requires_approval: false,
+            })
+            .get_result(conn)
+            .unwrap()
+    }
+
+    /// Three weekly hour-long occurrences starting tomorrow at 10:00 UTC.
+    fn weekly_series(
+        conn: &mut PgConnection,
+        owner: &User,
+        resource: &Resource,
+    ) -> (BookingSeries, Vec<Booking>) {
+        let tomorrow = (Utc::now() + Duration::days(1)).date_naive();
+        create_series(
+            conn,
+            owner,
+            CreateSeriesRequest {
+                resource_id: resource.id,
+                title: "Standup".into(),
+                description: None,
+                rrule: "FREQ=WEEKLY;COUNT=3".into(),
+                dtstart: Utc.from_utc_datetime(&tomorrow.and_time(NaiveTime::MIN))
+                    + Duration::hours(10),
+                duration_minutes: 60,
+                exdates: None,
+                timezone: None,
+            },
+        )
+        .unwrap()
+    }
+
+    fn edit(scope: EditScope, occurrence: &Booking) -> UpdateSeriesRequest {
+        UpdateSeriesRequest {
+            scope,
+            occurrence: occurrence.recurrence_id,
+            title: None,
+            description: None,
+            rrule: None,
+            dtstart: None,
+            duration_minutes: None,
+            exdates: None,
+            timezone: None,
+            status: None,
+        }
+    }
+
+    #[test]
+    fn reinstating_one_occurrence_checks_for_conflicts() {
+        let Some(mut conn) = test_conn() else { return };
+        let conn = &mut conn;
+        let alice = new_user(conn, "alice");
+        let bob = new_user(conn, "bob");
+        let room = new_resource(conn);
+        let (series, occurrences) = weekly_series(conn, &alice, &room);
+        let first = &occurrences[0];
+
+        diesel::update(crate::schema::bookings::table.find(first.id))
+            .set(crate::schema::bookings::status.eq(BookingStatus::Cancelled))
+            .execute(conn)
+            .unwrap();
+        create_booking(
+            conn,
+            &bob,
+            CreateBookingRequest {
+                resource_id: room.id,
+                title: "Interview".into(),
+                description: None,
+                booking_date: first.booking_date,
+                end_date: first.end_date,
+            },
+        )
+        .unwrap();
+
+        let reinstate = UpdateSeriesRequest {
+            status: Some(BookingStatus::Confirmed),
+            ..edit(EditScope::This, first)
+        };
+        assert!(matches!(
+            update_series(
+                conn,
+                &alice,
+                series.id,
+                reinstate,
+                &BookingsConfig::default()
+            ),
+            Err(ApiError::Conflict(..))
+        ));
+        assert_eq!(
+            load_booking(conn, first.id).unwrap().status,
+            BookingStatus::Cancelled
+        );
+    }
+
+    #[test]
+    fn owners_cannot_set_other_statuses_on_one_occurrence() {
+        let Some(mut conn) = test_conn() else { return };
+        let conn = &mut conn;
+        let alice = new_user(conn, "alice");
+        let room = new_resource(conn);
+        let (series, occurrences) = weekly_series(conn, &alice, &room);
+
+        let complete = UpdateSeriesRequest {
+            status: Some(BookingStatus::Completed),
+            ..edit(EditScope::This, &occurrences[0])
+        };
+        assert!(matches!(
+            update_series(
+                conn,
+                &alice,
+                series.id,
+                complete,
+                &BookingsConfig::default()
+            ),
+            Err(ApiError::Forbidden)
+        ));
+    }
+
+    fn notified(conn: &mut PgConnection, user: &User, kind: &str) -> Vec<serde_json::Value> {
+        use crate::schema::notification_events::dsl;
+
+        dsl::notification_events
+            .filter(dsl::user_id.eq(user.id))
+            .filter(dsl::kind.eq(kind))
+            .select(dsl::payload)
+            .load(conn)
+            .unwrap()
+    }
+
+    #[test]
+    fn moving_a_series_keeps_attendees_and_tells_them() {
+        let Some(mut conn) = test_conn() else { return };
+        let conn = &mut conn;
+        let alice = new_user(conn, "alice");
+        let bob = new_user(conn, "bob");
+        let room = new_resource(conn);
+        let (series, occurrences) = weekly_series(conn, &alice, &room);
+        let second = &occurrences[1];
+        invite_attendee(
+            conn,
+            &alice,
+            second.id,
+            InviteAttendeeRequest { user_id: bob.id },
+        )
+        .unwrap();
+
+        let later = UpdateSeriesRequest {
+            dtstart: Some(series.dtstart + Duration::hours(2)),
+            ..edit(EditScope::All, &occurrences[0])
+        };
+        let (_, moved) =
+            update_series(conn, &alice, series.id, later, &BookingsConfig::default()).unwrap();
+
+        let ids = |list: &[Booking]| list.iter().map(|b| b.id).collect::<Vec<_>>();
+        assert_eq!(ids(&moved), ids(&occurrences));
+        assert_eq!(
+            moved[1].booking_date,
+            second.booking_date + Duration::hours(2)
+        );
+        assert_eq!(moved[1].recurrence_id, Some(moved[1].booking_date));
+        assert!(find_attendee(conn, second.id, bob.id).unwrap().is_some());
+        let told = notified(conn, &bob, "booking.rescheduled");
+        assert_eq!(told.len(), 1);
+        assert_eq!(told[0]["booking_id"], serde_json::json!(second.id));
+    }
+
+    #[test]
+    fn shortening_a_series_drops_the_last_occurrences() {
+        let Some(mut conn) = test_conn() else { return };
+        let conn = &mut conn;
+        let alice = new_user(conn, "alice");
+        let bob = new_user(conn, "bob");
+        let room = new_resource(conn);
+        let (series, occurrences) = weekly_series(conn, &alice, &room);
+        let last = &occurrences[2];
+        invite_attendee(
+            conn,
+            &alice,
+            last.id,
+            InviteAttendeeRequest { user_id: bob.id },
+        )
+        .unwrap();
+
+        let shorter = UpdateSeriesRequest {
+            rrule: Some("FREQ=WEEKLY;COUNT=2".into()),
+            ..edit(EditScope::All, &occurrences[0])
+        };
+        let (_, remaining) =
+            update_series(conn, &alice, series.id, shorter, &BookingsConfig::default()).unwrap();
+
+        assert_eq!(remaining.len(), 2);
+        assert_eq!(remaining[0].id, occurrences[0].id);
+        assert_eq!(remaining[1].id, occurrences[1].id);
+        assert!(notified(conn, &bob, "booking.rescheduled").is_empty());
+        let told = notified(conn, &bob, "booking.deleted");
+        assert_eq!(told.len(), 1);
+        assert_eq!(told[0]["booking_id"], serde_json::json!(last.id));
+    }
+}
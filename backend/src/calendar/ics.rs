@@ -0,0 +1,517 @@
+//! Minimal iCalendar (RFC 5545) reader and writer.
+
+use crate::bookings::rrule::{format_ical_datetime, parse_ical_datetime};
+use crate::models::BookingStatus;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Content lines longer than this many octets are folded.
+const MAX_LINE_OCTETS: usize = 75;
+
+pub fn ical_status(status: BookingStatus) -> &'static str {
+    match status {
+        BookingStatus::Cancelled => "CANCELLED",
+        BookingStatus::Pending => "TENTATIVE",
+        BookingStatus::Confirmed
+        | BookingStatus::Completed
+        | BookingStatus::NoShow
+        | BookingStatus::Delayed => "CONFIRMED",
+    }
+}
+
+pub fn escape_text(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+/// Folds a content line at `MAX_LINE_OCTETS`, never splitting a UTF-8 character.
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 8);
+    let mut octets = 0;
+
+    for c in line.chars() {
+        let len = c.len_utf8();
+        if octets + len > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(c);
+        octets += len;
+    }
+
+    folded.push_str("\r\n");
+    folded
+}
+
+pub struct IcsWriter {
+    out: String,
+}
+
+impl IcsWriter {
+    pub fn new(name: &str) -> Self {
+        let mut writer = IcsWriter { out: String::new() };
+        writer.line("BEGIN:VCALENDAR");
+        writer.line("VERSION:2.0");
+        writer.line("PRODID:-//simple-booking//EN");
+        writer.line("CALSCALE:GREGORIAN");
+        writer.line("METHOD:PUBLISH");
+        writer.text("X-WR-CALNAME", name);
+        writer
+    }
+
+    pub fn line(&mut self, line: &str) {
+        self.out.push_str(&fold_line(line));
+    }
+
+    pub fn property(&mut self, name: &str, value: &str) {
+        self.line(&format!("{}:{}", name, value));
+    }
+
+    pub fn text(&mut self, name: &str, value: &str) {
+        self.property(name, &escape_text(value));
+    }
+
+    pub fn datetime(&mut self, name: &str, value: &DateTime<Utc>) {
+        self.property(name, &format_ical_datetime(value));
+    }
+
+    /// Writes `values` as local times in `tz` with a TZID, or in UTC when `tz`
+    /// is UTC. Several values are written as one comma separated list.
+    pub fn zoned(&mut self, name: &str, values: &[DateTime<Utc>], tz: Tz) {
+        if tz == Tz::UTC {
+            let dates: Vec<String> = values.iter().map(format_ical_datetime).collect();
+            return self.property(name, &dates.join(","));
+        }
+        let dates: Vec<String> = values
+            .iter()
+            .map(|v| v.with_timezone(&tz).format("%Y%m%dT%H%M%S").to_string())
+            .collect();
+        self.property(&format!("{};TZID={}", name, tz.name()), &dates.join(","));
+    }
+
+    pub fn finish(mut self) -> String {
+        self.line("END:VCALENDAR");
+        self.out
+    }
+}
+
+/// A parsed content line, e.g. `DTSTART;TZID=Europe/Oslo:20250101T090000`.
+#[derive(Debug, Clone)]
+pub struct IcsProperty {
+    pub name: String,
+    pub params: Vec<(String, String)>,
+    pub value: String,
+}
+
+impl IcsProperty {
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IcsEvent {
+    pub properties: Vec<IcsProperty>,
+}
+
+impl IcsEvent {
+    pub fn get(&self, name: &str) -> Option<&IcsProperty> {
+        self.properties.iter().find(|p| p.name == name)
+    }
+
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a IcsProperty> {
+        self.properties.iter().filter(move |p| p.name == name)
+    }
+
+    pub fn text(&self, name: &str) -> Option<String> {
+        self.get(name).map(|p| unescape_text(&p.value))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IcsDate {
+    DateTime(DateTime<Utc>),
+    Date(NaiveDate),
+}
+
+impl IcsDate {
+    /// All-day dates are anchored at midnight UTC.
+    pub fn to_utc(self) -> DateTime<Utc> {
+        match self {
+            IcsDate::DateTime(dt) => dt,
+            IcsDate::Date(d) => Utc.from_utc_datetime(&d.and_time(NaiveTime::MIN)),
+        }
+    }
+}
+
+pub fn unescape_text(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+fn split_unquoted(input: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (i, c) in input.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c == separator && !in_quotes => {
+                parts.push(&input[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&input[start..]);
+    parts
+}
+
+fn parse_content_line(line: &str) -> Result<IcsProperty, String> {
+    let mut in_quotes = false;
+    let colon = line
+        .char_indices()
+        .find(|(_, c)| {
+            if *c == '"' {
+                in_quotes = !in_quotes;
+            }
+            *c == ':' && !in_quotes
+        })
+        .map(|(i, _)| i)
+        .ok_or_else(|| format!("Malformed content line: {}", line))?;
+
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = split_unquoted(head, ';').into_iter();
+    let name = parts.next().unwrap_or_default().trim().to_ascii_uppercase();
+    if name.is_empty() {
+        return Err(format!("Malformed content line: {}", line));
+    }
+
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| {
+            (
+                k.trim().to_ascii_uppercase(),
+                v.trim_matches('"').to_string(),
+            )
+        })
+        .collect();
+
+    Ok(IcsProperty {
+        name,
+        params,
+        value: value.to_string(),
+    })
+}
+
+/// Parses every VEVENT in a calendar. Nested components such as VALARM are
+/// skipped, as are VTIMEZONE definitions (TZIDs are resolved by IANA name).
+pub fn parse_events(input: &str) -> Result<Vec<IcsEvent>, String> {
+    let mut lines: Vec<String> = Vec::new();
+    for raw in input.split('\n') {
+        let raw = raw.strip_suffix('\r').unwrap_or(raw);
+        if let Some(continuation) = raw.strip_prefix([' ', '\t']) {
+            match lines.last_mut() {
+                Some(last) => last.push_str(continuation),
+                None => return Err("Calendar starts with a folded line".into()),
+            }
+        } else if !raw.trim().is_empty() {
+            lines.push(raw.to_string());
+        }
+    }
+
+    if !lines
+        .first()
+        .is_some_and(|l| l.eq_ignore_ascii_case("BEGIN:VCALENDAR"))
+    {
+        return Err("Not an iCalendar file: missing BEGIN:VCALENDAR".into());
+    }
+
+    let mut events = Vec::new();
+    let mut current: Option<IcsEvent> = None;
+    let mut nested = 0;
+
+    for line in &lines {
+        let property = parse_content_line(line)?;
+        let component = property.value.to_ascii_uppercase();
+
+        match (property.name.as_str(), current.as_mut()) {
+            ("BEGIN", None) if component == "VEVENT" => current = Some(IcsEvent::default()),
+            ("BEGIN", Some(_)) => nested += 1,
+            ("END", Some(_)) if nested > 0 => nested -= 1,
+            ("END", Some(_)) if component == "VEVENT" => events.extend(current.take()),
+            (_, Some(event)) if nested == 0 => event.properties.push(property),
+            _ => {}
+        }
+    }
+
+    if current.is_some() {
+        return Err("Unterminated VEVENT".into());
+    }
+
+    Ok(events)
+}
+
+/// Parses a DATE or DATE-TIME property value, honouring `VALUE=DATE` and
+/// `TZID`. Floating times are taken as UTC.
+pub fn parse_date_value(property: &IcsProperty, value: &str) -> Result<IcsDate, String> {
+    let value = value.trim();
+
+    if property
+        .param("VALUE")
+        .is_some_and(|v| v.eq_ignore_ascii_case("DATE"))
+        || value.len() == 8
+    {
+        return NaiveDate::parse_from_str(value, "%Y%m%d")
+            .map(IcsDate::Date)
+            .map_err(|_| format!("Invalid date: {}", value));
+    }
+
+    if value.ends_with('Z') {
+        return parse_ical_datetime(value).map(IcsDate::DateTime);
+    }
+
+    let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .map_err(|_| format!("Invalid date-time: {}", value))?;
+
+    match property.param("TZID") {
+        Some(tzid) => {
+            let tz: Tz = tzid
+                .trim_start_matches('/')
+                .parse()
+                .map_err(|_| format!("Unsupported time zone: {}", tzid))?;
+            tz.from_local_datetime(&naive)
+                .earliest()
+                .map(|dt| IcsDate::DateTime(dt.with_timezone(&Utc)))
+                .ok_or_else(|| format!("Nonexistent local time {} in {}", value, tzid))
+        }
+        None => Ok(IcsDate::DateTime(Utc.from_utc_datetime(&naive))),
+    }
+}
+
+/// Parses an RFC 5545 DURATION such as `PT1H30M`, `P1D` or `P2W`.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration: {}", value);
+    let (negative, rest) = match value.trim().strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.trim().trim_start_matches('+')),
+    };
+    let rest = rest.strip_prefix('P').ok_or_else(invalid)?;
+
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in rest.chars() {
+        match c {
+            'T' => in_time = true,
+            '0'..='9' => number.push(c),
+            unit => {
+                let n: i64 = number.parse().map_err(|_| invalid())?;
+                number.clear();
+                total += match (unit, in_time) {
+                    ('W', false) => Duration::weeks(n),
+                    ('D', false) => Duration::days(n),
+                    ('H', true) => Duration::hours(n),
+                    ('M', true) => Duration::minutes(n),
+                    ('S', true) => Duration::seconds(n),
+                    _ => return Err(invalid()),
+                };
+            }
+        }
+    }
+
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(if negative { -total } else { total })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn property(line: &str) -> IcsProperty {
+        parse_content_line(line).unwrap()
+    }
+
+    #[test]
+    fn parses_events_with_folding_and_params() {
+        let input = "BEGIN:VCALENDAR\r\n\
+                     VERSION:2.0\r\n\
+                     BEGIN:VEVENT\r\n\
+                     UID:one\r\n\
+                     SUMMARY:Weekly \r\n \\, planning\r\n\
+                     DTSTART;TZID=\"Europe/Oslo\":20260105T090000\r\n\
+                     BEGIN:VALARM\r\n\
+                     SUMMARY:Reminder\r\n\
+                     END:VALARM\r\n\
+                     END:VEVENT\r\n\
+                     BEGIN:VEVENT\r\n\
+                     UID:two\r\n\
+                     END:VEVENT\r\n\
+                     END:VCALENDAR\r\n";
+        let events = parse_events(input).unwrap();
+        assert_eq!(events.len(), 2);
+
+        let first = &events[0];
+        assert_eq!(first.text("SUMMARY").as_deref(), Some("Weekly , planning"));
+        assert_eq!(first.get_all("SUMMARY").count(), 1);
+        let dtstart = first.get("DTSTART").unwrap();
+        assert_eq!(dtstart.param("tzid"), Some("Europe/Oslo"));
+        assert_eq!(dtstart.value, "20260105T090000");
+        assert_eq!(events[1].text("UID").as_deref(), Some("two"));
+    }
+
+    #[test]
+    fn rejects_malformed_calendars() {
+        assert!(parse_events("BEGIN:VEVENT\nEND:VEVENT\n").is_err());
+        assert!(parse_events(" folded\nBEGIN:VCALENDAR\n").is_err());
+        assert!(parse_events("BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:x\n").is_err());
+        assert!(parse_events("BEGIN:VCALENDAR\nBEGIN:VEVENT\nno colon\n").is_err());
+    }
+
+    #[test]
+    fn quoted_params_may_contain_separators() {
+        let p = property("ATTENDEE;CN=\"Doe; Jane: PhD\";ROLE=CHAIR:mailto:jane@example.com");
+        assert_eq!(p.name, "ATTENDEE");
+        assert_eq!(p.param("CN"), Some("Doe; Jane: PhD"));
+        assert_eq!(p.param("ROLE"), Some("CHAIR"));
+        assert_eq!(p.value, "mailto:jane@example.com");
+    }
+
+    #[test]
+    fn parses_date_values() {
+        let utc = property("DTSTART:20260105T090000Z");
+        assert_eq!(
+            parse_date_value(&utc, &utc.value),
+            Ok(IcsDate::DateTime(
+                Utc.with_ymd_and_hms(2026, 1, 5, 9, 0, 0).unwrap()
+            ))
+        );
+
+        let zoned = property("DTSTART;TZID=/Europe/Oslo:20260705T090000");
+        assert_eq!(
+            parse_date_value(&zoned, &zoned.value),
+            Ok(IcsDate::DateTime(
+                Utc.with_ymd_and_hms(2026, 7, 5, 7, 0, 0).unwrap()
+            ))
+        );
+
+        let floating = property("DTSTART:20260105T090000");
+        assert_eq!(
+            parse_date_value(&floating, &floating.value).map(IcsDate::to_utc),
+            Ok(Utc.with_ymd_and_hms(2026, 1, 5, 9, 0, 0).unwrap())
+        );
+
+        let date = property("DTSTART;VALUE=DATE:20260105");
+        let parsed = parse_date_value(&date, &date.value).unwrap();
+        assert_eq!(
+            parsed,
+            IcsDate::Date(NaiveDate::from_ymd_opt(2026, 1, 5).unwrap())
+        );
+        assert_eq!(
+            parsed.to_utc(),
+            Utc.with_ymd_and_hms(2026, 1, 5, 0, 0, 0).unwrap()
+        );
+
+        let unknown = property("DTSTART;TZID=Mars/Olympus:20260105T090000");
+        assert!(parse_date_value(&unknown, &unknown.value).is_err());
+        assert!(parse_date_value(&utc, "2026-01-05").is_err());
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("PT1H30M"), Ok(Duration::minutes(90)));
+        assert_eq!(parse_duration("P1D"), Ok(Duration::days(1)));
+        assert_eq!(parse_duration("P2W"), Ok(Duration::weeks(2)));
+        assert_eq!(parse_duration("-PT15M"), Ok(Duration::minutes(-15)));
+        assert_eq!(
+            parse_duration("P1DT2H3S"),
+            Ok(Duration::days(1) + Duration::hours(2) + Duration::seconds(3))
+        );
+        assert!(parse_duration("PT1D").is_err());
+        assert!(parse_duration("P1H").is_err());
+        assert!(parse_duration("PT15").is_err());
+        assert!(parse_duration("1H").is_err());
+    }
+
+    #[test]
+    fn escapes_and_unescapes_text() {
+        let text = "a\\b; c, d\r\ne";
+        let escaped = escape_text(text);
+        assert_eq!(escaped, "a\\\\b\\; c\\, d\\ne");
+        assert_eq!(unescape_text(&escaped), "a\\b; c, d\ne");
+        assert_eq!(unescape_text("x\\Ny\\"), "x\ny\\");
+    }
+
+    #[test]
+    fn folds_long_lines_without_splitting_characters() {
+        let line = format!("SUMMARY:{}", "ø".repeat(60));
+        let folded = fold_line(&line);
+        for part in folded.split("\r\n") {
+            assert!(part.len() <= MAX_LINE_OCTETS, "{} octets", part.len());
+        }
+        let unfolded = folded.trim_end().replace("\r\n ", "");
+        assert_eq!(unfolded, line);
+
+        assert_eq!(fold_line("SHORT:line"), "SHORT:line\r\n");
+    }
+
+    #[test]
+    fn writes_a_calendar_that_parses_back() {
+        let start = Utc.with_ymd_and_hms(2026, 7, 5, 7, 0, 0).unwrap();
+        let mut ics = IcsWriter::new("Room; A");
+        ics.line("BEGIN:VEVENT");
+        ics.property("UID", "one");
+        ics.datetime("DTSTAMP", &start);
+        ics.zoned("DTSTART", &[start], "Europe/Oslo".parse().unwrap());
+        ics.zoned("EXDATE", &[start, start + Duration::days(7)], Tz::UTC);
+        ics.text("SUMMARY", &"Long, escaped title ".repeat(6));
+        ics.line("END:VEVENT");
+        let output = ics.finish();
+
+        assert!(output.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(output.ends_with("END:VCALENDAR\r\n"));
+        assert!(output.contains("X-WR-CALNAME:Room\\; A\r\n"));
+        assert!(output.contains("DTSTART;TZID=Europe/Oslo:20260705T090000\r\n"));
+        assert!(output.contains("EXDATE:20260705T070000Z,20260712T070000Z\r\n"));
+
+        let events = parse_events(&output).unwrap();
+        assert_eq!(events.len(), 1);
+        let dtstart = events[0].get("DTSTART").unwrap();
+        assert_eq!(
+            parse_date_value(dtstart, &dtstart.value).map(IcsDate::to_utc),
+            Ok(start)
+        );
+        assert_eq!(
+            events[0].text("SUMMARY"),
+            Some("Long, escaped title ".repeat(6))
+        );
+    }
+}
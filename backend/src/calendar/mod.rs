@@ -0,0 +1,226 @@
+use crate::config::Config;
+use crate::errors::ApiError;
+use crate::users::service::extract_bearer_token;
+use crate::validation::{Validate, ValidatedJson, Validator};
+use crate::{services, DbPool};
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, ResponseError};
+use serde::Deserialize;
+use uuid::Uuid;
+
+pub mod ics;
+pub mod service;
+
+#[derive(Deserialize)]
+pub struct CreateFeedRequest {
+    /// Subscribe to every booking on this resource instead of your own.
+    pub resource_id: Option<Uuid>,
+}
+
+impl Validate for CreateFeedRequest {
+    fn validate(&self, _v: &mut Validator) {}
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Create bookings owned by the uploader.
+    Bookings,
+    /// Only block the time on the resource.
+    BusyBlocks,
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    pub resource_id: Uuid,
+    pub mode: ImportMode,
+}
+
+fn feed_urls(req: &HttpRequest, token: &str) -> (String, String) {
+    let info = req.connection_info();
+    let path = format!("/calendar/feeds/{}.ics", token);
+    (
+        format!("webcal://{}{}", info.host(), path),
+        format!("{}://{}{}", info.scheme(), info.host(), path),
+    )
+}
+
+#[post("/calendar/feeds")]
+pub async fn create_feed_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    body: ValidatedJson<CreateFeedRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::create_feed(&mut conn, &user, body.into_inner())
+    })
+    .await
+    {
+        Ok(Ok((feed, feed_token))) => {
+            let (webcal_url, url) = feed_urls(&req, &feed_token);
+            HttpResponse::Created().json(serde_json::json!({
+                "feed": feed,
+                "webcal_url": webcal_url,
+                "url": url,
+            }))
+        }
+        Ok(Err(e)) => e.into_response("Error creating calendar feed"),
+        Err(e) => ApiError::from(e).into_response("Error creating calendar feed"),
+    }
+}
+
+#[get("/calendar/feeds")]
+pub async fn get_feeds_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        Ok::<_, crate::errors::ApiError>(service::get_feeds(&mut conn, &user)?)
+    })
+    .await
+    {
+        Ok(Ok(feeds)) => HttpResponse::Ok().json(feeds),
+        Ok(Err(e)) => e.into_response("Error fetching calendar feeds"),
+        Err(e) => ApiError::from(e).into_response("Error fetching calendar feeds"),
+    }
+}
+
+#[delete("/calendar/feeds/{id}")]
+pub async fn revoke_feed_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let feed_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::revoke_feed(&mut conn, &user, feed_id)
+    })
+    .await
+    {
+        Ok(Ok(())) => HttpResponse::Ok().json(serde_json::json!({"success": true})),
+        Ok(Err(e)) => e.into_response("Error revoking calendar feed"),
+        Err(e) => ApiError::from(e).into_response("Error revoking calendar feed"),
+    }
+}
+
+/// Public, read-only feed authenticated by the token in the URL, so calendar
+/// clients can subscribe without a JWT.
+#[get("/calendar/feeds/{token}.ics")]
+pub async fn calendar_feed_endpoint(
+    pool: web::Data<DbPool>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let feed_token = path.into_inner();
+
+    match services::block(move || service::render_feed(&mut conn, &feed_token)).await {
+        Ok(Ok(calendar)) => HttpResponse::Ok()
+            .content_type("text/calendar; charset=utf-8")
+            .body(calendar),
+        Ok(Err(e)) => e.into_response("Error rendering calendar feed"),
+        Err(e) => ApiError::from(e).into_response("Error rendering calendar feed"),
+    }
+}
+
+async fn import_calendar(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    query: ImportQuery,
+    body: web::Bytes,
+    commit: bool,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let input = match String::from_utf8(body.to_vec()) {
+        Ok(s) => s,
+        Err(_) => {
+            return ApiError::Invalid("Calendar must be UTF-8 encoded".into()).error_response()
+        }
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::import_calendar(&mut conn, &user, query, &input, commit)
+    })
+    .await
+    {
+        Ok(Ok(events)) => HttpResponse::Ok().json(serde_json::json!({ "events": events })),
+        Ok(Err(e)) => e.into_response("Error importing calendar"),
+        Err(e) => ApiError::from(e).into_response("Error importing calendar"),
+    }
+}
+
+/// Reports what importing the uploaded .ics body would create, without
+/// writing anything.
+#[post("/calendar/import/preview")]
+pub async fn preview_import_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    query: web::Query<ImportQuery>,
+    body: web::Bytes,
+) -> HttpResponse {
+    import_calendar(pool, config, req, query.into_inner(), body, false).await
+}
+
+#[post("/calendar/import")]
+pub async fn import_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    query: web::Query<ImportQuery>,
+    body: web::Bytes,
+) -> HttpResponse {
+    import_calendar(pool, config, req, query.into_inner(), body, true).await
+}
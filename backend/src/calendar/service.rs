@@ -0,0 +1,748 @@
+use crate::booking_rules::service::enforce_rules;
+use crate::bookings::rrule::RecurrenceRule;
+use crate::bookings::service::{
+    check_booking_text, check_slot, find_conflicts, lock_resource, BookingConflict, Slot,
+};
+use crate::bookings::{CreateBookingRequest, CreateSeriesRequest};
+use crate::calendar::ics::{
+    ical_status, parse_date_value, parse_duration, parse_events, IcsDate, IcsEvent, IcsWriter,
+};
+use crate::calendar::{CreateFeedRequest, ImportMode, ImportQuery};
+use crate::errors::ApiError;
+use crate::models::{
+    AttendeeStatus, Booking, BookingSeries, BookingStatus, CalendarFeed, NewBusyBlock,
+    NewCalendarFeed, User,
+};
+use crate::users::service::has_permission;
+use crate::validation::Validator;
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// Permission required to subscribe to every booking on a resource.
+const RESOURCE_FEED_PERMISSION: &str = "bookings:edit";
+
+pub fn generate_feed_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+pub fn hash_feed_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+pub fn create_feed(
+    conn: &mut PgConnection,
+    user: &User,
+    data: CreateFeedRequest,
+) -> Result<(CalendarFeed, String), ApiError> {
+    use crate::schema::calendar_feeds::dsl::*;
+
+    if let Some(resource) = data.resource_id {
+        crate::resources::service::get_resource(conn, resource)?;
+        if !has_permission(conn, user.id, RESOURCE_FEED_PERMISSION)? {
+            return Err(ApiError::Forbidden);
+        }
+    }
+
+    let token = generate_feed_token();
+    let new_feed = NewCalendarFeed {
+        user_id: user.id,
+        resource_id: data.resource_id,
+        token_hash: hash_feed_token(&token),
+    };
+
+    let feed = diesel::insert_into(calendar_feeds)
+        .values(&new_feed)
+        .get_result::<CalendarFeed>(conn)?;
+
+    Ok((feed, token))
+}
+
+pub fn get_feeds(conn: &mut PgConnection, user: &User) -> QueryResult<Vec<CalendarFeed>> {
+    use crate::schema::calendar_feeds::dsl::*;
+
+    calendar_feeds
+        .filter(user_id.eq(user.id))
+        .filter(revoked_at.is_null())
+        .order(created_at.asc())
+        .load::<CalendarFeed>(conn)
+}
+
+pub fn revoke_feed(conn: &mut PgConnection, user: &User, feed_uuid: Uuid) -> Result<(), ApiError> {
+    use crate::schema::calendar_feeds::dsl::*;
+
+    let revoked = diesel::update(
+        calendar_feeds
+            .find(feed_uuid)
+            .filter(user_id.eq(user.id))
+            .filter(revoked_at.is_null()),
+    )
+    .set(revoked_at.eq(Some(Utc::now())))
+    .execute(conn)?;
+
+    if revoked == 0 {
+        return Err(ApiError::NotFound);
+    }
+
+    Ok(())
+}
+
+/// Resolves a feed token and renders its calendar. Unknown and revoked
+/// tokens, as well as feeds whose owner lost access, are all `NotFound`.
+pub fn render_feed(conn: &mut PgConnection, token: &str) -> Result<String, ApiError> {
+    use crate::schema::booking_attendees::dsl as a_dsl;
+    use crate::schema::booking_series::dsl as s_dsl;
+    use crate::schema::bookings::dsl as b_dsl;
+    use crate::schema::calendar_feeds::dsl::*;
+    use crate::schema::resources::dsl as r_dsl;
+    use crate::schema::users::dsl as u_dsl;
+
+    let feed = calendar_feeds
+        .filter(token_hash.eq(hash_feed_token(token)))
+        .filter(revoked_at.is_null())
+        .first::<CalendarFeed>(conn)
+        .optional()?
+        .ok_or(ApiError::NotFound)?;
+
+    let owner = u_dsl::users
+        .find(feed.user_id)
+        .filter(u_dsl::deleted_at.is_null())
+        .select(u_dsl::username)
+        .first::<String>(conn)
+        .optional()?
+        .ok_or(ApiError::NotFound)?;
+
+    diesel::update(calendar_feeds.find(feed.id))
+        .set(last_used_at.eq(Some(Utc::now())))
+        .execute(conn)?;
+
+    let (name, bookings, series) = match feed.resource_id {
+        Some(resource) => {
+            if !has_permission(conn, feed.user_id, RESOURCE_FEED_PERMISSION)? {
+                return Err(ApiError::NotFound);
+            }
+
+            let resource_name = r_dsl::resources
+                .find(resource)
+                .filter(r_dsl::deleted_at.is_null())
+                .select(r_dsl::name)
+                .first::<String>(conn)
+                .optional()?
+                .ok_or(ApiError::NotFound)?;
+
+            let bookings = b_dsl::bookings
+                .filter(b_dsl::resource_id.eq(resource))
+                .filter(b_dsl::deleted_at.is_null())
+                .order(b_dsl::booking_date.asc())
+                .load::<Booking>(conn)?;
+            let series = s_dsl::booking_series
+                .filter(s_dsl::resource_id.eq(resource))
+                .filter(s_dsl::deleted_at.is_null())
+                .load::<BookingSeries>(conn)?;
+
+            (resource_name, bookings, series)
+        }
+        None => {
+            let attended = a_dsl::booking_attendees
+                .filter(a_dsl::user_id.eq(feed.user_id))
+                .filter(a_dsl::status.eq(AttendeeStatus::Accepted))
+                .select(a_dsl::booking_id);
+            let bookings = b_dsl::bookings
+                .filter(
+                    b_dsl::user_id
+                        .eq(feed.user_id)
+                        .or(b_dsl::id.eq_any(attended)),
+                )
+                .filter(b_dsl::deleted_at.is_null())
+                .order(b_dsl::booking_date.asc())
+                .load::<Booking>(conn)?;
+            let series = s_dsl::booking_series
+                .filter(s_dsl::user_id.eq(feed.user_id))
+                .filter(s_dsl::deleted_at.is_null())
+                .load::<BookingSeries>(conn)?;
+
+            (format!("Bookings for {}", owner), bookings, series)
+        }
+    };
+
+    Ok(write_calendar(&name, bookings, series))
+}
+
+fn event_uid(id: &Uuid) -> String {
+    format!("{}@simple-booking", id)
+}
+
+fn write_booking(
+    ics: &mut IcsWriter,
+    booking: &Booking,
+    uid: &str,
+    recurrence_id: Option<DateTime<Utc>>,
+) {
+    ics.line("BEGIN:VEVENT");
+    ics.property("UID", uid);
+    ics.datetime("DTSTAMP", &booking.updated_at);
+    if let Some(recurrence) = recurrence_id {
+        ics.datetime("RECURRENCE-ID", &recurrence);
+    }
+    ics.datetime("DTSTART", &booking.booking_date);
+    ics.datetime("DTEND", &booking.end_date);
+    ics.text("SUMMARY", &booking.title);
+    if let Some(ref description) = booking.description {
+        ics.text("DESCRIPTION", description);
+    }
+    ics.property("STATUS", ical_status(booking.status));
+    ics.datetime("CREATED", &booking.created_at);
+    ics.datetime("LAST-MODIFIED", &booking.updated_at);
+    ics.line("END:VEVENT");
+}
+
+/// Writes a series as one recurring VEVENT (UID from the series id) plus an
+/// overriding VEVENT with a RECURRENCE-ID for every occurrence that differs.
+/// Generated occurrences that no longer exist are emitted as EXDATEs.
+fn write_series(ics: &mut IcsWriter, series: &BookingSeries, occurrences: &[Booking]) {
+    let uid = event_uid(&series.id);
+    let master_status = occurrences
+        .iter()
+        .find(|b| !b.is_override)
+        .map(|b| b.status)
+        .unwrap_or(BookingStatus::Confirmed);
+
+    let existing: HashSet<DateTime<Utc>> =
+        occurrences.iter().filter_map(|b| b.recurrence_id).collect();
+    let mut exdates: Vec<DateTime<Utc>> = series.exdates.iter().flatten().copied().collect();
+    let tz = series.timezone.parse::<Tz>().unwrap_or(Tz::UTC);
+    if let Ok(rule) = series.rrule.parse::<RecurrenceRule>()
+        && let Ok(starts) = rule.occurrences(series.dtstart, tz)
+    {
+        if !starts.contains(&series.dtstart) {
+            exdates.push(series.dtstart);
+        }
+        exdates.extend(starts.into_iter().filter(|s| !existing.contains(s)));
+    }
+    exdates.sort();
+    exdates.dedup();
+
+    ics.line("BEGIN:VEVENT");
+    ics.property("UID", &uid);
+    ics.datetime("DTSTAMP", &series.updated_at);
+    ics.zoned("DTSTART", &[series.dtstart], tz);
+    ics.zoned(
+        "DTEND",
+        &[series.dtstart + Duration::minutes(series.duration_minutes as i64)],
+        tz,
+    );
+    ics.property("RRULE", &series.rrule);
+    if !exdates.is_empty() {
+        ics.zoned("EXDATE", &exdates, tz);
+    }
+    ics.text("SUMMARY", &series.title);
+    if let Some(ref description) = series.description {
+        ics.text("DESCRIPTION", description);
+    }
+    ics.property("STATUS", ical_status(master_status));
+    ics.datetime("CREATED", &series.created_at);
+    ics.datetime("LAST-MODIFIED", &series.updated_at);
+    ics.line("END:VEVENT");
+
+    for occurrence in occurrences {
+        if occurrence.is_override || ical_status(occurrence.status) != ical_status(master_status) {
+            write_booking(ics, occurrence, &uid, occurrence.recurrence_id);
+        }
+    }
+}
+
+pub fn write_calendar(name: &str, bookings: Vec<Booking>, series: Vec<BookingSeries>) -> String {
+    let mut ics = IcsWriter::new(name);
+
+    let mut by_series: HashMap<Uuid, Vec<Booking>> = HashMap::new();
+    let mut singles = Vec::new();
+    for booking in bookings {
+        match booking.series_id {
+            Some(series_id) if series.iter().any(|s| s.id == series_id) => {
+                by_series.entry(series_id).or_default().push(booking)
+            }
+            _ => singles.push(booking),
+        }
+    }
+
+    for booking in &singles {
+        write_booking(&mut ics, booking, &event_uid(&booking.id), None);
+    }
+
+    for s in &series {
+        let occurrences = by_series.remove(&s.id).unwrap_or_default();
+        write_series(&mut ics, s, &occurrences);
+    }
+
+    ics.finish()
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportOutcome {
+    /// Preview only: the event would be imported.
+    Ready,
+    Imported,
+    Skipped,
+    Conflict,
+    /// The event breaks the resource's booking rules; see `violations`.
+    RuleViolation,
+    UnsupportedRecurrence,
+    Invalid,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportedEvent {
+    pub uid: Option<String>,
+    pub title: Option<String>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub rrule: Option<String>,
+    pub occurrences: usize,
+    pub outcome: ImportOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<BookingConflict>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub violations: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub created: Vec<Uuid>,
+}
+
+impl ImportedEvent {
+    fn rejected(event: &IcsEvent, outcome: ImportOutcome, message: String) -> Self {
+        ImportedEvent {
+            uid: event.text("UID"),
+            title: event.text("SUMMARY"),
+            starts_at: None,
+            ends_at: None,
+            rrule: event.get("RRULE").map(|p| p.value.clone()),
+            occurrences: 0,
+            outcome,
+            message: Some(message),
+            conflicts: Vec::new(),
+            violations: None,
+            created: Vec::new(),
+        }
+    }
+}
+
+/// An event reduced to what a booking or busy block needs.
+struct ImportCandidate {
+    uid: Option<String>,
+    title: String,
+    description: Option<String>,
+    starts_at: DateTime<Utc>,
+    ends_at: DateTime<Utc>,
+    /// Zone of DTSTART, which the rule is expanded in.
+    timezone: Tz,
+    rule: Option<RecurrenceRule>,
+    exdates: Vec<DateTime<Utc>>,
+}
+
+fn read_event(
+    event: &IcsEvent,
+    overridden: &HashMap<String, Vec<DateTime<Utc>>>,
+) -> Result<ImportCandidate, (ImportOutcome, String)> {
+    let invalid = |msg: String| (ImportOutcome::Invalid, msg);
+
+    if event
+        .get("STATUS")
+        .is_some_and(|p| p.value.eq_ignore_ascii_case("CANCELLED"))
+    {
+        return Err((ImportOutcome::Skipped, "Event is cancelled".into()));
+    }
+
+    let dtstart = event
+        .get("DTSTART")
+        .ok_or_else(|| invalid("Event has no DTSTART".into()))?;
+    let start = parse_date_value(dtstart, &dtstart.value).map_err(invalid)?;
+    let timezone = match dtstart.param("TZID") {
+        Some(tzid) => tzid
+            .trim_start_matches('/')
+            .parse::<Tz>()
+            .map_err(|_| invalid(format!("Unsupported time zone: {}", tzid)))?,
+        None => Tz::UTC,
+    };
+
+    let ends_at = if let Some(dtend) = event.get("DTEND") {
+        parse_date_value(dtend, &dtend.value)
+            .map_err(invalid)?
+            .to_utc()
+    } else if let Some(duration) = event.get("DURATION") {
+        start.to_utc() + parse_duration(&duration.value).map_err(invalid)?
+    } else if let IcsDate::Date(_) = start {
+        start.to_utc() + Duration::days(1)
+    } else {
+        return Err(invalid("Event has neither DTEND nor DURATION".into()));
+    };
+
+    if event.get("RDATE").is_some() {
+        return Err((
+            ImportOutcome::UnsupportedRecurrence,
+            "RDATE is not supported".into(),
+        ));
+    }
+
+    let rule = match event.get_all("RRULE").count() {
+        0 => None,
+        1 => Some(
+            event
+                .get("RRULE")
+                .map(|p| p.value.as_str())
+                .unwrap_or_default()
+                .parse::<RecurrenceRule>()
+                .map_err(|e| (ImportOutcome::UnsupportedRecurrence, e))?,
+        ),
+        _ => {
+            return Err((
+                ImportOutcome::UnsupportedRecurrence,
+                "Multiple RRULEs are not supported".into(),
+            ))
+        }
+    };
+
+    let uid = event.text("UID");
+    let mut exdates = Vec::new();
+    for property in event.get_all("EXDATE") {
+        for value in property.value.split(',') {
+            exdates.push(parse_date_value(property, value).map_err(invalid)?.to_utc());
+        }
+    }
+    if let Some(dates) = uid.as_ref().and_then(|u| overridden.get(u)) {
+        exdates.extend(dates);
+    }
+
+    let title = event
+        .text("SUMMARY")
+        .filter(|t| !t.trim().is_empty())
+        .unwrap_or_else(|| "Imported event".into())
+        .chars()
+        .take(255)
+        .collect();
+
+    Ok(ImportCandidate {
+        uid,
+        title,
+        description: event.text("DESCRIPTION"),
+        starts_at: start.to_utc(),
+        ends_at,
+        timezone,
+        rule,
+        exdates,
+    })
+}
+
+fn candidate_slots(candidate: &ImportCandidate) -> Result<Vec<Slot>, (ImportOutcome, String)> {
+    if candidate.ends_at <= candidate.starts_at {
+        return Err((
+            ImportOutcome::Invalid,
+            "Event must end after it starts".into(),
+        ));
+    }
+
+    let Some(ref rule) = candidate.rule else {
+        return Ok(vec![(candidate.starts_at, candidate.ends_at)]);
+    };
+
+    let duration = candidate.ends_at - candidate.starts_at;
+    let slots: Vec<Slot> = rule
+        .occurrences(candidate.starts_at, candidate.timezone)
+        .map_err(|e| (ImportOutcome::UnsupportedRecurrence, e))?
+        .into_iter()
+        .filter(|s| !candidate.exdates.contains(s))
+        .map(|s| (s, s + duration))
+        .collect();
+
+    if slots.is_empty() {
+        return Err((
+            ImportOutcome::Invalid,
+            "Recurrence rule does not produce any occurrences".into(),
+        ));
+    }
+
+    Ok(slots)
+}
+
+/// Holds the candidate to the rules the booking endpoints apply to requests.
+fn check_candidate(candidate: &ImportCandidate) -> Result<(), String> {
+    let mut v = Validator::default();
+    check_booking_text(
+        &mut v,
+        Some(&candidate.title),
+        candidate.description.as_deref(),
+    );
+    check_slot(&mut v, "end_date", (candidate.starts_at, candidate.ends_at));
+    v.finish().map_err(|e| match e {
+        ApiError::Validation(errors) => errors
+            .iter()
+            .map(|e| format!("{}: {}", e.field, e.message))
+            .collect::<Vec<_>>()
+            .join("; "),
+        other => other.to_string(),
+    })
+}
+
+fn commit_candidate(
+    conn: &mut PgConnection,
+    user: &User,
+    query: &ImportQuery,
+    candidate: &ImportCandidate,
+    slots: &[Slot],
+) -> Result<Vec<Uuid>, ApiError> {
+    use crate::schema::busy_blocks::dsl::*;
+
+    match query.mode {
+        ImportMode::Bookings => match candidate.rule {
+            Some(ref rule) => {
+                let (_, occurrences) = crate::bookings::service::create_series(
+                    conn,
+                    user,
+                    CreateSeriesRequest {
+                        resource_id: query.resource_id,
+                        title: candidate.title.clone(),
+                        description: candidate.description.clone(),
+                        rrule: rule.to_string(),
+                        dtstart: candidate.starts_at,
+                        duration_minutes: (candidate.ends_at - candidate.starts_at).num_minutes()
+                            as i32,
+                        exdates: Some(candidate.exdates.clone()),
+                        timezone: Some(candidate.timezone.name().to_string()),
+                    },
+                )?;
+                Ok(occurrences.into_iter().map(|b| b.id).collect())
+            }
+            None => {
+                let booking = crate::bookings::service::create_booking(
+                    conn,
+                    user,
+                    CreateBookingRequest {
+                        resource_id: query.resource_id,
+                        title: candidate.title.clone(),
+                        description: candidate.description.clone(),
+                        booking_date: candidate.starts_at,
+                        end_date: candidate.ends_at,
+                    },
+                )?;
+                Ok(vec![booking.id])
+            }
+        },
+        ImportMode::BusyBlocks => {
+            let rows: Vec<NewBusyBlock> = slots
+                .iter()
+                .map(|(start, end)| NewBusyBlock {
+                    user_id: user.id,
+                    resource_id: query.resource_id,
+                    summary: Some(candidate.title.clone()),
+                    starts_at: *start,
+                    ends_at: *end,
+                    source_uid: candidate.uid.clone(),
+                })
+                .collect();
+            Ok(diesel::insert_into(busy_blocks)
+                .values(&rows)
+                .returning(id)
+                .get_results::<Uuid>(conn)?)
+        }
+    }
+}
+
+/// Parses an .ics upload and reports, per VEVENT, what importing it into
+/// the resource would do. With `commit` the importable events are created;
+/// events that conflict, break the booking rules, are invalid or use
+/// unsupported recurrence are skipped and reported either way.
+///
+/// Overridden occurrences (VEVENTs with a RECURRENCE-ID) are imported as
+/// standalone events and excluded from their recurring master.
+pub fn import_calendar(
+    conn: &mut PgConnection,
+    user: &User,
+    query: ImportQuery,
+    input: &str,
+    commit: bool,
+) -> Result<Vec<ImportedEvent>, ApiError> {
+    if query.mode == ImportMode::BusyBlocks && !has_permission(conn, user.id, "resources:manage")? {
+        return Err(ApiError::Forbidden);
+    }
+
+    let events = parse_events(input).map_err(ApiError::Invalid)?;
+
+    let mut overridden: HashMap<String, Vec<DateTime<Utc>>> = HashMap::new();
+    for event in &events {
+        if let (Some(uid), Some(recurrence)) = (event.text("UID"), event.get("RECURRENCE-ID"))
+            && let Ok(date) = parse_date_value(recurrence, &recurrence.value)
+        {
+            overridden.entry(uid).or_default().push(date.to_utc());
+        }
+    }
+
+    conn.transaction(|conn| {
+        lock_resource(conn, query.resource_id)?;
+
+        let mut accepted: Vec<Slot> = Vec::new();
+        let mut report = Vec::with_capacity(events.len());
+
+        for event in &events {
+            let (candidate, slots) = match read_event(event, &overridden)
+                .and_then(|c| candidate_slots(&c).map(|slots| (c, slots)))
+            {
+                Ok(parsed) => parsed,
+                Err((outcome, message)) => {
+                    report.push(ImportedEvent::rejected(event, outcome, message));
+                    continue;
+                }
+            };
+
+            let mut result = ImportedEvent {
+                uid: candidate.uid.clone(),
+                title: Some(candidate.title.clone()),
+                starts_at: Some(candidate.starts_at),
+                ends_at: Some(candidate.ends_at),
+                rrule: candidate.rule.as_ref().map(|r| r.to_string()),
+                occurrences: slots.len(),
+                outcome: if commit {
+                    ImportOutcome::Imported
+                } else {
+                    ImportOutcome::Ready
+                },
+                message: None,
+                conflicts: Vec::new(),
+                violations: None,
+                created: Vec::new(),
+            };
+
+            if query.mode == ImportMode::Bookings
+                && let Err(msg) = check_candidate(&candidate)
+            {
+                result.outcome = ImportOutcome::Invalid;
+                result.message = Some(msg);
+                report.push(result);
+                continue;
+            }
+
+            // Imported bookings are new bookings and follow the same rules.
+            if query.mode == ImportMode::Bookings {
+                match enforce_rules(conn, user, user.id, query.resource_id, &slots, &[]) {
+                    Ok(()) => {}
+                    Err(ApiError::RuleViolations(violations)) => {
+                        result.outcome = ImportOutcome::RuleViolation;
+                        result.violations = Some(violations);
+                        report.push(result);
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+
+            result.conflicts = find_conflicts(conn, query.resource_id, &slots, &[])?;
+            let overlaps_file = !commit
+                && slots
+                    .iter()
+                    .any(|(s, e)| accepted.iter().any(|(a_s, a_e)| a_s < e && a_e > s));
+            if !result.conflicts.is_empty() || overlaps_file {
+                result.outcome = ImportOutcome::Conflict;
+                if overlaps_file {
+                    result.message = Some("Overlaps another event in the file".into());
+                }
+                report.push(result);
+                continue;
+            }
+
+            if commit {
+                match commit_candidate(conn, user, &query, &candidate, &slots) {
+                    Ok(created) => result.created = created,
+                    Err(ApiError::Invalid(msg)) => {
+                        result.outcome = ImportOutcome::Invalid;
+                        result.message = Some(msg);
+                    }
+                    Err(ApiError::Conflict(msg, _)) => {
+                        result.outcome = ImportOutcome::Conflict;
+                        result.message = Some(msg);
+                    }
+                    // Earlier events in the file can use up a weekly quota.
+                    Err(ApiError::RuleViolations(violations)) => {
+                        result.outcome = ImportOutcome::RuleViolation;
+                        result.violations = Some(violations);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+
+            accepted.extend(slots);
+            report.push(result);
+        }
+
+        Ok(report)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn candidate(lines: &str) -> Result<ImportCandidate, (ImportOutcome, String)> {
+        let input = format!(
+            "BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:one\n{}\nEND:VEVENT\nEND:VCALENDAR\n",
+            lines
+        );
+        let events = parse_events(&input).unwrap();
+        read_event(&events[0], &HashMap::new())
+    }
+
+    #[test]
+    fn expands_imported_series_in_their_zone_without_exdates() {
+        let at = |month, day, hour| Utc.with_ymd_and_hms(2026, month, day, hour, 0, 0).unwrap();
+
+        let zoned = candidate(
+            "DTSTART;TZID=Europe/Oslo:20261018T090000\n\
+             DTEND;TZID=Europe/Oslo:20261018T100000\n\
+             RRULE:FREQ=WEEKLY;COUNT=3\n\
+             EXDATE;TZID=Europe/Oslo:20261025T090000",
+        )
+        .unwrap();
+        assert_eq!(zoned.timezone, Tz::Europe__Oslo);
+        assert_eq!(
+            candidate_slots(&zoned).unwrap(),
+            [(at(10, 18, 7), at(10, 18, 8)), (at(11, 1, 8), at(11, 1, 9))]
+        );
+
+        let utc_exdate = candidate(
+            "DTSTART;TZID=Europe/Oslo:20261018T090000\n\
+             DURATION:PT1H\n\
+             RRULE:FREQ=WEEKLY;COUNT=3\n\
+             EXDATE:20261018T070000Z",
+        )
+        .unwrap();
+        assert_eq!(
+            candidate_slots(&utc_exdate).unwrap(),
+            [(at(10, 25, 8), at(10, 25, 9)), (at(11, 1, 8), at(11, 1, 9))]
+        );
+    }
+
+    #[test]
+    fn rejects_events_that_cannot_be_imported() {
+        let outcome = |lines| candidate(lines).err().map(|(outcome, _)| outcome);
+        assert_eq!(
+            outcome("STATUS:CANCELLED\nDTSTART:20260101T090000Z\nDURATION:PT1H"),
+            Some(ImportOutcome::Skipped)
+        );
+        assert_eq!(
+            outcome("DTSTART:20260101T090000Z"),
+            Some(ImportOutcome::Invalid)
+        );
+        assert_eq!(
+            outcome("DTSTART:20260101T090000Z\nDURATION:PT1H\nRRULE:FREQ=YEARLY;COUNT=2"),
+            Some(ImportOutcome::UnsupportedRecurrence)
+        );
+    }
+}
@@ -0,0 +1,666 @@
+//! Application settings, loaded once at startup.
+//!
+//! Values start from built-in defaults, are overridden by an optional TOML
+//! file and then by environment variables. The file is the one named by
+//! `CONFIG_FILE`, or `config.toml` in the working directory if it exists:
+//!
+//! ```toml
+//! [database]
+//! url = "postgres://postgres:password@db/simple_booking"
+//! pool_size = 10
+//! migrations = "run"
+//!
+//! [server]
+//! bind = "0.0.0.0:3000"
+//!
+//! [cors]
+//! allowed_origins = ["https://booking.example.com"]
+//!
+//! [auth]
+//! token_lifetime_secs = 3600
+//!
+//! [metrics]
+//! bind = "0.0.0.0:9100"
+//! token = "scrape-secret"
+//!
+//! [logging]
+//! level = "info,backend=debug"
+//! format = "json"
+//!
+//! [features]
+//! scheduler = true
+//!
+//! [scheduler.intervals]
+//! reap_holds = 30
+//! ```
+//!
+//! Every setting is validated before the server starts and all problems are
+//! reported together. Handlers take the settings as `web::Data<Config>` and
+//! hand services the sections they need; the scheduler and the command-line
+//! tools are given the same instance by `main`.
+//!
+//! | Section      | Environment variables                                          |
+//! |--------------|----------------------------------------------------------------|
+//! | `database`   | `DATABASE_URL`, `DATABASE_POOL_SIZE`, `DATABASE_MIGRATIONS`    |
+//! | `server`     | `BIND_ADDRESS`                                                 |
+//! | `cors`       | `CORS_ALLOWED_ORIGINS` (comma separated), `CORS_MAX_AGE_SECS`  |
+//! | `auth`       | `JWT_SECRET`, `JWT_EXPIRE_SECONDS`                             |
+//! | `logging`    | `RUST_LOG`, `LOG_FORMAT`                                       |
+//! | `metrics`    | `METRICS_ENABLED`, `METRICS_BIND_ADDRESS`, `METRICS_TOKEN`     |
+//! | `bookings`   | `BOOKING_HOLD_MINUTES`, `NO_SHOW_GRACE_MINUTES`,               |
+//! |              | `APPROVAL_ESCALATION_HOURS`, `WAITLIST_HOLD_MINUTES`,          |
+//! |              | `CHECKIN_OPENS_MINUTES_BEFORE`, `CHECKIN_CODE_TTL_SECONDS`     |
+//! | `features`   | `SCHEDULER_ENABLED`, `CHECKIN_MARK_LATE_AS_DELAYED`            |
+//! | `scheduler`  | `SCHEDULER_<JOB>_INTERVAL_SECS`                                |
+//! | `password`   | see [`crate::users::password`]                                 |
+//! | `hashing`    | see [`crate::users::hashing`]                                  |
+
+use crate::users::hashing::HashingConfig;
+use crate::users::password::PasswordPolicy;
+use actix_cors::Cors;
+use actix_web::http::header;
+use anyhow::{anyhow, bail, Context};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::net::ToSocketAddrs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub database: DatabaseConfig,
+    pub server: ServerConfig,
+    pub cors: CorsConfig,
+    pub auth: AuthConfig,
+    pub logging: LoggingConfig,
+    pub metrics: MetricsConfig,
+    pub bookings: BookingsConfig,
+    pub features: FeaturesConfig,
+    pub scheduler: SchedulerConfig,
+    pub password: PasswordPolicy,
+    pub hashing: HashingConfig,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub pool_size: u32,
+    pub migrations: MigrationMode,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            url: String::new(),
+            pool_size: 10,
+            migrations: MigrationMode::Run,
+        }
+    }
+}
+
+/// What the server does with the embedded migrations when it starts.
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationMode {
+    /// Applies pending migrations.
+    Run,
+    /// Refuses to start unless the database matches the embedded migrations.
+    Check,
+    /// Leaves the database alone.
+    Off,
+}
+
+impl FromStr for MigrationMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "run" => Ok(MigrationMode::Run),
+            "check" => Ok(MigrationMode::Check),
+            "off" => Ok(MigrationMode::Off),
+            _ => Err("expected run, check or off".into()),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// `host:port` the API listens on.
+    pub bind: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: "0.0.0.0:3000".into(),
+        }
+    }
+}
+
+/// Browser origins allowed to call the API. Empty allows none, `["*"]` any.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>,
+    pub max_age_secs: usize,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            allowed_origins: Vec::new(),
+            max_age_secs: 3600,
+        }
+    }
+}
+
+impl CorsConfig {
+    pub fn middleware(&self) -> Cors {
+        let mut cors = Cors::default()
+            .allowed_methods(["GET", "POST", "PUT", "PATCH", "DELETE"])
+            .allowed_headers([
+                header::AUTHORIZATION,
+                header::CONTENT_TYPE,
+                header::ACCEPT,
+                crate::telemetry::REQUEST_ID.clone(),
+            ])
+            .expose_headers([crate::telemetry::REQUEST_ID.clone()])
+            .max_age(self.max_age_secs);
+        for origin in &self.allowed_origins {
+            cors = if origin == "*" {
+                cors.allow_any_origin()
+            } else {
+                cors.allowed_origin(origin)
+            };
+        }
+        cors
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub jwt_secret: String,
+    pub token_lifetime_secs: i64,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            jwt_secret: String::new(),
+            token_lifetime_secs: 3600,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Filter directives in `RUST_LOG` syntax, e.g. `info,backend=debug`.
+    pub level: String,
+    pub format: LogFormat,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: "info".into(),
+            format: LogFormat::Json,
+        }
+    }
+}
+
+/// How log lines are written to standard output.
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One JSON object per line, for log collectors.
+    Json,
+    /// Human readable lines, for local development.
+    Text,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(LogFormat::Json),
+            "text" => Ok(LogFormat::Text),
+            _ => Err("expected json or text".into()),
+        }
+    }
+}
+
+/// The Prometheus endpoint, served apart from the API.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    /// `host:port` serving `/metrics`; must differ from `server.bind`.
+    pub bind: String,
+    /// Bearer token scrapers must send; without one the endpoint is open.
+    pub token: Option<String>,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig {
+            enabled: true,
+            bind: "127.0.0.1:9100".into(),
+            token: None,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BookingsConfig {
+    /// How long a tentative hold blocks its slot before it is released.
+    pub hold_minutes: i64,
+    /// How long after the start a booking without check-in becomes a no-show.
+    pub no_show_grace_minutes: i64,
+    /// How long an approval may stay pending before it is escalated.
+    pub approval_escalation_hours: i64,
+    /// How long a promoted waitlist entry has to accept its offer.
+    pub waitlist_hold_minutes: i64,
+    /// How long before the start check-in opens.
+    pub checkin_opens_minutes_before: i64,
+    /// How long a check-in QR code stays valid.
+    pub checkin_code_ttl_secs: i64,
+}
+
+impl Default for BookingsConfig {
+    fn default() -> Self {
+        BookingsConfig {
+            hold_minutes: 10,
+            no_show_grace_minutes: 15,
+            approval_escalation_hours: 24,
+            waitlist_hold_minutes: 15,
+            checkin_opens_minutes_before: 15,
+            checkin_code_ttl_secs: 120,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeaturesConfig {
+    /// Runs the background jobs in this process.
+    pub scheduler: bool,
+    /// A check-in after the start time moves the booking to `Delayed`.
+    pub checkin_mark_late_as_delayed: bool,
+}
+
+impl Default for FeaturesConfig {
+    fn default() -> Self {
+        FeaturesConfig {
+            scheduler: true,
+            checkin_mark_late_as_delayed: true,
+        }
+    }
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulerConfig {
+    /// Per-job interval overrides in seconds, keyed by job name; `0` turns a
+    /// job off.
+    pub intervals: HashMap<String, u64>,
+}
+
+impl Config {
+    /// Reads the config file and environment and validates the result.
+    pub fn load() -> anyhow::Result<Config> {
+        let mut config = match config_file()? {
+            Some(path) => Config::from_file(&path)?,
+            None => Config::default(),
+        };
+
+        let mut problems = config.apply_env();
+        problems.extend(config.validate());
+        if !problems.is_empty() {
+            bail!("Invalid configuration:\n  - {}", problems.join("\n  - "));
+        }
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> anyhow::Result<Config> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read config file {}", path.display()))?;
+        toml::from_str(&text).map_err(|e| anyhow!("Invalid config file {}: {}", path.display(), e))
+    }
+
+    /// Applies environment overrides and returns the values that did not parse.
+    fn apply_env(&mut self) -> Vec<String> {
+        let mut env = Env::default();
+
+        env.string("DATABASE_URL", &mut self.database.url);
+        env.parse("DATABASE_POOL_SIZE", &mut self.database.pool_size);
+        env.parse("DATABASE_MIGRATIONS", &mut self.database.migrations);
+        env.string("BIND_ADDRESS", &mut self.server.bind);
+        if let Some(origins) = read_env("CORS_ALLOWED_ORIGINS") {
+            self.cors.allowed_origins = origins
+                .split(',')
+                .map(str::trim)
+                .filter(|o| !o.is_empty())
+                .map(String::from)
+                .collect();
+        }
+        env.parse("CORS_MAX_AGE_SECS", &mut self.cors.max_age_secs);
+
+        env.string("JWT_SECRET", &mut self.auth.jwt_secret);
+        env.parse("JWT_EXPIRE_SECONDS", &mut self.auth.token_lifetime_secs);
+
+        env.string("RUST_LOG", &mut self.logging.level);
+        env.parse("LOG_FORMAT", &mut self.logging.format);
+
+        env.flag("METRICS_ENABLED", &mut self.metrics.enabled);
+        env.string("METRICS_BIND_ADDRESS", &mut self.metrics.bind);
+        if let Some(token) = read_env("METRICS_TOKEN") {
+            self.metrics.token = Some(token);
+        }
+
+        let bookings = &mut self.bookings;
+        env.parse("BOOKING_HOLD_MINUTES", &mut bookings.hold_minutes);
+        env.parse("NO_SHOW_GRACE_MINUTES", &mut bookings.no_show_grace_minutes);
+        env.parse(
+            "APPROVAL_ESCALATION_HOURS",
+            &mut bookings.approval_escalation_hours,
+        );
+        env.parse("WAITLIST_HOLD_MINUTES", &mut bookings.waitlist_hold_minutes);
+        env.parse(
+            "CHECKIN_OPENS_MINUTES_BEFORE",
+            &mut bookings.checkin_opens_minutes_before,
+        );
+        env.parse(
+            "CHECKIN_CODE_TTL_SECONDS",
+            &mut bookings.checkin_code_ttl_secs,
+        );
+
+        env.flag("SCHEDULER_ENABLED", &mut self.features.scheduler);
+        env.flag(
+            "CHECKIN_MARK_LATE_AS_DELAYED",
+            &mut self.features.checkin_mark_late_as_delayed,
+        );
+        for (key, _) in std::env::vars() {
+            if let Some(job) = key
+                .strip_prefix("SCHEDULER_")
+                .and_then(|rest| rest.strip_suffix("_INTERVAL_SECS"))
+            {
+                let entry = self
+                    .scheduler
+                    .intervals
+                    .entry(job.to_ascii_lowercase())
+                    .or_default();
+                env.parse(&key, entry);
+            }
+        }
+
+        let password = &mut self.password;
+        env.parse("PASSWORD_MIN_LENGTH", &mut password.min_length);
+        env.parse("PASSWORD_MAX_LENGTH", &mut password.max_length);
+        env.flag(
+            "PASSWORD_REQUIRE_UPPERCASE",
+            &mut password.require_uppercase,
+        );
+        env.flag(
+            "PASSWORD_REQUIRE_LOWERCASE",
+            &mut password.require_lowercase,
+        );
+        env.flag("PASSWORD_REQUIRE_DIGIT", &mut password.require_digit);
+        env.flag("PASSWORD_REQUIRE_SYMBOL", &mut password.require_symbol);
+        env.parse("PASSWORD_MIN_ENTROPY_BITS", &mut password.min_entropy_bits);
+        if let Some(path) = read_env("PASSWORD_BREACHED_LIST") {
+            password.breached_list = Some(PathBuf::from(path));
+        }
+
+        let hashing = &mut self.hashing;
+        env.parse("ARGON2_MEMORY_KIB", &mut hashing.memory_kib);
+        env.parse("ARGON2_ITERATIONS", &mut hashing.iterations);
+        env.parse("ARGON2_PARALLELISM", &mut hashing.parallelism);
+        if let Some(pepper) = read_env("PASSWORD_PEPPER") {
+            hashing.pepper = Some(pepper);
+        }
+        env.string("PASSWORD_PEPPER_ID", &mut hashing.pepper_id);
+
+        env.problems
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut check = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_string());
+            }
+        };
+
+        check(
+            !self.database.url.trim().is_empty(),
+            "database.url (DATABASE_URL) must be set",
+        );
+        check(
+            self.database.pool_size > 0,
+            "database.pool_size (DATABASE_POOL_SIZE) must be at least 1",
+        );
+        check(
+            self.server
+                .bind
+                .to_socket_addrs()
+                .is_ok_and(|mut addrs| addrs.next().is_some()),
+            "server.bind (BIND_ADDRESS) must be a host:port address",
+        );
+        let origins = &self.cors.allowed_origins;
+        check(
+            !(origins.len() > 1 && origins.iter().any(|o| o == "*")),
+            "cors.allowed_origins (CORS_ALLOWED_ORIGINS) cannot mix \"*\" with other origins",
+        );
+        for origin in origins.iter().filter(|o| *o != "*") {
+            check(
+                is_origin(origin),
+                &format!(
+                    "cors.allowed_origins (CORS_ALLOWED_ORIGINS): {:?} is not an origin like https://example.com",
+                    origin
+                ),
+            );
+        }
+
+        check(
+            !self.auth.jwt_secret.is_empty(),
+            "auth.jwt_secret (JWT_SECRET) must be set",
+        );
+        check(
+            self.auth.token_lifetime_secs > 0,
+            "auth.token_lifetime_secs (JWT_EXPIRE_SECONDS) must be positive",
+        );
+        if self.metrics.enabled {
+            check(
+                self.metrics
+                    .bind
+                    .to_socket_addrs()
+                    .is_ok_and(|mut addrs| addrs.next().is_some()),
+                "metrics.bind (METRICS_BIND_ADDRESS) must be a host:port address",
+            );
+            check(
+                self.metrics.bind != self.server.bind,
+                "metrics.bind (METRICS_BIND_ADDRESS) must differ from server.bind",
+            );
+        }
+        check(
+            self.metrics.token.as_ref().is_none_or(|t| !t.is_empty()),
+            "metrics.token (METRICS_TOKEN) must not be empty when given",
+        );
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&self.logging.level) {
+            check(false, &format!("logging.level (RUST_LOG): {}", e));
+        }
+
+        let bookings = &self.bookings;
+        check(
+            bookings.hold_minutes > 0,
+            "bookings.hold_minutes (BOOKING_HOLD_MINUTES) must be positive",
+        );
+        check(
+            bookings.no_show_grace_minutes >= 0,
+            "bookings.no_show_grace_minutes (NO_SHOW_GRACE_MINUTES) must not be negative",
+        );
+        check(
+            bookings.approval_escalation_hours > 0,
+            "bookings.approval_escalation_hours (APPROVAL_ESCALATION_HOURS) must be positive",
+        );
+        check(
+            bookings.waitlist_hold_minutes > 0,
+            "bookings.waitlist_hold_minutes (WAITLIST_HOLD_MINUTES) must be positive",
+        );
+        check(
+            bookings.checkin_opens_minutes_before >= 0,
+            "bookings.checkin_opens_minutes_before (CHECKIN_OPENS_MINUTES_BEFORE) must not be negative",
+        );
+        check(
+            bookings.checkin_code_ttl_secs > 0,
+            "bookings.checkin_code_ttl_secs (CHECKIN_CODE_TTL_SECONDS) must be positive",
+        );
+
+        for job in self.scheduler.intervals.keys() {
+            check(
+                crate::scheduler::job_names().any(|name| name == job),
+                &format!(
+                    "scheduler.intervals: unknown job {:?}; known jobs are {}",
+                    job,
+                    crate::scheduler::job_names().collect::<Vec<_>>().join(", ")
+                ),
+            );
+        }
+
+        let password = &self.password;
+        check(
+            password.min_length > 0,
+            "password.min_length (PASSWORD_MIN_LENGTH) must be at least 1",
+        );
+        check(
+            password.max_length >= password.min_length,
+            "password.max_length (PASSWORD_MAX_LENGTH) must not be below the minimum length",
+        );
+        check(
+            password.min_entropy_bits >= 0.0,
+            "password.min_entropy_bits (PASSWORD_MIN_ENTROPY_BITS) must not be negative",
+        );
+
+        let hashing = &self.hashing;
+        if let Err(e) = argon2::Params::new(
+            hashing.memory_kib,
+            hashing.iterations,
+            hashing.parallelism,
+            None,
+        ) {
+            check(
+                false,
+                &format!(
+                    "hashing (ARGON2_MEMORY_KIB, ARGON2_ITERATIONS, ARGON2_PARALLELISM): {}",
+                    e
+                ),
+            );
+        }
+        check(
+            (1..=8).contains(&hashing.pepper_id.len()),
+            "hashing.pepper_id (PASSWORD_PEPPER_ID) must be 1 to 8 bytes long",
+        );
+        check(
+            hashing.pepper.as_ref().is_none_or(|p| !p.is_empty()),
+            "hashing.pepper (PASSWORD_PEPPER) must not be empty when given",
+        );
+
+        problems
+    }
+}
+
+/// Keeps `config` for the rest of the process, so the server, the scheduler
+/// and the command-line tools can share it.
+pub fn init(config: Config) -> &'static Config {
+    CONFIG.get_or_init(|| config)
+}
+
+/// `CONFIG_FILE` if set, which must then exist, else `config.toml` if present.
+fn config_file() -> anyhow::Result<Option<PathBuf>> {
+    match read_env("CONFIG_FILE") {
+        Some(path) => {
+            let path = PathBuf::from(path);
+            if !path.is_file() {
+                bail!("CONFIG_FILE {} does not exist", path.display());
+            }
+            Ok(Some(path))
+        }
+        None => {
+            let path = PathBuf::from("config.toml");
+            Ok(path.is_file().then_some(path))
+        }
+    }
+}
+
+/// An environment variable, treating an empty value as unset.
+fn read_env(key: &str) -> Option<String> {
+    std::env::var(key).ok().filter(|v| !v.trim().is_empty())
+}
+
+/// Collects environment overrides, remembering the ones that did not parse.
+#[derive(Default)]
+struct Env {
+    problems: Vec<String>,
+}
+
+impl Env {
+    fn string(&mut self, key: &str, target: &mut String) {
+        if let Some(value) = read_env(key) {
+            *target = value;
+        }
+    }
+
+    fn parse<T: FromStr>(&mut self, key: &str, target: &mut T)
+    where
+        T::Err: Display,
+    {
+        if let Some(value) = read_env(key) {
+            match value.trim().parse() {
+                Ok(parsed) => *target = parsed,
+                Err(e) => self
+                    .problems
+                    .push(format!("{}: cannot use {:?}: {}", key, value, e)),
+            }
+        }
+    }
+
+    fn flag(&mut self, key: &str, target: &mut bool) {
+        if let Some(value) = read_env(key) {
+            match value.trim().to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => *target = true,
+                "0" | "false" | "no" | "off" => *target = false,
+                _ => self
+                    .problems
+                    .push(format!("{}: {:?} is not true or false", key, value)),
+            }
+        }
+    }
+}
+
+/// `scheme://host[:port]` without a path, as browsers send in `Origin`. Any
+/// scheme is allowed, so app shells such as `tauri://localhost` can be listed.
+fn is_origin(value: &str) -> bool {
+    let Some((scheme, rest)) = value.split_once("://") else {
+        return false;
+    };
+    let scheme_ok = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    scheme_ok
+        && !rest.is_empty()
+        && !rest
+            .chars()
+            .any(|c| matches!(c, '/' | '?' | '#') || c.is_whitespace())
+}
@@ -0,0 +1,189 @@
+//! The one error type handlers and services return.
+//!
+//! Every variant renders as an RFC 7807 `application/problem+json` body with a
+//! stable `code` clients can match on. Internal errors are logged with their
+//! full cause chain but only ever described to the client in general terms.
+
+use actix_web::error::{BlockingError, JsonPayloadError, PathError, QueryPayloadError};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::fmt;
+
+/// One rejected input field.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ApiError {
+    /// No usable bearer token was sent; carries what was wrong with it.
+    MissingCredentials(&'static str),
+    Unauthorized,
+    InvalidCredentials,
+    Forbidden,
+    /// The credentials are right but the account is locked until then.
+    Locked(DateTime<Utc>),
+    NotFound,
+    Invalid(String),
+    /// Input failed validation; carries every rejected field.
+    Validation(Vec<FieldError>),
+    Conflict(String, Value),
+    /// The request breaks one or more booking rules; carries every violation.
+    RuleViolations(Value),
+    Internal(anyhow::Error),
+}
+
+impl From<diesel::result::Error> for ApiError {
+    fn from(e: diesel::result::Error) -> Self {
+        ApiError::Internal(e.into())
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        ApiError::Internal(e)
+    }
+}
+
+impl From<BlockingError> for ApiError {
+    fn from(e: BlockingError) -> Self {
+        ApiError::Internal(e.into())
+    }
+}
+
+impl ApiError {
+    /// Stable, machine-readable identifier of the kind of problem.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::MissingCredentials(_) => "missing_credentials",
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::InvalidCredentials => "invalid_credentials",
+            ApiError::Forbidden => "forbidden",
+            ApiError::Locked(_) => "account_locked",
+            ApiError::NotFound => "not_found",
+            ApiError::Invalid(_) => "invalid_request",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::Conflict(..) => "conflict",
+            ApiError::RuleViolations(_) => "rule_violations",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    fn detail(&self) -> String {
+        match self {
+            ApiError::MissingCredentials(reason) => reason.to_string(),
+            ApiError::Unauthorized => "Invalid or expired token".into(),
+            ApiError::InvalidCredentials => "Invalid username/email or password".into(),
+            ApiError::Forbidden => "Permission denied".into(),
+            ApiError::Locked(until) => format!("Account is locked until {}", until.to_rfc3339()),
+            ApiError::NotFound => "Not found".into(),
+            ApiError::Invalid(msg) | ApiError::Conflict(msg, _) => msg.clone(),
+            ApiError::Validation(_) => "One or more fields are invalid".into(),
+            ApiError::RuleViolations(_) => "The booking breaks the rules of this resource".into(),
+            ApiError::Internal(_) => "Internal server error".into(),
+        }
+    }
+
+    fn problem(&self, detail: String) -> HttpResponse {
+        let status = self.status_code();
+        let mut body = Map::new();
+        body.insert("type".into(), json!("about:blank"));
+        body.insert(
+            "title".into(),
+            json!(status.canonical_reason().unwrap_or("Error")),
+        );
+        body.insert("status".into(), json!(status.as_u16()));
+        body.insert("detail".into(), json!(detail));
+        body.insert("code".into(), json!(self.code()));
+        match self {
+            ApiError::Validation(errors) => {
+                body.insert("errors".into(), json!(errors));
+            }
+            ApiError::Conflict(_, conflicts) => {
+                body.insert("conflicts".into(), conflicts.clone());
+            }
+            ApiError::RuleViolations(violations) => {
+                body.insert("violations".into(), violations.clone());
+            }
+            _ => {}
+        }
+
+        HttpResponse::build(status)
+            .content_type("application/problem+json")
+            .json(Value::Object(body))
+    }
+
+    /// Renders the error for a handler; `context` describes what the handler
+    /// was doing and is what the client sees when the failure is internal.
+    pub fn into_response(self, context: &str) -> HttpResponse {
+        match self {
+            ApiError::Internal(ref e) => {
+                tracing::error!(error = format!("{:#}", e), "{}", context);
+                self.problem(context.to_string())
+            }
+            _ => self.error_response(),
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Internal(e) => write!(f, "{:#}", e),
+            _ => f.write_str(&self.detail()),
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::MissingCredentials(_)
+            | ApiError::Unauthorized
+            | ApiError::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden | ApiError::Locked(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::Invalid(_) => StatusCode::BAD_REQUEST,
+            ApiError::Validation(_) | ApiError::RuleViolations(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            ApiError::Conflict(..) => StatusCode::CONFLICT,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if let ApiError::Internal(e) = self {
+            tracing::error!(error = format!("{:#}", e), "internal error");
+        }
+        self.problem(self.detail())
+    }
+}
+
+/// Makes malformed JSON bodies, query strings and path segments answer with
+/// the same problem bodies as everything else.
+pub fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    ApiError::Invalid(err.to_string()).into()
+}
+
+pub fn query_error_handler(err: QueryPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    ApiError::Invalid(err.to_string()).into()
+}
+
+pub fn path_error_handler(err: PathError, _req: &HttpRequest) -> actix_web::Error {
+    ApiError::Invalid(err.to_string()).into()
+}
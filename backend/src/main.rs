@@ -1,14 +1,59 @@
 extern crate core;
 
+mod admin;
+mod booking_rules;
+mod bookings;
+mod calendar;
+mod config;
+mod errors;
+mod metrics;
+mod migrations;
 mod models;
+mod notifications;
+mod resources;
+mod scheduler;
 mod schema;
 mod services;
+mod telemetry;
 mod users;
+mod validation;
+mod waitlist;
 
+use crate::booking_rules::{
+    create_booking_rule_endpoint, delete_booking_rule_endpoint, get_booking_rules_endpoint,
+    update_booking_rule_endpoint,
+};
+use crate::bookings::{
+    approve_booking_endpoint, approve_delay_endpoint, cancel_booking_endpoint,
+    cancel_series_endpoint, check_in_code_endpoint, check_in_endpoint, check_out_endpoint,
+    confirm_hold_endpoint, create_booking_endpoint, create_hold_endpoint, create_series_endpoint,
+    delay_booking_endpoint, delete_booking_endpoint, get_approval_queue_endpoint,
+    get_attendees_endpoint, get_booking_endpoint, get_cancellation_stats_endpoint,
+    get_delay_requests_endpoint, get_series_endpoint, get_user_bookings_endpoint,
+    invite_attendee_endpoint, reject_booking_endpoint, reject_delay_endpoint,
+    remove_attendee_endpoint, respond_to_booking_endpoint, scan_check_in_endpoint,
+    search_bookings_endpoint, update_booking_endpoint, update_series_endpoint,
+};
+use crate::calendar::{
+    calendar_feed_endpoint, create_feed_endpoint, get_feeds_endpoint, import_endpoint,
+    preview_import_endpoint, revoke_feed_endpoint,
+};
+use crate::config::Config;
+use crate::notifications::{get_notifications_endpoint, mark_notification_read_endpoint};
+use crate::resources::{
+    create_resource_endpoint, delete_cancellation_policy_endpoint,
+    get_cancellation_policy_endpoint, get_resource_endpoint, get_resources_endpoint,
+    set_cancellation_policy_endpoint, update_resource_endpoint,
+};
 use crate::users::{
-    create_user_endpoint, get_users_endpoint, sign_in_endpoint, update_user_endpoint,
-    update_user_password_endpoint, users_verify_token_endpoint,
+    create_user_endpoint, get_current_user_endpoint, get_users_endpoint, sign_in_endpoint,
+    update_user_endpoint, update_user_password_endpoint, users_verify_token_endpoint,
 };
+use crate::waitlist::{
+    accept_waitlist_offer_endpoint, decline_waitlist_offer_endpoint, get_waitlist_endpoint,
+    join_waitlist_endpoint, leave_waitlist_endpoint,
+};
+use actix_web::middleware::from_fn;
 use actix_web::{web, App, HttpServer};
 use diesel::pg::PgConnection;
 use diesel::r2d2;
@@ -21,25 +66,113 @@ pub type DbPool = r2d2::Pool<ConnectionManager<PgConnection>>;
 #[actix_web::main]
 async fn main() -> anyhow::Result<()> {
     dotenv().ok();
-    let database_url = env::var("DATABASE_URL")?;
 
-    let manager = ConnectionManager::<PgConnection>::new(&database_url);
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("hash-benchmark") {
+        return users::hashing::run_benchmark(&args[1..]);
+    }
+    let config = config::init(Config::load()?);
+    match args.first().map(String::as_str) {
+        Some("migrate") => {
+            telemetry::init_command(&config.logging);
+            return migrations::command(config, &args[1..]);
+        }
+        Some("admin") => {
+            telemetry::init_command(&config.logging);
+            migrations::on_startup(config)?;
+            return admin::command(config, &args[1..]);
+        }
+        _ => {}
+    }
+
+    telemetry::init(&config.logging);
+    migrations::on_startup(config)?;
+
+    let manager = ConnectionManager::<PgConnection>::new(&config.database.url);
 
     let pool: DbPool = r2d2::Pool::builder()
+        .max_size(config.database.pool_size)
+        .event_handler(Box::new(metrics::PoolEvents))
         .build(manager)
         .expect("Failed to create DB pool.");
 
+    scheduler::spawn(pool.clone(), config);
+    if let Some(server) = metrics::serve(&config.metrics, pool.clone())? {
+        actix_web::rt::spawn(server);
+    }
+
+    let config_data = web::Data::new(config.clone());
     HttpServer::new(move || {
         App::new()
+            .wrap(config.cors.middleware())
+            .wrap(from_fn(telemetry::trace_requests))
             .app_data(web::Data::new(pool.clone()))
+            .app_data(config_data.clone())
+            .app_data(web::JsonConfig::default().error_handler(errors::json_error_handler))
+            .app_data(web::QueryConfig::default().error_handler(errors::query_error_handler))
+            .app_data(web::PathConfig::default().error_handler(errors::path_error_handler))
             .service(create_user_endpoint)
             .service(get_users_endpoint)
+            .service(get_current_user_endpoint)
             .service(sign_in_endpoint)
             .service(users_verify_token_endpoint)
             .service(update_user_endpoint)
             .service(update_user_password_endpoint)
+            .service(create_resource_endpoint)
+            .service(get_resources_endpoint)
+            .service(get_resource_endpoint)
+            .service(update_resource_endpoint)
+            .service(get_cancellation_policy_endpoint)
+            .service(set_cancellation_policy_endpoint)
+            .service(delete_cancellation_policy_endpoint)
+            .service(create_booking_rule_endpoint)
+            .service(get_booking_rules_endpoint)
+            .service(update_booking_rule_endpoint)
+            .service(delete_booking_rule_endpoint)
+            .service(create_hold_endpoint)
+            .service(confirm_hold_endpoint)
+            .service(get_approval_queue_endpoint)
+            .service(approve_booking_endpoint)
+            .service(reject_booking_endpoint)
+            .service(scan_check_in_endpoint)
+            .service(check_in_code_endpoint)
+            .service(check_in_endpoint)
+            .service(check_out_endpoint)
+            .service(cancel_booking_endpoint)
+            .service(cancel_series_endpoint)
+            .service(get_cancellation_stats_endpoint)
+            .service(delay_booking_endpoint)
+            .service(get_delay_requests_endpoint)
+            .service(approve_delay_endpoint)
+            .service(reject_delay_endpoint)
+            .service(create_series_endpoint)
+            .service(get_series_endpoint)
+            .service(update_series_endpoint)
+            .service(get_user_bookings_endpoint)
+            .service(get_attendees_endpoint)
+            .service(invite_attendee_endpoint)
+            .service(respond_to_booking_endpoint)
+            .service(remove_attendee_endpoint)
+            .service(search_bookings_endpoint)
+            .service(create_booking_endpoint)
+            .service(get_booking_endpoint)
+            .service(update_booking_endpoint)
+            .service(delete_booking_endpoint)
+            .service(calendar_feed_endpoint)
+            .service(create_feed_endpoint)
+            .service(get_feeds_endpoint)
+            .service(revoke_feed_endpoint)
+            .service(preview_import_endpoint)
+            .service(import_endpoint)
+            .service(join_waitlist_endpoint)
+            .service(get_waitlist_endpoint)
+            .service(accept_waitlist_offer_endpoint)
+            .service(decline_waitlist_offer_endpoint)
+            .service(leave_waitlist_endpoint)
+            .service(get_notifications_endpoint)
+            .service(mark_notification_read_endpoint)
     })
-    .bind(("0.0.0.0", 3000))?
+    .bind(&config.server.bind)?
     .run()
     .await?;
     Ok(())
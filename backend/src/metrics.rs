@@ -0,0 +1,171 @@
+//! Prometheus metrics, served as `GET /metrics` on `metrics.bind`, an address
+//! of their own so they are not exposed with the API. When `metrics.token` is
+//! set, scrapers must send it as a bearer token.
+//!
+//! | Metric                          | Labels                      |
+//! |---------------------------------|-----------------------------|
+//! | `http_requests_total`           | `method`, `route`, `status` |
+//! | `http_request_duration_seconds` | `method`, `route`, `status` |
+//! | `db_pool_connections`           | `state` (`idle`, `in_use`)  |
+//! | `db_pool_max_connections`       |                             |
+//! | `db_pool_wait_seconds`          |                             |
+//! | `db_pool_timeouts_total`        |                             |
+//! | `blocking_queue_seconds`        |                             |
+//! | `auth_sign_ins_total`           | `outcome`                   |
+//! | `bookings_created_total`        | `status`                    |
+//!
+//! `auth_sign_ins_total` counts `success`, `invalid_credentials`, `locked`
+//! (refused because the account is locked) and `error`. Accounts are locked
+//! by the admin command, outside the server, so `locked` is how lockouts
+//! show up here. Bookings are counted by the service layer as they are
+//! inserted, whichever endpoint, import or waitlist offer made them. Pool
+//! gauges are read when scraped.
+
+use crate::config::MetricsConfig;
+use crate::errors::ApiError;
+use crate::models::Booking;
+use crate::users::service::extract_bearer_token;
+use crate::DbPool;
+use actix_web::dev::Server;
+use actix_web::{get, web, App, HttpRequest, HttpResponse, HttpServer, ResponseError};
+use anyhow::anyhow;
+use diesel::r2d2::{event, HandleEvent};
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+
+/// Histogram buckets for every `*_seconds` metric.
+const BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Installs the recorder and returns the metrics server, or `None` when
+/// metrics are disabled, in which case recording anything is a no-op.
+pub fn serve(config: &MetricsConfig, pool: DbPool) -> anyhow::Result<Option<Server>> {
+    if !config.enabled {
+        return Ok(None);
+    }
+
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Suffix("_seconds".into()), BUCKETS)?
+        .install_recorder()
+        .map_err(|e| anyhow!("Cannot install the metrics recorder: {}", e))?;
+    describe();
+
+    let state = web::Data::new(State {
+        handle,
+        pool,
+        token: config.token.clone(),
+    });
+    let server =
+        HttpServer::new(move || App::new().app_data(state.clone()).service(metrics_endpoint))
+            .workers(1)
+            .disable_signals()
+            .bind(&config.bind)?
+            .run();
+    tracing::info!(bind = %config.bind, "serving metrics");
+    Ok(Some(server))
+}
+
+fn describe() {
+    describe_counter!("http_requests_total", "Requests answered");
+    describe_histogram!(
+        "http_request_duration_seconds",
+        "Time from receiving a request to answering it"
+    );
+    describe_gauge!("db_pool_connections", "Open database connections");
+    describe_gauge!("db_pool_max_connections", "Connections the pool may open");
+    describe_histogram!(
+        "db_pool_wait_seconds",
+        "Time spent waiting for a database connection"
+    );
+    describe_counter!(
+        "db_pool_timeouts_total",
+        "Connection checkouts that timed out"
+    );
+    describe_histogram!(
+        "blocking_queue_seconds",
+        "Time blocking work waited for a thread"
+    );
+    describe_counter!("auth_sign_ins_total", "Sign-in attempts");
+    describe_counter!("bookings_created_total", "Bookings created");
+}
+
+struct State {
+    handle: PrometheusHandle,
+    pool: DbPool,
+    token: Option<String>,
+}
+
+#[get("/metrics")]
+async fn metrics_endpoint(state: web::Data<State>, req: HttpRequest) -> HttpResponse {
+    if let Some(ref expected) = state.token {
+        let sent = match extract_bearer_token(&req) {
+            Ok(t) => t,
+            Err(resp) => return resp,
+        };
+        // Comparing digests takes the same time wherever the tokens differ.
+        if Sha256::digest(sent.as_bytes()) != Sha256::digest(expected.as_bytes()) {
+            return ApiError::Unauthorized.error_response();
+        }
+    }
+
+    let pool = state.pool.state();
+    gauge!("db_pool_connections", "state" => "idle").set(pool.idle_connections);
+    gauge!("db_pool_connections", "state" => "in_use")
+        .set(pool.connections - pool.idle_connections);
+    gauge!("db_pool_max_connections").set(state.pool.max_size());
+
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(state.handle.render())
+}
+
+/// Counts an answered API request; `route` is the matched pattern.
+pub fn observe_request(method: &str, route: &str, status: u16, elapsed: Duration) {
+    let labels = [
+        ("method", method.to_string()),
+        ("route", route.to_string()),
+        ("status", status.to_string()),
+    ];
+    counter!("http_requests_total", &labels).increment(1);
+    histogram!("http_request_duration_seconds", &labels).record(elapsed);
+}
+
+/// Records how long blocking work waited for a free thread.
+pub fn observe_queue(waited: Duration) {
+    histogram!("blocking_queue_seconds").record(waited);
+}
+
+/// Counts a sign-in attempt by its outcome.
+pub fn sign_in(outcome: Result<(), &ApiError>) {
+    let outcome = match outcome {
+        Ok(()) => "success",
+        Err(ApiError::InvalidCredentials) => "invalid_credentials",
+        Err(ApiError::Locked(_)) => "locked",
+        Err(_) => "error",
+    };
+    counter!("auth_sign_ins_total", "outcome" => outcome).increment(1);
+}
+
+/// Counts newly inserted bookings.
+pub fn bookings_created(bookings: &[Booking]) {
+    for booking in bookings {
+        counter!("bookings_created_total", "status" => booking.status.as_str()).increment(1);
+    }
+}
+
+/// Times connection checkouts for every user of the pool.
+#[derive(Debug)]
+pub struct PoolEvents;
+
+impl HandleEvent for PoolEvents {
+    fn handle_checkout(&self, event: event::CheckoutEvent) {
+        histogram!("db_pool_wait_seconds").record(event.duration());
+    }
+
+    fn handle_timeout(&self, _event: event::TimeoutEvent) {
+        counter!("db_pool_timeouts_total").increment(1);
+    }
+}
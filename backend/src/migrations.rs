@@ -0,0 +1,213 @@
+//! Database migrations embedded in the binary.
+//!
+//! The server applies or checks them at startup according to
+//! `database.migrations`, and `backend migrate [run|check|dry-run]` does the
+//! same on demand. Applying takes a session level advisory lock, so replicas
+//! starting together wait for each other instead of racing; whoever comes
+//! second finds nothing left to do.
+//!
+//! Progress and warnings are logged; only the results `backend migrate` was
+//! asked for are printed.
+
+use crate::config::{Config, MigrationMode};
+use anyhow::{anyhow, bail};
+use diesel::connection::SimpleConnection;
+use diesel::migration::{Migration, MigrationSource, MigrationVersion};
+use diesel::pg::{Pg, PgConnection};
+use diesel::prelude::*;
+use diesel::sql_types::Integer;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+/// Keys of the advisory lock held while migrations run.
+const LOCK_NAMESPACE: i32 = 0x4D49_4752;
+const LOCK_KEY: i32 = 1;
+
+/// How the database compares with the embedded migrations.
+struct Status {
+    /// Embedded migrations not yet applied, oldest first.
+    pending: Vec<Box<dyn Migration<Pg>>>,
+    /// Applied versions this build does not know, e.g. from a newer release.
+    unknown: Vec<String>,
+}
+
+impl Status {
+    fn read(conn: &mut PgConnection) -> anyhow::Result<Status> {
+        let known: Vec<MigrationVersion<'static>> = MigrationSource::<Pg>::migrations(&MIGRATIONS)
+            .map_err(|e| anyhow!(e))?
+            .iter()
+            .map(|m| m.name().version().as_owned())
+            .collect();
+        let unknown = conn
+            .applied_migrations()
+            .map_err(|e| anyhow!(e))?
+            .into_iter()
+            .filter(|v| !known.contains(v))
+            .map(|v| v.to_string())
+            .collect();
+        let pending = conn
+            .pending_migrations(MIGRATIONS)
+            .map_err(|e| anyhow!(e))?;
+        Ok(Status { pending, unknown })
+    }
+
+    fn pending_names(&self) -> Vec<String> {
+        self.pending.iter().map(|m| m.name().to_string()).collect()
+    }
+
+    fn warn_unknown(&self) {
+        if !self.unknown.is_empty() {
+            tracing::warn!(
+                unknown = %self.unknown.join(", "),
+                "database has migrations this build does not know"
+            );
+        }
+    }
+}
+
+/// Applies or checks the migrations as configured, before the server starts.
+pub fn on_startup(config: &Config) -> anyhow::Result<()> {
+    match config.database.migrations {
+        MigrationMode::Run => run(&mut connect(config)?),
+        MigrationMode::Check => check(&mut connect(config)?),
+        MigrationMode::Off => Ok(()),
+    }
+}
+
+/// `backend migrate [run|check|dry-run]`.
+pub fn command(config: &Config, args: &[String]) -> anyhow::Result<()> {
+    let mut conn = connect(config)?;
+    match args.first().map(String::as_str).unwrap_or("run") {
+        "run" => run(&mut conn),
+        "check" => check(&mut conn).map(|()| println!("Database is up to date")),
+        "dry-run" => dry_run(&mut conn),
+        other => bail!(
+            "Unknown migrate command {:?}; expected run, check or dry-run",
+            other
+        ),
+    }
+}
+
+fn connect(config: &Config) -> anyhow::Result<PgConnection> {
+    PgConnection::establish(&config.database.url)
+        .map_err(|e| anyhow!("Cannot connect to the database to migrate it: {}", e))
+}
+
+/// Runs `f` while holding the migration lock, waiting for it if necessary.
+fn with_lock<T>(
+    conn: &mut PgConnection,
+    f: impl FnOnce(&mut PgConnection) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    diesel::sql_query("SELECT pg_advisory_lock($1, $2)")
+        .bind::<Integer, _>(LOCK_NAMESPACE)
+        .bind::<Integer, _>(LOCK_KEY)
+        .execute(conn)?;
+    let result = f(conn);
+    diesel::sql_query("SELECT pg_advisory_unlock($1, $2)")
+        .bind::<Integer, _>(LOCK_NAMESPACE)
+        .bind::<Integer, _>(LOCK_KEY)
+        .execute(conn)?;
+    result
+}
+
+fn run(conn: &mut PgConnection) -> anyhow::Result<()> {
+    with_lock(conn, |conn| {
+        let status = Status::read(conn)?;
+        status.warn_unknown();
+        for migration in &status.pending {
+            conn.run_migration(migration.as_ref())
+                .map_err(|e| anyhow!("Migration {} failed: {}", migration.name(), e))?;
+            tracing::info!(migration = %migration.name(), "applied migration");
+        }
+        Ok(())
+    })
+}
+
+/// Fails unless every embedded migration is applied and nothing else is.
+fn check(conn: &mut PgConnection) -> anyhow::Result<()> {
+    let status = Status::read(conn)?;
+    let mut problems = Vec::new();
+    if !status.pending.is_empty() {
+        problems.push(format!("pending: {}", status.pending_names().join(", ")));
+    }
+    if !status.unknown.is_empty() {
+        problems.push(format!(
+            "applied but unknown to this build: {}",
+            status.unknown.join(", ")
+        ));
+    }
+    if !problems.is_empty() {
+        bail!(
+            "Database schema does not match this build ({}); run `backend migrate` or deploy the matching release",
+            problems.join("; ")
+        );
+    }
+    Ok(())
+}
+
+/// Lists the pending migrations and tries each in a savepoint of one
+/// transaction that is always rolled back, so nothing is changed. A failure
+/// is undone before the next migration is tried. Migrations marked
+/// `run_in_transaction = false` cannot be tried this way; they and everything
+/// after them are reported as not tried.
+fn dry_run(conn: &mut PgConnection) -> anyhow::Result<()> {
+    with_lock(conn, |conn| {
+        let status = Status::read(conn)?;
+        status.warn_unknown();
+        if status.pending.is_empty() {
+            println!("No pending migrations");
+            return Ok(());
+        }
+
+        println!("{} pending migration(s):", status.pending.len());
+        conn.batch_execute("BEGIN")?;
+        let mut failures = Vec::new();
+        let mut untried = 0;
+        for migration in &status.pending {
+            if untried > 0 || !migration.metadata().run_in_transaction() {
+                println!(
+                    "  {}  not tried: {}",
+                    migration.name(),
+                    if untried > 0 {
+                        "follows a migration that was not tried"
+                    } else {
+                        "runs outside a transaction"
+                    }
+                );
+                untried += 1;
+                continue;
+            }
+
+            conn.batch_execute("SAVEPOINT dry_run")?;
+            match migration.run(conn) {
+                Ok(()) => {
+                    conn.batch_execute("RELEASE SAVEPOINT dry_run")?;
+                    println!("  {}  ok", migration.name());
+                }
+                Err(e) => {
+                    conn.batch_execute("ROLLBACK TO SAVEPOINT dry_run")?;
+                    println!("  {}  FAILED: {}", migration.name(), e);
+                    failures.push(migration.name().to_string());
+                }
+            }
+        }
+        conn.batch_execute("ROLLBACK")?;
+
+        if !failures.is_empty() {
+            bail!(
+                "Migration(s) {} would fail; nothing was changed",
+                failures.join(", ")
+            );
+        }
+        if untried > 0 {
+            println!(
+                "{} migration(s) could not be tried; the rest apply cleanly and nothing was changed",
+                untried
+            );
+        } else {
+            println!("All pending migrations apply cleanly; nothing was changed");
+        }
+        Ok(())
+    })
+}
@@ -1,7 +1,6 @@
 use chrono::{DateTime, Utc};
-use diesel::backend::RawValue;
 use diesel::deserialize::FromSql;
-use diesel::pg::Pg;
+use diesel::pg::{Pg, PgValue};
 use diesel::serialize::{IsNull, Output, ToSql};
 use diesel::{
     deserialize, serialize, AsChangeset, AsExpression, Associations, FromSqlRow, Identifiable,
@@ -11,10 +10,19 @@ use serde::{Deserialize, Serialize};
 use std::io::Write;
 use uuid::Uuid;
 
+use crate::schema::sql_types::ApprovalStatus as ApprovalStatusSql;
+use crate::schema::sql_types::AttendeeStatus as AttendeeStatusSql;
+use crate::schema::sql_types::BookingRuleKind as BookingRuleKindSql;
 use crate::schema::sql_types::BookingStatus as BookingStatusSql;
-use crate::schema::{bookings, users};
+use crate::schema::sql_types::CancellationReason as CancellationReasonSql;
+use crate::schema::sql_types::WaitlistStatus as WaitlistStatusSql;
+use crate::schema::{
+    booking_approvals, booking_attendees, booking_cancellations, booking_rules, booking_series,
+    bookings, busy_blocks, calendar_feeds, cancellation_policies, delay_request_approvals,
+    delay_requests, job_runs, notification_events, resources, users, waitlist_entries,
+};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, FromSqlRow, AsExpression)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, FromSqlRow, AsExpression)]
 #[diesel(sql_type = BookingStatusSql)]
 pub enum BookingStatus {
     Pending,
@@ -26,7 +34,7 @@ pub enum BookingStatus {
 }
 
 impl FromSql<BookingStatusSql, Pg> for BookingStatus {
-    fn from_sql(value: RawValue<'_, Pg>) -> deserialize::Result<Self> {
+    fn from_sql(value: PgValue<'_>) -> deserialize::Result<Self> {
         match value.as_bytes() {
             b"pending" => Ok(BookingStatus::Pending),
             b"confirmed" => Ok(BookingStatus::Confirmed),
@@ -39,15 +47,52 @@ impl FromSql<BookingStatusSql, Pg> for BookingStatus {
     }
 }
 
-impl ToSql<BookingStatusSql, Pg> for BookingStatus {
-    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
-        let s = match self {
+impl BookingStatus {
+    /// The database spelling, e.g. `no_show`.
+    pub fn as_str(self) -> &'static str {
+        match self {
             BookingStatus::Pending => "pending",
             BookingStatus::Confirmed => "confirmed",
             BookingStatus::Cancelled => "cancelled",
             BookingStatus::Completed => "completed",
             BookingStatus::NoShow => "no_show",
             BookingStatus::Delayed => "delayed",
+        }
+    }
+}
+
+impl ToSql<BookingStatusSql, Pg> for BookingStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, FromSqlRow, AsExpression)]
+#[diesel(sql_type = AttendeeStatusSql)]
+pub enum AttendeeStatus {
+    Invited,
+    Accepted,
+    Declined,
+}
+
+impl FromSql<AttendeeStatusSql, Pg> for AttendeeStatus {
+    fn from_sql(value: PgValue<'_>) -> deserialize::Result<Self> {
+        match value.as_bytes() {
+            b"invited" => Ok(AttendeeStatus::Invited),
+            b"accepted" => Ok(AttendeeStatus::Accepted),
+            b"declined" => Ok(AttendeeStatus::Declined),
+            other => Err(format!("Unrecognized attendee status: {:?}", other).into()),
+        }
+    }
+}
+
+impl ToSql<AttendeeStatusSql, Pg> for AttendeeStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        let s = match self {
+            AttendeeStatus::Invited => "invited",
+            AttendeeStatus::Accepted => "accepted",
+            AttendeeStatus::Declined => "declined",
         };
         out.write_all(s.as_bytes())?;
         Ok(IsNull::No)
@@ -67,6 +112,134 @@ pub struct Booking {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub deleted_at: Option<DateTime<Utc>>,
+    pub resource_id: Option<Uuid>,
+    pub end_date: DateTime<Utc>,
+    pub series_id: Option<Uuid>,
+    pub recurrence_id: Option<DateTime<Utc>>,
+    pub is_override: bool,
+    pub hold_expires_at: Option<DateTime<Utc>>,
+    pub checked_in_at: Option<DateTime<Utc>>,
+    pub checked_out_at: Option<DateTime<Utc>>,
+    pub original_booking_date: Option<DateTime<Utc>>,
+    pub original_end_date: Option<DateTime<Utc>>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+    pub cancelled_by: Option<Uuid>,
+    pub cancellation_reason: Option<CancellationReason>,
+    pub cancellation_note: Option<String>,
+    pub late_cancellation: bool,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = bookings)]
+pub struct NewBooking {
+    pub user_id: Uuid,
+    pub resource_id: Option<Uuid>,
+    pub title: String,
+    pub description: Option<String>,
+    pub booking_date: DateTime<Utc>,
+    pub end_date: DateTime<Utc>,
+    pub status: BookingStatus,
+    pub series_id: Option<Uuid>,
+    pub recurrence_id: Option<DateTime<Utc>>,
+    pub hold_expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = bookings)]
+pub struct UpdateBookingChangeset {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub booking_date: Option<DateTime<Utc>>,
+    pub end_date: Option<DateTime<Utc>>,
+    pub status: Option<BookingStatus>,
+    pub is_override: Option<bool>,
+}
+
+#[derive(Debug, Queryable, Identifiable, Associations, Serialize, Deserialize)]
+#[diesel(belongs_to(User))]
+#[diesel(belongs_to(Resource))]
+#[diesel(table_name = booking_series)]
+pub struct BookingSeries {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub resource_id: Uuid,
+    pub title: String,
+    pub description: Option<String>,
+    pub rrule: String,
+    pub dtstart: DateTime<Utc>,
+    pub duration_minutes: i32,
+    pub exdates: Vec<Option<DateTime<Utc>>>,
+    /// IANA time zone the rule is expanded in.
+    pub timezone: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = booking_series)]
+pub struct NewBookingSeries {
+    pub user_id: Uuid,
+    pub resource_id: Uuid,
+    pub title: String,
+    pub description: Option<String>,
+    pub rrule: String,
+    pub dtstart: DateTime<Utc>,
+    pub duration_minutes: i32,
+    pub exdates: Vec<Option<DateTime<Utc>>>,
+    pub timezone: String,
+}
+
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = booking_series)]
+pub struct UpdateSeriesChangeset {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub rrule: Option<String>,
+    pub dtstart: Option<DateTime<Utc>>,
+    pub duration_minutes: Option<i32>,
+    pub exdates: Option<Vec<Option<DateTime<Utc>>>>,
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Queryable, Identifiable, Serialize, Deserialize)]
+#[diesel(table_name = resources)]
+pub struct Resource {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub capacity: Option<i32>,
+    pub checkin_enabled: bool,
+    pub cascade_delays: bool,
+    pub resource_type: Option<String>,
+    pub requires_approval: bool,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = resources)]
+pub struct NewResource {
+    pub name: String,
+    pub description: Option<String>,
+    pub capacity: Option<i32>,
+    pub checkin_enabled: bool,
+    pub cascade_delays: bool,
+    pub resource_type: Option<String>,
+    pub requires_approval: bool,
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = resources)]
+pub struct UpdateResourceChangeset {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub capacity: Option<i32>,
+    pub checkin_enabled: Option<bool>,
+    pub cascade_delays: Option<bool>,
+    pub resource_type: Option<String>,
+    pub requires_approval: Option<bool>,
 }
 
 #[derive(Debug, Queryable, Identifiable, Serialize, Deserialize)]
@@ -79,6 +252,7 @@ pub struct User {
     pub email: String,
     pub password_hash: String,
     pub token_version: i32,
+    pub is_active: bool,
     pub locked_until: Option<DateTime<Utc>>,
     pub last_login_at: Option<DateTime<Utc>>,
     pub deleted_at: Option<DateTime<Utc>>,
@@ -97,12 +271,16 @@ pub struct NewUser {
     pub token_version: i32,
 }
 
-#[derive(Queryable, Debug)]
+#[derive(Debug, Serialize)]
 pub struct UserBasic {
+    pub id: Uuid,
     pub username: String,
     pub email: String,
     pub first_name: String,
     pub last_name: String,
+    pub is_active: bool,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
     pub roles: Vec<String>,
 }
 
@@ -121,3 +299,414 @@ pub struct UpdatePasswordChangeset {
     pub password_hash: String,
     pub token_version: i32,
 }
+
+#[derive(Debug, Queryable, Identifiable, Associations, Serialize, Deserialize)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = calendar_feeds)]
+pub struct CalendarFeed {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub resource_id: Option<Uuid>,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = calendar_feeds)]
+pub struct NewCalendarFeed {
+    pub user_id: Uuid,
+    pub resource_id: Option<Uuid>,
+    pub token_hash: String,
+}
+
+#[derive(Debug, Queryable, Identifiable, Associations, Serialize, Deserialize)]
+#[diesel(belongs_to(Resource))]
+#[diesel(table_name = busy_blocks)]
+pub struct BusyBlock {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub resource_id: Uuid,
+    pub summary: Option<String>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub source_uid: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = busy_blocks)]
+pub struct NewBusyBlock {
+    pub user_id: Uuid,
+    pub resource_id: Uuid,
+    pub summary: Option<String>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub source_uid: Option<String>,
+}
+
+#[derive(Debug, Queryable, Identifiable, Associations, Serialize, Deserialize)]
+#[diesel(belongs_to(Booking))]
+#[diesel(primary_key(booking_id, user_id))]
+#[diesel(table_name = booking_attendees)]
+pub struct BookingAttendee {
+    pub booking_id: Uuid,
+    pub user_id: Uuid,
+    pub status: AttendeeStatus,
+    pub invited_by: Option<Uuid>,
+    pub responded_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = booking_attendees)]
+pub struct NewBookingAttendee {
+    pub booking_id: Uuid,
+    pub user_id: Uuid,
+    pub status: AttendeeStatus,
+    pub invited_by: Option<Uuid>,
+    pub responded_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Queryable, Identifiable, Associations, Serialize, Deserialize)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = notification_events)]
+pub struct NotificationEvent {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = notification_events)]
+pub struct NewNotificationEvent<'a> {
+    pub user_id: Uuid,
+    pub kind: &'a str,
+    pub payload: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, FromSqlRow, AsExpression)]
+#[diesel(sql_type = WaitlistStatusSql)]
+pub enum WaitlistStatus {
+    Waiting,
+    Offered,
+    Accepted,
+    Declined,
+    Expired,
+    Cancelled,
+}
+
+impl FromSql<WaitlistStatusSql, Pg> for WaitlistStatus {
+    fn from_sql(value: PgValue<'_>) -> deserialize::Result<Self> {
+        match value.as_bytes() {
+            b"waiting" => Ok(WaitlistStatus::Waiting),
+            b"offered" => Ok(WaitlistStatus::Offered),
+            b"accepted" => Ok(WaitlistStatus::Accepted),
+            b"declined" => Ok(WaitlistStatus::Declined),
+            b"expired" => Ok(WaitlistStatus::Expired),
+            b"cancelled" => Ok(WaitlistStatus::Cancelled),
+            other => Err(format!("Unrecognized waitlist status: {:?}", other).into()),
+        }
+    }
+}
+
+impl ToSql<WaitlistStatusSql, Pg> for WaitlistStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        let s = match self {
+            WaitlistStatus::Waiting => "waiting",
+            WaitlistStatus::Offered => "offered",
+            WaitlistStatus::Accepted => "accepted",
+            WaitlistStatus::Declined => "declined",
+            WaitlistStatus::Expired => "expired",
+            WaitlistStatus::Cancelled => "cancelled",
+        };
+        out.write_all(s.as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+#[derive(Debug, Queryable, Identifiable, Associations, Serialize, Deserialize)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = waitlist_entries)]
+pub struct WaitlistEntry {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub resource_id: Uuid,
+    pub title: String,
+    pub description: Option<String>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub priority: i32,
+    pub status: WaitlistStatus,
+    pub booking_id: Option<Uuid>,
+    pub offer_expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = waitlist_entries)]
+pub struct NewWaitlistEntry {
+    pub user_id: Uuid,
+    pub resource_id: Uuid,
+    pub title: String,
+    pub description: Option<String>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub priority: i32,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = job_runs)]
+pub struct NewJobRun<'a> {
+    pub job_name: &'a str,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub succeeded: bool,
+    pub affected: i32,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, FromSqlRow, AsExpression)]
+#[diesel(sql_type = ApprovalStatusSql)]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Rejected,
+    /// The booking was cancelled or deleted before anyone decided.
+    Withdrawn,
+}
+
+impl FromSql<ApprovalStatusSql, Pg> for ApprovalStatus {
+    fn from_sql(value: PgValue<'_>) -> deserialize::Result<Self> {
+        match value.as_bytes() {
+            b"pending" => Ok(ApprovalStatus::Pending),
+            b"approved" => Ok(ApprovalStatus::Approved),
+            b"rejected" => Ok(ApprovalStatus::Rejected),
+            b"withdrawn" => Ok(ApprovalStatus::Withdrawn),
+            other => Err(format!("Unrecognized approval status: {:?}", other).into()),
+        }
+    }
+}
+
+impl ToSql<ApprovalStatusSql, Pg> for ApprovalStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        let s = match self {
+            ApprovalStatus::Pending => "pending",
+            ApprovalStatus::Approved => "approved",
+            ApprovalStatus::Rejected => "rejected",
+            ApprovalStatus::Withdrawn => "withdrawn",
+        };
+        out.write_all(s.as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+#[derive(Debug, Queryable, Identifiable, Associations, Serialize, Deserialize)]
+#[diesel(belongs_to(Booking))]
+#[diesel(table_name = delay_requests)]
+pub struct DelayRequest {
+    pub id: Uuid,
+    pub booking_id: Uuid,
+    pub requested_by: Uuid,
+    pub minutes: i32,
+    pub status: ApprovalStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = delay_requests)]
+pub struct NewDelayRequest {
+    pub booking_id: Uuid,
+    pub requested_by: Uuid,
+    pub minutes: i32,
+}
+
+#[derive(Debug, Queryable, Identifiable, Associations, Serialize, Deserialize)]
+#[diesel(belongs_to(DelayRequest))]
+#[diesel(primary_key(delay_request_id, booking_id))]
+#[diesel(table_name = delay_request_approvals)]
+pub struct DelayApproval {
+    pub delay_request_id: Uuid,
+    pub booking_id: Uuid,
+    pub owner_id: Uuid,
+    pub status: ApprovalStatus,
+    pub responded_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = delay_request_approvals)]
+pub struct NewDelayApproval {
+    pub delay_request_id: Uuid,
+    pub booking_id: Uuid,
+    pub owner_id: Uuid,
+    pub status: ApprovalStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, FromSqlRow, AsExpression)]
+#[diesel(sql_type = CancellationReasonSql)]
+pub enum CancellationReason {
+    ScheduleChange,
+    NoLongerNeeded,
+    Illness,
+    Weather,
+    Duplicate,
+    Other,
+}
+
+impl FromSql<CancellationReasonSql, Pg> for CancellationReason {
+    fn from_sql(value: PgValue<'_>) -> deserialize::Result<Self> {
+        match value.as_bytes() {
+            b"schedule_change" => Ok(CancellationReason::ScheduleChange),
+            b"no_longer_needed" => Ok(CancellationReason::NoLongerNeeded),
+            b"illness" => Ok(CancellationReason::Illness),
+            b"weather" => Ok(CancellationReason::Weather),
+            b"duplicate" => Ok(CancellationReason::Duplicate),
+            b"other" => Ok(CancellationReason::Other),
+            other => Err(format!("Unrecognized cancellation reason: {:?}", other).into()),
+        }
+    }
+}
+
+impl ToSql<CancellationReasonSql, Pg> for CancellationReason {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        let s = match self {
+            CancellationReason::ScheduleChange => "schedule_change",
+            CancellationReason::NoLongerNeeded => "no_longer_needed",
+            CancellationReason::Illness => "illness",
+            CancellationReason::Weather => "weather",
+            CancellationReason::Duplicate => "duplicate",
+            CancellationReason::Other => "other",
+        };
+        out.write_all(s.as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+/// One entry of a user's cancellation history.
+#[derive(Debug, Insertable)]
+#[diesel(table_name = booking_cancellations)]
+pub struct NewBookingCancellation {
+    pub booking_id: Option<Uuid>,
+    pub user_id: Uuid,
+    pub cancelled_by: Option<Uuid>,
+    pub reason: CancellationReason,
+    pub note: Option<String>,
+    pub late: bool,
+}
+
+#[derive(Debug, Queryable, Identifiable, Serialize, Deserialize)]
+#[diesel(primary_key(resource_id))]
+#[diesel(table_name = cancellation_policies)]
+pub struct CancellationPolicy {
+    pub resource_id: Uuid,
+    pub free_cancel_minutes: i32,
+    pub allow_late_cancel: bool,
+    pub staff_can_override: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[diesel(table_name = cancellation_policies)]
+pub struct NewCancellationPolicy {
+    pub resource_id: Uuid,
+    pub free_cancel_minutes: i32,
+    pub allow_late_cancel: bool,
+    pub staff_can_override: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, FromSqlRow, AsExpression)]
+#[diesel(sql_type = BookingRuleKindSql)]
+pub enum BookingRuleKind {
+    /// Bookings must start at least this many minutes from now.
+    MinLeadMinutes,
+    /// Bookings must start at most this many days from now.
+    MaxLeadDays,
+    MaxDurationMinutes,
+    /// Active bookings per user per calendar week (Monday to Sunday, UTC).
+    MaxActivePerWeek,
+}
+
+impl FromSql<BookingRuleKindSql, Pg> for BookingRuleKind {
+    fn from_sql(value: PgValue<'_>) -> deserialize::Result<Self> {
+        match value.as_bytes() {
+            b"min_lead_minutes" => Ok(BookingRuleKind::MinLeadMinutes),
+            b"max_lead_days" => Ok(BookingRuleKind::MaxLeadDays),
+            b"max_duration_minutes" => Ok(BookingRuleKind::MaxDurationMinutes),
+            b"max_active_per_week" => Ok(BookingRuleKind::MaxActivePerWeek),
+            other => Err(format!("Unrecognized booking rule kind: {:?}", other).into()),
+        }
+    }
+}
+
+impl ToSql<BookingRuleKindSql, Pg> for BookingRuleKind {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        let s = match self {
+            BookingRuleKind::MinLeadMinutes => "min_lead_minutes",
+            BookingRuleKind::MaxLeadDays => "max_lead_days",
+            BookingRuleKind::MaxDurationMinutes => "max_duration_minutes",
+            BookingRuleKind::MaxActivePerWeek => "max_active_per_week",
+        };
+        out.write_all(s.as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+#[derive(Debug, Queryable, Identifiable, Serialize, Deserialize)]
+#[diesel(table_name = booking_rules)]
+pub struct BookingRule {
+    pub id: Uuid,
+    pub resource_id: Option<Uuid>,
+    pub resource_type: Option<String>,
+    pub kind: BookingRuleKind,
+    pub value: i32,
+    pub bypass_roles: Vec<Option<String>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = booking_rules)]
+pub struct NewBookingRule {
+    pub resource_id: Option<Uuid>,
+    pub resource_type: Option<String>,
+    pub kind: BookingRuleKind,
+    pub value: i32,
+    pub bypass_roles: Vec<Option<String>>,
+}
+
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = booking_rules)]
+pub struct UpdateBookingRuleChangeset {
+    pub value: Option<i32>,
+    pub bypass_roles: Option<Vec<Option<String>>>,
+}
+
+#[derive(Debug, Queryable, Identifiable, Associations, Serialize, Deserialize)]
+#[diesel(belongs_to(Booking))]
+#[diesel(primary_key(booking_id))]
+#[diesel(table_name = booking_approvals)]
+pub struct BookingApproval {
+    pub booking_id: Uuid,
+    pub status: ApprovalStatus,
+    pub requested_at: DateTime<Utc>,
+    pub decided_by: Option<Uuid>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub comment: Option<String>,
+    pub escalated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = booking_approvals)]
+pub struct NewBookingApproval {
+    pub booking_id: Uuid,
+}
@@ -0,0 +1,76 @@
+use crate::config::Config;
+use crate::errors::ApiError;
+use crate::users::service::extract_bearer_token;
+use crate::{services, DbPool};
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use uuid::Uuid;
+
+pub mod service;
+
+#[derive(Deserialize)]
+pub struct NotificationsQuery {
+    pub unread: Option<bool>,
+    pub limit: Option<i64>,
+}
+
+#[get("/user/notifications")]
+pub async fn get_notifications_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    query: web::Query<NotificationsQuery>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::get_notifications(&mut conn, &user, query.into_inner())
+    })
+    .await
+    {
+        Ok(Ok(events)) => HttpResponse::Ok().json(events),
+        Ok(Err(e)) => e.into_response("Error fetching notifications"),
+        Err(e) => ApiError::from(e).into_response("Error fetching notifications"),
+    }
+}
+
+#[post("/user/notifications/{id}/read")]
+pub async fn mark_notification_read_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let event_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::mark_read(&mut conn, &user, event_id)
+    })
+    .await
+    {
+        Ok(Ok(event)) => HttpResponse::Ok().json(event),
+        Ok(Err(e)) => e.into_response("Error updating notification"),
+        Err(e) => ApiError::from(e).into_response("Error updating notification"),
+    }
+}
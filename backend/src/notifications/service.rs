@@ -0,0 +1,74 @@
+use crate::errors::ApiError;
+use crate::models::{NewNotificationEvent, NotificationEvent, User};
+use crate::notifications::NotificationsQuery;
+use chrono::Utc;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 200;
+
+/// Records a notification for `recipient`. Runs on the caller's connection so
+/// the event is only kept if the surrounding transaction commits.
+pub fn notify(
+    conn: &mut PgConnection,
+    recipient: Uuid,
+    event_kind: &str,
+    event_payload: serde_json::Value,
+) -> QueryResult<()> {
+    use crate::schema::notification_events::dsl::*;
+
+    diesel::insert_into(notification_events)
+        .values(&NewNotificationEvent {
+            user_id: recipient,
+            kind: event_kind,
+            payload: event_payload,
+        })
+        .execute(conn)?;
+
+    Ok(())
+}
+
+pub fn get_notifications(
+    conn: &mut PgConnection,
+    user: &User,
+    query: NotificationsQuery,
+) -> Result<Vec<NotificationEvent>, ApiError> {
+    use crate::schema::notification_events::dsl::*;
+
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let mut events = notification_events
+        .filter(user_id.eq(user.id))
+        .order(created_at.desc())
+        .limit(limit)
+        .into_boxed();
+    if query.unread.unwrap_or(false) {
+        events = events.filter(read_at.is_null());
+    }
+
+    Ok(events.load::<NotificationEvent>(conn)?)
+}
+
+pub fn mark_read(
+    conn: &mut PgConnection,
+    user: &User,
+    event_uuid: Uuid,
+) -> Result<NotificationEvent, ApiError> {
+    use crate::schema::notification_events::dsl::*;
+
+    let event = notification_events
+        .find(event_uuid)
+        .filter(user_id.eq(user.id))
+        .first::<NotificationEvent>(conn)
+        .optional()?
+        .ok_or(ApiError::NotFound)?;
+
+    if event.read_at.is_some() {
+        return Ok(event);
+    }
+
+    Ok(diesel::update(notification_events.find(event.id))
+        .set(read_at.eq(Some(Utc::now())))
+        .get_result::<NotificationEvent>(conn)?)
+}
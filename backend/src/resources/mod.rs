@@ -0,0 +1,324 @@
+use crate::config::Config;
+use crate::errors::ApiError;
+use crate::users::service::extract_bearer_token;
+use crate::validation::{nfc, nfc_trim, Validate, ValidatedJson, Validator};
+use crate::{services, DbPool};
+use actix_web::{delete, get, patch, post, put, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use uuid::Uuid;
+
+pub mod service;
+
+#[derive(Deserialize)]
+pub struct CreateResourceRequest {
+    pub name: String,
+    pub description: Option<String>,
+    /// Maximum number of people on one booking, organiser included.
+    pub capacity: Option<i32>,
+    /// Bookings must be checked in or they become no-shows.
+    pub checkin_enabled: Option<bool>,
+    /// Delays may push following bookings back once their owners approve.
+    pub cascade_delays: Option<bool>,
+    /// Free-form grouping (e.g. "meeting_room") that booking rules can target.
+    pub resource_type: Option<String>,
+    /// New bookings start as `Pending` until someone with `bookings:approve`
+    /// signs them off.
+    pub requires_approval: Option<bool>,
+}
+
+impl Validate for CreateResourceRequest {
+    fn normalize(&mut self) {
+        nfc_trim(&mut self.name);
+        if let Some(ref mut text) = self.description {
+            nfc(text);
+        }
+        if let Some(ref mut kind) = self.resource_type {
+            nfc_trim(kind);
+        }
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        v.field("name", &self.name).not_blank().length(1, 100);
+        v.check(
+            "capacity",
+            self.capacity.is_none_or(|c| c >= 1),
+            "Capacity must be at least 1",
+        );
+        v.optional("resource_type", self.resource_type.as_deref())
+            .length(1, 50);
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UpdateResourceRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub capacity: Option<i32>,
+    pub checkin_enabled: Option<bool>,
+    pub cascade_delays: Option<bool>,
+    pub resource_type: Option<String>,
+    pub requires_approval: Option<bool>,
+}
+
+impl Validate for UpdateResourceRequest {
+    fn normalize(&mut self) {
+        if let Some(ref mut text) = self.name {
+            nfc_trim(text);
+        }
+        if let Some(ref mut text) = self.description {
+            nfc(text);
+        }
+        if let Some(ref mut kind) = self.resource_type {
+            nfc_trim(kind);
+        }
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        v.optional("name", self.name.as_deref())
+            .not_blank()
+            .length(1, 100);
+        v.check(
+            "capacity",
+            self.capacity.is_none_or(|c| c >= 1),
+            "Capacity must be at least 1",
+        );
+        v.optional("resource_type", self.resource_type.as_deref())
+            .length(1, 50);
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CancellationPolicyRequest {
+    /// Bookings can be cancelled freely until this many minutes before they start.
+    pub free_cancel_minutes: i32,
+    /// Whether cancelling after the cut-off is allowed at all (flagged as late).
+    pub allow_late_cancel: Option<bool>,
+    /// Whether staff with `bookings:edit` may waive the cut-off.
+    pub staff_can_override: Option<bool>,
+}
+
+impl Validate for CancellationPolicyRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            "free_cancel_minutes",
+            self.free_cancel_minutes >= 0,
+            "Must not be negative",
+        );
+    }
+}
+
+#[post("/resources")]
+pub async fn create_resource_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    body: ValidatedJson<CreateResourceRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::create_resource(&mut conn, &user, body.into_inner())
+    })
+    .await
+    {
+        Ok(Ok(resource)) => HttpResponse::Created().json(resource),
+        Ok(Err(e)) => e.into_response("Error creating resource"),
+        Err(e) => ApiError::from(e).into_response("Error creating resource"),
+    }
+}
+
+#[get("/resources")]
+pub async fn get_resources_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        services::authenticate(&mut conn, &token, &secret)?;
+        Ok::<_, crate::errors::ApiError>(service::get_resources(&mut conn)?)
+    })
+    .await
+    {
+        Ok(Ok(resources)) => HttpResponse::Ok().json(resources),
+        Ok(Err(e)) => e.into_response("Error fetching resources"),
+        Err(e) => ApiError::from(e).into_response("Error fetching resources"),
+    }
+}
+
+#[get("/resources/{id}")]
+pub async fn get_resource_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let resource_id = path.into_inner();
+
+    match services::block(move || {
+        services::authenticate(&mut conn, &token, &secret)?;
+        service::get_resource(&mut conn, resource_id)
+    })
+    .await
+    {
+        Ok(Ok(resource)) => HttpResponse::Ok().json(resource),
+        Ok(Err(e)) => e.into_response("Error fetching resource"),
+        Err(e) => ApiError::from(e).into_response("Error fetching resource"),
+    }
+}
+
+#[patch("/resources/{id}")]
+pub async fn update_resource_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    body: ValidatedJson<UpdateResourceRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let resource_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::update_resource(&mut conn, &user, resource_id, body.into_inner())
+    })
+    .await
+    {
+        Ok(Ok(resource)) => HttpResponse::Ok().json(resource),
+        Ok(Err(e)) => e.into_response("Error updating resource"),
+        Err(e) => ApiError::from(e).into_response("Error updating resource"),
+    }
+}
+
+#[get("/resources/{id}/cancellation-policy")]
+pub async fn get_cancellation_policy_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let resource_id = path.into_inner();
+
+    match services::block(move || {
+        services::authenticate(&mut conn, &token, &secret)?;
+        service::get_cancellation_policy(&mut conn, resource_id)
+    })
+    .await
+    {
+        Ok(Ok(policy)) => HttpResponse::Ok().json(policy),
+        Ok(Err(e)) => e.into_response("Error fetching cancellation policy"),
+        Err(e) => ApiError::from(e).into_response("Error fetching cancellation policy"),
+    }
+}
+
+#[put("/resources/{id}/cancellation-policy")]
+pub async fn set_cancellation_policy_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    body: ValidatedJson<CancellationPolicyRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let resource_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::set_cancellation_policy(&mut conn, &user, resource_id, body.into_inner())
+    })
+    .await
+    {
+        Ok(Ok(policy)) => HttpResponse::Ok().json(policy),
+        Ok(Err(e)) => e.into_response("Error saving cancellation policy"),
+        Err(e) => ApiError::from(e).into_response("Error saving cancellation policy"),
+    }
+}
+
+#[delete("/resources/{id}/cancellation-policy")]
+pub async fn delete_cancellation_policy_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let resource_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::delete_cancellation_policy(&mut conn, &user, resource_id)
+    })
+    .await
+    {
+        Ok(Ok(())) => HttpResponse::Ok().json(serde_json::json!({"success": true})),
+        Ok(Err(e)) => e.into_response("Error deleting cancellation policy"),
+        Err(e) => ApiError::from(e).into_response("Error deleting cancellation policy"),
+    }
+}
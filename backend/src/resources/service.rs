@@ -0,0 +1,175 @@
+use crate::errors::ApiError;
+use crate::models::{
+    CancellationPolicy, NewCancellationPolicy, NewResource, Resource, UpdateResourceChangeset, User,
+};
+use crate::resources::{CancellationPolicyRequest, CreateResourceRequest, UpdateResourceRequest};
+use crate::users::service::has_permission;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+fn ensure_can_manage(conn: &mut PgConnection, user: &User) -> Result<(), ApiError> {
+    if has_permission(conn, user.id, "resources:manage")? {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden)
+    }
+}
+
+pub fn name_exists(conn: &mut PgConnection, name_check: &str) -> QueryResult<bool> {
+    use crate::schema::resources::dsl::*;
+    let exists = resources
+        .filter(name.eq(name_check))
+        .select(id)
+        .first::<Uuid>(conn)
+        .optional()?;
+    Ok(exists.is_some())
+}
+
+pub fn create_resource(
+    conn: &mut PgConnection,
+    user: &User,
+    data: CreateResourceRequest,
+) -> Result<Resource, ApiError> {
+    use crate::schema::resources::dsl::*;
+
+    ensure_can_manage(conn, user)?;
+
+    if name_exists(conn, &data.name)? {
+        return Err(ApiError::Invalid("Resource name already in use".into()));
+    }
+
+    let new_resource = NewResource {
+        name: data.name,
+        description: data.description,
+        capacity: data.capacity,
+        checkin_enabled: data.checkin_enabled.unwrap_or(false),
+        cascade_delays: data.cascade_delays.unwrap_or(false),
+        resource_type: data.resource_type,
+        requires_approval: data.requires_approval.unwrap_or(false),
+    };
+
+    Ok(diesel::insert_into(resources)
+        .values(&new_resource)
+        .get_result::<Resource>(conn)?)
+}
+
+pub fn get_resources(conn: &mut PgConnection) -> QueryResult<Vec<Resource>> {
+    use crate::schema::resources::dsl::*;
+
+    resources
+        .filter(deleted_at.is_null())
+        .order(name.asc())
+        .load::<Resource>(conn)
+}
+
+pub fn get_resource(conn: &mut PgConnection, resource_uuid: Uuid) -> Result<Resource, ApiError> {
+    use crate::schema::resources::dsl::*;
+
+    resources
+        .find(resource_uuid)
+        .filter(deleted_at.is_null())
+        .first::<Resource>(conn)
+        .optional()?
+        .ok_or(ApiError::NotFound)
+}
+
+pub fn update_resource(
+    conn: &mut PgConnection,
+    user: &User,
+    resource_uuid: Uuid,
+    data: UpdateResourceRequest,
+) -> Result<Resource, ApiError> {
+    use crate::schema::resources::dsl::*;
+
+    ensure_can_manage(conn, user)?;
+
+    let current = get_resource(conn, resource_uuid)?;
+    if let Some(ref new_name) = data.name
+        && *new_name != current.name
+        && name_exists(conn, new_name)?
+    {
+        return Err(ApiError::Invalid("Resource name already in use".into()));
+    }
+
+    let changes = UpdateResourceChangeset {
+        name: data.name,
+        description: data.description,
+        capacity: data.capacity,
+        checkin_enabled: data.checkin_enabled,
+        cascade_delays: data.cascade_delays,
+        resource_type: data.resource_type,
+        requires_approval: data.requires_approval,
+    };
+
+    Ok(diesel::update(resources.find(current.id))
+        .set(&changes)
+        .get_result::<Resource>(conn)?)
+}
+
+pub fn get_cancellation_policy(
+    conn: &mut PgConnection,
+    resource_uuid: Uuid,
+) -> Result<CancellationPolicy, ApiError> {
+    use crate::schema::cancellation_policies::dsl::*;
+
+    get_resource(conn, resource_uuid)?;
+    cancellation_policies
+        .find(resource_uuid)
+        .first::<CancellationPolicy>(conn)
+        .optional()?
+        .ok_or(ApiError::NotFound)
+}
+
+/// Creates or replaces the resource's cancellation policy.
+pub fn set_cancellation_policy(
+    conn: &mut PgConnection,
+    user: &User,
+    resource_uuid: Uuid,
+    data: CancellationPolicyRequest,
+) -> Result<CancellationPolicy, ApiError> {
+    use crate::schema::cancellation_policies::dsl::*;
+
+    ensure_can_manage(conn, user)?;
+    get_resource(conn, resource_uuid)?;
+
+    let policy = NewCancellationPolicy {
+        resource_id: resource_uuid,
+        free_cancel_minutes: data.free_cancel_minutes,
+        allow_late_cancel: data.allow_late_cancel.unwrap_or(true),
+        staff_can_override: data.staff_can_override.unwrap_or(true),
+    };
+
+    Ok(diesel::insert_into(cancellation_policies)
+        .values(&policy)
+        .on_conflict(resource_id)
+        .do_update()
+        .set(&policy)
+        .get_result::<CancellationPolicy>(conn)?)
+}
+
+pub fn delete_cancellation_policy(
+    conn: &mut PgConnection,
+    user: &User,
+    resource_uuid: Uuid,
+) -> Result<(), ApiError> {
+    use crate::schema::cancellation_policies::dsl::*;
+
+    ensure_can_manage(conn, user)?;
+    let deleted = diesel::delete(cancellation_policies.find(resource_uuid)).execute(conn)?;
+    if deleted == 0 {
+        return Err(ApiError::NotFound);
+    }
+    Ok(())
+}
+
+/// Permanently deletes resources soft-deleted before `cutoff`, with their
+/// bookings and settings.
+pub fn purge_deleted_resources(
+    conn: &mut PgConnection,
+    cutoff: DateTime<Utc>,
+) -> Result<usize, ApiError> {
+    use crate::schema::resources::dsl::*;
+
+    Ok(diesel::delete(resources.filter(deleted_at.lt(cutoff))).execute(conn)?)
+}
@@ -0,0 +1,169 @@
+//! In-process job scheduler.
+//!
+//! Every replica runs the same loop, but each job run takes a transaction
+//! level advisory lock first, so a job only executes on one replica at a
+//! time. Runs that acquire the lock are recorded in `job_runs`.
+
+use crate::config::Config;
+use crate::errors::ApiError;
+use crate::models::NewJobRun;
+use crate::{bookings, waitlist, DbPool};
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Text};
+use tracing::Instrument;
+
+/// First key of every scheduler advisory lock; the second is the job name's hash.
+const LOCK_NAMESPACE: i32 = 0x5343_4844;
+
+/// How long `job_runs` rows are kept.
+const JOB_RUN_RETENTION_DAYS: i64 = 30;
+
+diesel::define_sql_function! {
+    fn pg_try_advisory_xact_lock(namespace: Integer, key: Integer) -> Bool;
+}
+
+diesel::define_sql_function! {
+    fn hashtext(value: Text) -> Integer;
+}
+
+type JobFn = fn(&mut PgConnection, &Config) -> Result<usize, ApiError>;
+
+struct Job {
+    name: &'static str,
+    default_interval_secs: u64,
+    run: JobFn,
+}
+
+const JOBS: &[Job] = &[
+    Job {
+        name: "complete_bookings",
+        default_interval_secs: 60,
+        run: |conn, _| bookings::service::complete_finished_bookings(conn),
+    },
+    Job {
+        name: "mark_no_shows",
+        default_interval_secs: 60,
+        run: |conn, config| bookings::service::mark_no_shows(conn, &config.bookings),
+    },
+    Job {
+        name: "reap_holds",
+        default_interval_secs: 30,
+        run: |conn, config| bookings::service::reap_expired_holds(conn, &config.bookings),
+    },
+    Job {
+        name: "expire_waitlist_offers",
+        default_interval_secs: 60,
+        run: |conn, config| waitlist::service::expire_offers(conn, &config.bookings),
+    },
+    Job {
+        name: "escalate_approvals",
+        default_interval_secs: 5 * 60,
+        run: |conn, config| bookings::approval::escalate_overdue_approvals(conn, &config.bookings),
+    },
+    Job {
+        name: "prune_job_runs",
+        default_interval_secs: 24 * 60 * 60,
+        run: |conn, _| prune_job_runs(conn),
+    },
+];
+
+/// Names of every job, as used for interval overrides.
+pub fn job_names() -> impl Iterator<Item = &'static str> {
+    JOBS.iter().map(|job| job.name)
+}
+
+/// The configured interval of a job, or its default; `0` disables it.
+fn interval_secs(config: &Config, job: &Job) -> u64 {
+    config
+        .scheduler
+        .intervals
+        .get(job.name)
+        .copied()
+        .unwrap_or(job.default_interval_secs)
+}
+
+fn prune_job_runs(conn: &mut PgConnection) -> Result<usize, ApiError> {
+    use crate::schema::job_runs::dsl::*;
+
+    let cutoff = Utc::now() - Duration::days(JOB_RUN_RETENTION_DAYS);
+    Ok(diesel::delete(job_runs.filter(started_at.lt(cutoff))).execute(conn)?)
+}
+
+/// Runs the job if no other replica holds its lock and records the outcome.
+fn run_job(conn: &mut PgConnection, job: &Job, config: &Config) -> QueryResult<()> {
+    use crate::schema::job_runs::dsl::*;
+
+    let started = Utc::now();
+    let outcome = conn.transaction(|conn| {
+        let locked = diesel::select(pg_try_advisory_xact_lock(
+            LOCK_NAMESPACE,
+            hashtext(job.name),
+        ))
+        .get_result::<bool>(conn)?;
+        if !locked {
+            return Ok(None);
+        }
+        (job.run)(conn, config).map(Some)
+    });
+
+    let (ok, rows, failure) = match outcome {
+        Ok(None) => return Ok(()),
+        Ok(Some(rows)) => (true, rows, None),
+        Err(e) => {
+            tracing::error!(error = format!("{:?}", e), "job failed");
+            (false, 0, Some(format!("{:?}", e)))
+        }
+    };
+
+    diesel::insert_into(job_runs)
+        .values(&NewJobRun {
+            job_name: job.name,
+            started_at: started,
+            finished_at: Utc::now(),
+            succeeded: ok,
+            affected: rows.try_into().unwrap_or(i32::MAX),
+            error: failure,
+        })
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Starts one background loop per enabled job.
+pub fn spawn(pool: DbPool, config: &'static Config) {
+    if !config.features.scheduler {
+        tracing::info!("scheduler disabled");
+        return;
+    }
+
+    for job in JOBS {
+        let every = interval_secs(config, job);
+        if every == 0 {
+            continue;
+        }
+
+        let pool = pool.clone();
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(std::time::Duration::from_secs(every));
+            loop {
+                interval.tick().await;
+                let pool = pool.clone();
+                let span = tracing::info_span!("job", job = job.name);
+                let result = crate::services::block(move || {
+                    let mut conn = pool.get().map_err(anyhow::Error::from)?;
+                    run_job(&mut conn, job, config).map_err(anyhow::Error::from)
+                })
+                .instrument(span.clone())
+                .await;
+
+                let _entered = span.enter();
+                match result {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => tracing::error!(error = %e, "error running job"),
+                    Err(e) => tracing::error!(error = %e, "blocking error"),
+                }
+            }
+        });
+    }
+}
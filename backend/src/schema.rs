@@ -1,14 +1,50 @@
 // @generated automatically by Diesel CLI.
 
 pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "approval_status"))]
+    pub struct ApprovalStatus;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "attendee_status"))]
+    pub struct AttendeeStatus;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "booking_rule_kind"))]
+    pub struct BookingRuleKind;
+
     #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "booking_status"))]
     pub struct BookingStatus;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "cancellation_reason"))]
+    pub struct CancellationReason;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "waitlist_status"))]
+    pub struct WaitlistStatus;
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::AttendeeStatus;
+
+    booking_attendees (booking_id, user_id) {
+        booking_id -> Uuid,
+        user_id -> Uuid,
+        status -> AttendeeStatus,
+        invited_by -> Nullable<Uuid>,
+        responded_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
 }
 
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::BookingStatus;
+    use super::sql_types::CancellationReason;
 
     bookings (id) {
         id -> Uuid,
@@ -21,6 +57,181 @@ diesel::table! {
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
         deleted_at -> Nullable<Timestamptz>,
+        resource_id -> Nullable<Uuid>,
+        end_date -> Timestamptz,
+        series_id -> Nullable<Uuid>,
+        recurrence_id -> Nullable<Timestamptz>,
+        is_override -> Bool,
+        hold_expires_at -> Nullable<Timestamptz>,
+        checked_in_at -> Nullable<Timestamptz>,
+        checked_out_at -> Nullable<Timestamptz>,
+        original_booking_date -> Nullable<Timestamptz>,
+        original_end_date -> Nullable<Timestamptz>,
+        cancelled_at -> Nullable<Timestamptz>,
+        cancelled_by -> Nullable<Uuid>,
+        cancellation_reason -> Nullable<CancellationReason>,
+        cancellation_note -> Nullable<Text>,
+        late_cancellation -> Bool,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ApprovalStatus;
+
+    booking_approvals (booking_id) {
+        booking_id -> Uuid,
+        status -> ApprovalStatus,
+        requested_at -> Timestamptz,
+        decided_by -> Nullable<Uuid>,
+        decided_at -> Nullable<Timestamptz>,
+        comment -> Nullable<Text>,
+        escalated_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::CancellationReason;
+
+    booking_cancellations (id) {
+        id -> Uuid,
+        booking_id -> Nullable<Uuid>,
+        user_id -> Uuid,
+        cancelled_by -> Nullable<Uuid>,
+        cancelled_at -> Timestamptz,
+        reason -> CancellationReason,
+        note -> Nullable<Text>,
+        late -> Bool,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::BookingRuleKind;
+
+    booking_rules (id) {
+        id -> Uuid,
+        resource_id -> Nullable<Uuid>,
+        #[max_length = 50]
+        resource_type -> Nullable<Varchar>,
+        kind -> BookingRuleKind,
+        value -> Int4,
+        bypass_roles -> Array<Nullable<Text>>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    booking_series (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        resource_id -> Uuid,
+        #[max_length = 255]
+        title -> Varchar,
+        description -> Nullable<Text>,
+        rrule -> Text,
+        dtstart -> Timestamptz,
+        duration_minutes -> Int4,
+        exdates -> Array<Nullable<Timestamptz>>,
+        #[max_length = 64]
+        timezone -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    busy_blocks (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        resource_id -> Uuid,
+        #[max_length = 255]
+        summary -> Nullable<Varchar>,
+        starts_at -> Timestamptz,
+        ends_at -> Timestamptz,
+        source_uid -> Nullable<Text>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    calendar_feeds (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        resource_id -> Nullable<Uuid>,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        last_used_at -> Nullable<Timestamptz>,
+        revoked_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    cancellation_policies (resource_id) {
+        resource_id -> Uuid,
+        free_cancel_minutes -> Int4,
+        allow_late_cancel -> Bool,
+        staff_can_override -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ApprovalStatus;
+
+    delay_request_approvals (delay_request_id, booking_id) {
+        delay_request_id -> Uuid,
+        booking_id -> Uuid,
+        owner_id -> Uuid,
+        status -> ApprovalStatus,
+        responded_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ApprovalStatus;
+
+    delay_requests (id) {
+        id -> Uuid,
+        booking_id -> Uuid,
+        requested_by -> Uuid,
+        minutes -> Int4,
+        status -> ApprovalStatus,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        resolved_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    job_runs (id) {
+        id -> Uuid,
+        #[max_length = 100]
+        job_name -> Varchar,
+        started_at -> Timestamptz,
+        finished_at -> Timestamptz,
+        succeeded -> Bool,
+        affected -> Int4,
+        error -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    notification_events (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 64]
+        kind -> Varchar,
+        payload -> Jsonb,
+        created_at -> Timestamptz,
+        read_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -32,6 +243,24 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    resources (id) {
+        id -> Uuid,
+        #[max_length = 100]
+        name -> Varchar,
+        description -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+        capacity -> Nullable<Int4>,
+        checkin_enabled -> Bool,
+        cascade_delays -> Bool,
+        #[max_length = 50]
+        resource_type -> Nullable<Varchar>,
+        requires_approval -> Bool,
+    }
+}
+
 diesel::table! {
     roles (id) {
         id -> Int4,
@@ -60,6 +289,7 @@ diesel::table! {
         email -> Varchar,
         password_hash -> Text,
         token_version -> Int4,
+        is_active -> Bool,
         locked_until -> Nullable<Timestamptz>,
         last_login_at -> Nullable<Timestamptz>,
         deleted_at -> Nullable<Timestamptz>,
@@ -75,17 +305,76 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::WaitlistStatus;
+
+    waitlist_entries (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        resource_id -> Uuid,
+        #[max_length = 255]
+        title -> Varchar,
+        description -> Nullable<Text>,
+        starts_at -> Timestamptz,
+        ends_at -> Timestamptz,
+        priority -> Int4,
+        status -> WaitlistStatus,
+        booking_id -> Nullable<Uuid>,
+        offer_expires_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::joinable!(booking_attendees -> bookings (booking_id));
+diesel::joinable!(booking_cancellations -> bookings (booking_id));
+diesel::joinable!(booking_approvals -> bookings (booking_id));
+diesel::joinable!(booking_approvals -> users (decided_by));
+diesel::joinable!(booking_rules -> resources (resource_id));
+diesel::joinable!(booking_series -> resources (resource_id));
+diesel::joinable!(booking_series -> users (user_id));
+diesel::joinable!(bookings -> booking_series (series_id));
+diesel::joinable!(bookings -> resources (resource_id));
 diesel::joinable!(bookings -> users (user_id));
+diesel::joinable!(busy_blocks -> resources (resource_id));
+diesel::joinable!(busy_blocks -> users (user_id));
+diesel::joinable!(calendar_feeds -> resources (resource_id));
+diesel::joinable!(calendar_feeds -> users (user_id));
+diesel::joinable!(cancellation_policies -> resources (resource_id));
+diesel::joinable!(delay_request_approvals -> bookings (booking_id));
+diesel::joinable!(delay_request_approvals -> delay_requests (delay_request_id));
+diesel::joinable!(delay_request_approvals -> users (owner_id));
+diesel::joinable!(delay_requests -> bookings (booking_id));
+diesel::joinable!(delay_requests -> users (requested_by));
+diesel::joinable!(notification_events -> users (user_id));
 diesel::joinable!(roles_permissions -> permissions (permission_id));
 diesel::joinable!(roles_permissions -> roles (role_id));
 diesel::joinable!(users_roles -> roles (role_id));
 diesel::joinable!(users_roles -> users (user_id));
+diesel::joinable!(waitlist_entries -> bookings (booking_id));
+diesel::joinable!(waitlist_entries -> resources (resource_id));
+diesel::joinable!(waitlist_entries -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    booking_approvals,
+    booking_attendees,
+    booking_cancellations,
+    booking_rules,
+    booking_series,
     bookings,
+    busy_blocks,
+    calendar_feeds,
+    cancellation_policies,
+    delay_request_approvals,
+    delay_requests,
+    job_runs,
+    notification_events,
     permissions,
+    resources,
     roles,
     roles_permissions,
     users,
     users_roles,
+    waitlist_entries,
 );
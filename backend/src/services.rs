@@ -1,17 +1,65 @@
+use crate::errors::ApiError;
+use crate::models::User;
 use crate::DbPool;
-use actix_web::HttpResponse;
+use actix_web::error::BlockingError;
+use actix_web::{web, HttpResponse};
 use diesel::pg::PgConnection;
 use diesel::r2d2::PooledConnection;
+use std::time::Instant;
 
+// Handlers return the error response as is, so its size does not matter.
+#[allow(clippy::result_large_err)]
 pub fn get_conn(
     pool: &DbPool,
 ) -> Result<PooledConnection<diesel::r2d2::ConnectionManager<PgConnection>>, HttpResponse> {
-    pool.get().map_err(|e| {
-        eprintln!("Failed to get DB connection: {}", e);
-        HttpResponse::InternalServerError().finish()
+    pool.get()
+        .map_err(|e| ApiError::Internal(e.into()).into_response("Database unavailable"))
+}
+
+/// `web::block` for handlers: runs `f` on the blocking thread pool in a `db`
+/// span under the request's span, so what it logs is attributed to the request,
+/// and records how long it queued for a thread.
+pub async fn block<F, R>(f: F) -> Result<R, BlockingError>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let request = tracing::Span::current();
+    let span = tracing::info_span!("db");
+    let queued = Instant::now();
+    web::block(move || {
+        crate::metrics::observe_queue(queued.elapsed());
+        crate::telemetry::with_request(request, || {
+            let _entered = span.enter();
+            let started = Instant::now();
+            let result = f();
+            tracing::debug!(
+                elapsed_ms = started.elapsed().as_millis() as u64,
+                "db call finished"
+            );
+            result
+        })
+    })
+    .await
+}
+
+pub fn authenticate(conn: &mut PgConnection, token: &str, secret: &str) -> Result<User, ApiError> {
+    crate::users::service::authenticate(conn, token, secret).map_err(|e| {
+        tracing::info!(reason = %e, "authentication failed");
+        ApiError::Unauthorized
     })
 }
 
-pub fn get_jwt_secret() -> String {
-    std::env::var("JWT_SECRET").expect("JWT_SECRET must be set in the environment")
+/// Escapes `%`, `_` and `\` so the text is matched literally by ILIKE.
+pub fn like_pattern(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('%');
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped.push('%');
+    escaped
 }
@@ -0,0 +1,155 @@
+//! Structured logging and request tracing.
+//!
+//! Log lines go to standard output as one JSON object each, or as plain text
+//! with `logging.format = "text"`. The `migrate` and `admin` commands log
+//! plain text to standard error instead, keeping their output clean. Every request runs in a `request` span that
+//! carries its id, method, route and, once known, the signed-in user, status
+//! and latency, so everything logged while handling it (including inside
+//! [`crate::services::block`]) can be traced back to it. The id is taken from
+//! the caller's `X-Request-Id` header when it looks sane, otherwise generated,
+//! and is sent back in the response.
+//!
+//! Bodies, headers and raw paths are never logged. Routes are logged as their
+//! patterns (`/bookings/{id}`) and query strings with the values of secret
+//! looking parameters replaced, see [`redact_query`].
+
+use crate::config::{LogFormat, LoggingConfig};
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use std::cell::RefCell;
+use std::io::IsTerminal;
+use std::time::Instant;
+use tracing::{field, Instrument, Span};
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+
+pub static REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Query parameters whose values are replaced in logs.
+const SECRET_PARAMS: &[&str] = &["password", "token", "secret", "code", "key", "signature"];
+
+thread_local! {
+    /// The request span on whose behalf a blocking call is running.
+    static REQUEST: RefCell<Option<Span>> = const { RefCell::new(None) };
+}
+
+/// Installs the global subscriber; `config.level` was validated at load.
+pub fn init(config: &LoggingConfig) {
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::new(&config.level))
+        .with_writer(std::io::stdout)
+        .with_ansi(std::io::stdout().is_terminal());
+    match config.format {
+        LogFormat::Json => builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(false)
+            .with_span_list(true)
+            .init(),
+        LogFormat::Text => builder.init(),
+    }
+}
+
+/// Installs a plain text subscriber writing to standard error, for commands
+/// whose standard output is their result.
+pub fn init_command(config: &LoggingConfig) {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::new(&config.level))
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .init();
+}
+
+/// Middleware running each request in its own span and logging its outcome.
+pub async fn trace_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let request_id = req
+        .headers()
+        .get(&REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_request_id(id))
+        .map(String::from)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let method = req.method().clone();
+    let route = req
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %method,
+        route = %route,
+        query = field::Empty,
+        user_id = field::Empty,
+        status = field::Empty,
+        latency_ms = field::Empty,
+    );
+    if !req.query_string().is_empty() {
+        span.record("query", redact_query(req.query_string()));
+    }
+
+    let started = Instant::now();
+    let result = next.call(req).instrument(span.clone()).await;
+    let elapsed = started.elapsed();
+    span.record("latency_ms", elapsed.as_millis() as u64);
+    let _entered = span.enter();
+
+    let mut res = result.inspect_err(|e| tracing::error!(error = %e, "request failed"))?;
+    let status = res.status();
+    span.record("status", status.as_u16());
+    crate::metrics::observe_request(method.as_str(), &route, status.as_u16(), elapsed);
+    if status.is_server_error() {
+        tracing::error!("request failed");
+    } else {
+        tracing::info!("request completed");
+    }
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res.headers_mut().insert(REQUEST_ID.clone(), value);
+    }
+    Ok(res)
+}
+
+/// Accepts ids of reasonable length made of visible ASCII, so a caller cannot
+/// inject line breaks or huge values into the logs.
+fn is_request_id(id: &str) -> bool {
+    (1..=128).contains(&id.len()) && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Runs `f` with `request` as the span [`record_user`] writes to. Used on the
+/// blocking thread pool, where the request's task context is not available.
+pub fn with_request<R>(request: Span, f: impl FnOnce() -> R) -> R {
+    let previous = REQUEST.with(|current| current.replace(Some(request)));
+    let result = f();
+    REQUEST.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+/// Notes the authenticated user on the current request's span.
+pub fn record_user(user_id: Uuid) {
+    REQUEST.with(|current| {
+        let current = current.borrow();
+        let span = current.clone().unwrap_or_else(Span::current);
+        span.record("user_id", field::display(user_id));
+    });
+}
+
+/// The query string with the values of secret looking parameters replaced.
+pub fn redact_query(query: &str) -> String {
+    query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if is_secret(name) => format!("{}=[redacted]", name),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn is_secret(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SECRET_PARAMS.iter().any(|secret| name.contains(secret))
+}
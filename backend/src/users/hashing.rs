@@ -0,0 +1,214 @@
+//! Argon2id password hashing with configurable cost and an optional pepper.
+//!
+//! Configured in the `[hashing]` section of the config file or through the
+//! environment:
+//!
+//! | Setting       | Variable              | Default | Meaning                              |
+//! |---------------|-----------------------|---------|--------------------------------------|
+//! | `memory_kib`  | `ARGON2_MEMORY_KIB`   | 19456   | Memory cost in KiB                   |
+//! | `iterations`  | `ARGON2_ITERATIONS`   | 2       | Time cost                            |
+//! | `parallelism` | `ARGON2_PARALLELISM`  | 1       | Lanes                                |
+//! | `pepper`      | `PASSWORD_PEPPER`     | unset   | Server-side secret mixed into hashes |
+//! | `pepper_id`   | `PASSWORD_PEPPER_ID`  | `p1`    | Up to 8 bytes naming the pepper      |
+//!
+//! Peppered hashes carry the pepper id as the PHC `keyid` parameter. Hashes
+//! made before a pepper was introduced still verify and are upgraded at the
+//! next sign-in; hashes made under a different pepper id can no longer be
+//! verified, so changing the pepper means those users reset their password.
+
+use argon2::password_hash::{self, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, ParamsBuilder, Version};
+use rand::rngs::OsRng;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HashingConfig {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    pub pepper: Option<String>,
+    pub pepper_id: String,
+}
+
+impl Default for HashingConfig {
+    fn default() -> Self {
+        HashingConfig {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+            pepper: None,
+            pepper_id: "p1".into(),
+        }
+    }
+}
+
+impl HashingConfig {
+    fn params(&self) -> password_hash::Result<Params> {
+        let mut builder = ParamsBuilder::new();
+        builder
+            .m_cost(self.memory_kib)?
+            .t_cost(self.iterations)?
+            .p_cost(self.parallelism)?;
+        if self.pepper.is_some() {
+            builder.keyid(self.pepper_id.as_bytes())?;
+        }
+        Ok(builder.params()?)
+    }
+
+    fn hasher(&self, params: Params) -> password_hash::Result<Argon2<'_>> {
+        match self.pepper {
+            Some(ref pepper) => Ok(Argon2::new_with_secret(
+                pepper.as_bytes(),
+                Algorithm::Argon2id,
+                Version::V0x13,
+                params,
+            )?),
+            None => Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params)),
+        }
+    }
+}
+
+pub fn hash_password(password: &str, config: &HashingConfig) -> password_hash::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = config
+        .hasher(config.params()?)?
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string();
+    Ok(hash)
+}
+
+/// Whether `password` matches `stored`. Hashes tagged with a pepper id other
+/// than the configured one cannot be checked and count as a mismatch.
+pub fn verify_password(
+    password: &str,
+    stored: &str,
+    config: &HashingConfig,
+) -> password_hash::Result<bool> {
+    let parsed = PasswordHash::new(stored)?;
+    let keyid = Params::try_from(&parsed)?.keyid().to_vec();
+
+    let verified = if keyid.is_empty() {
+        Argon2::default().verify_password(password.as_bytes(), &parsed)
+    } else if config.pepper.is_some() && keyid == config.pepper_id.as_bytes() {
+        config
+            .hasher(Params::default())?
+            .verify_password(password.as_bytes(), &parsed)
+    } else {
+        tracing::warn!(
+            pepper_id = %String::from_utf8_lossy(&keyid),
+            "password hash uses a pepper that is not configured"
+        );
+        return Ok(false);
+    };
+
+    match verified {
+        Ok(()) => Ok(true),
+        Err(password_hash::Error::Password) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether `stored` was made with anything weaker than the current settings
+/// or without the current pepper.
+pub fn needs_rehash(stored: &str, config: &HashingConfig) -> bool {
+    let Ok(parsed) = PasswordHash::new(stored) else {
+        return true;
+    };
+    let Ok(params) = Params::try_from(&parsed) else {
+        return true;
+    };
+    let wanted_keyid: &[u8] = match config.pepper {
+        Some(_) => config.pepper_id.as_bytes(),
+        None => &[],
+    };
+
+    parsed.algorithm != Algorithm::Argon2id.ident()
+        || parsed.version != Some(Version::V0x13.into())
+        || params.m_cost() < config.memory_kib
+        || params.t_cost() < config.iterations
+        || params.p_cost() < config.parallelism
+        || params.keyid() != wanted_keyid
+}
+
+/// `backend hash-benchmark [target-ms] [max-memory-mib]`: finds the largest
+/// memory cost, then the iteration count, that keeps one hash within the
+/// target time on this machine, and prints them as environment settings.
+pub fn run_benchmark(args: &[String]) -> anyhow::Result<()> {
+    let target = Duration::from_millis(args.first().and_then(|a| a.parse().ok()).unwrap_or(500));
+    let max_memory_kib = args
+        .get(1)
+        .and_then(|a| a.parse::<u32>().ok())
+        .unwrap_or(1024)
+        * 1024;
+    let parallelism = std::thread::available_parallelism()
+        .map(|n| n.get().min(4) as u32)
+        .unwrap_or(1);
+
+    let time = |memory_kib: u32, iterations: u32| -> anyhow::Result<Duration> {
+        let params = Params::new(memory_kib, iterations, parallelism, None)
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        let hasher = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+        let mut out = [0u8; 32];
+        let started = Instant::now();
+        hasher
+            .hash_password_into(b"benchmark password", b"benchmark salt", &mut out)
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        Ok(started.elapsed())
+    };
+
+    println!(
+        "Target {} ms per hash, up to {} MiB, {} lanes",
+        target.as_millis(),
+        max_memory_kib / 1024,
+        parallelism
+    );
+
+    let mut memory_kib = 19 * 1024;
+    let mut elapsed = time(memory_kib, 2)?;
+    println!(
+        "  m={:>7} KiB t=2  {:>5} ms",
+        memory_kib,
+        elapsed.as_millis()
+    );
+    while memory_kib * 2 <= max_memory_kib {
+        let next = time(memory_kib * 2, 2)?;
+        println!(
+            "  m={:>7} KiB t=2  {:>5} ms",
+            memory_kib * 2,
+            next.as_millis()
+        );
+        if next > target {
+            break;
+        }
+        memory_kib *= 2;
+        elapsed = next;
+    }
+
+    let mut iterations = 2;
+    while elapsed < target {
+        let next = time(memory_kib, iterations + 1)?;
+        println!(
+            "  m={:>7} KiB t={:<2} {:>5} ms",
+            memory_kib,
+            iterations + 1,
+            next.as_millis()
+        );
+        if next > target {
+            break;
+        }
+        iterations += 1;
+        elapsed = next;
+    }
+
+    println!();
+    if elapsed > target {
+        println!("Even the baseline exceeds the target; is this a debug build?");
+    }
+    println!("Suggested settings ({} ms per hash):", elapsed.as_millis());
+    println!("ARGON2_MEMORY_KIB={}", memory_kib);
+    println!("ARGON2_ITERATIONS={}", iterations);
+    println!("ARGON2_PARALLELISM={}", parallelism);
+    Ok(())
+}
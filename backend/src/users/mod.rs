@@ -1,8 +1,13 @@
+use crate::config::Config;
+use crate::errors::ApiError;
 use crate::models::NewUser;
-use crate::{services, DbPool};
+use crate::validation::{nfc_trim, Validate, ValidatedJson, Validator};
+use crate::{metrics, services, DbPool};
 use actix_web::{get, patch, post, web, HttpResponse};
 use serde::Deserialize;
 
+pub mod hashing;
+pub mod password;
 pub mod service;
 
 #[derive(Deserialize)]
@@ -14,12 +19,52 @@ pub struct CreateUserRequest {
     pub password: String,
 }
 
+#[derive(Deserialize)]
+pub struct ListUsersQuery {
+    /// Case-insensitive text matched against username, email and names.
+    pub q: Option<String>,
+    pub role: Option<String>,
+    pub active: Option<bool>,
+    pub locked: Option<bool>,
+    /// `true` lists only soft-deleted users instead of live ones.
+    pub deleted: Option<bool>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+impl Validate for CreateUserRequest {
+    fn normalize(&mut self) {
+        nfc_trim(&mut self.first_name);
+        nfc_trim(&mut self.last_name);
+        nfc_trim(&mut self.username);
+        nfc_trim(&mut self.email);
+        // Passwords are hashed exactly as typed; normalising them would lock
+        // out anybody whose password was stored in another form.
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        v.field("username", &self.username).length(3, 40).username();
+        v.field("first_name", &self.first_name).length(3, 100);
+        v.field("last_name", &self.last_name).length(3, 100);
+        v.field("email", &self.email).length(3, 255).email();
+        // The password policy is part of the settings; the service checks it.
+    }
+}
+
 #[derive(Deserialize)]
 pub struct SignInRequest {
     pub username_or_email: String,
     pub password: String,
 }
 
+impl Validate for SignInRequest {
+    fn normalize(&mut self) {
+        nfc_trim(&mut self.username_or_email);
+    }
+
+    fn validate(&self, _v: &mut Validator) {}
+}
+
 #[derive(Deserialize)]
 pub struct VerifyTok {
     pub token: Option<String>,
@@ -33,45 +78,79 @@ pub struct UpdateUserRequest {
     pub email: Option<String>,
 }
 
+impl Validate for UpdateUserRequest {
+    fn normalize(&mut self) {
+        for value in [
+            &mut self.username,
+            &mut self.first_name,
+            &mut self.last_name,
+            &mut self.email,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            nfc_trim(value);
+        }
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        v.optional("username", self.username.as_deref())
+            .length(3, 40)
+            .username();
+        v.optional("first_name", self.first_name.as_deref())
+            .length(3, 100);
+        v.optional("last_name", self.last_name.as_deref())
+            .length(3, 100);
+        v.optional("email", self.email.as_deref())
+            .length(3, 255)
+            .email();
+    }
+}
+
 #[derive(Deserialize)]
 pub struct UpdatePasswordRequest {
     pub old_password: String,
     pub new_password: String,
 }
 
+impl Validate for UpdatePasswordRequest {
+    // The password policy is checked by the service, which has the settings
+    // and knows whose username and email the password must not contain.
+    fn validate(&self, _v: &mut Validator) {}
+}
+
 #[post("/users")]
 pub async fn create_user_endpoint(
     pool: web::Data<DbPool>,
-    body: web::Json<CreateUserRequest>,
+    config: web::Data<Config>,
+    body: ValidatedJson<CreateUserRequest>,
 ) -> HttpResponse {
     let mut conn = match services::get_conn(&pool) {
         Ok(c) => c,
         Err(err) => return err,
     };
-    let secret = services::get_jwt_secret();
-
-    if let Err(err_msg) = service::validate_password(&body.password) {
-        return HttpResponse::BadRequest().body(format!("Password validation failed: {}", err_msg));
-    }
-
-    let password_hash = match service::hash_password(&body.password) {
-        Ok(hash) => hash,
-        Err(e) => {
-            eprintln!("Password hashing failed: {}", e);
-            return HttpResponse::InternalServerError().finish();
-        }
-    };
-
-    let new_user = NewUser {
-        first_name: body.first_name.clone(),
-        last_name: body.last_name.clone(),
-        username: body.username.clone(),
-        email: body.email.clone(),
-        password_hash,
-        token_version: 0,
-    };
 
-    match web::block(move || service::create_user(&mut conn, new_user, &secret)).await {
+    match services::block(move || {
+        service::ensure_password_allowed(
+            "password",
+            &body.password,
+            &[&body.username, &body.email],
+            &config.password,
+        )?;
+        let password_hash = hashing::hash_password(&body.password, &config.hashing)
+            .map_err(|e| anyhow::anyhow!("Password hashing failed: {}", e))?;
+        let new_user = NewUser {
+            first_name: body.first_name.clone(),
+            last_name: body.last_name.clone(),
+            username: body.username.clone(),
+            email: body.email.clone(),
+            password_hash,
+            token_version: 0,
+        };
+        service::create_user(&mut conn, new_user, &config.auth)
+    })
+    .await
+    {
         Ok(Ok((user, token))) => HttpResponse::Ok().json(serde_json::json!({
             "user": {
                 "username": user.username,
@@ -79,66 +158,61 @@ pub async fn create_user_endpoint(
             },
             "token": token
         })),
-        Ok(Err(e)) => {
-            eprintln!("Create user error: {}", e);
-            HttpResponse::InternalServerError().body(format!("Error creating user: {}", e))
-        }
-        Err(e) => {
-            eprintln!("Blocking error: {}", e);
-            HttpResponse::InternalServerError().body("Error creating user")
-        }
+        Ok(Err(e)) => e.into_response("Error creating user"),
+        Err(e) => ApiError::from(e).into_response("Error creating user"),
     }
 }
 
 #[get("/users")]
-pub async fn get_users_endpoint(pool: web::Data<DbPool>) -> HttpResponse {
+pub async fn get_users_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: actix_web::HttpRequest,
+    query: web::Query<ListUsersQuery>,
+) -> HttpResponse {
+    let token = match service::extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
     let mut conn = match services::get_conn(&pool) {
         Ok(c) => c,
         Err(err) => return err,
     };
+    let secret = config.auth.jwt_secret.clone();
 
-    match web::block(move || service::get_users(&mut conn)).await {
-        Ok(Ok(users)) => HttpResponse::Ok().json(
-            users
-                .into_iter()
-                .map(|user| {
-                    serde_json::json!({
-                        "username": user.username,
-                        "email": user.email,
-                        "first_name": user.first_name,
-                        "last_name": user.last_name,
-                        "roles": user.roles,
-                    })
-                })
-                .collect::<Vec<_>>(),
-        ),
-        Ok(Err(e)) => {
-            eprintln!("DB query error: {}", e);
-            HttpResponse::InternalServerError().body("Error fetching users")
-        }
-        Err(e) => {
-            eprintln!("Blocking error: {}", e);
-            HttpResponse::InternalServerError().body("Blocking error")
-        }
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::get_users(&mut conn, &user, query.into_inner())
+    })
+    .await
+    {
+        Ok(Ok(page)) => HttpResponse::Ok().json(page),
+        Ok(Err(e)) => e.into_response("Error fetching users"),
+        Err(e) => ApiError::from(e).into_response("Error fetching users"),
     }
 }
 
 #[post("/sign-in")]
 pub async fn sign_in_endpoint(
     pool: web::Data<DbPool>,
-    body: web::Json<SignInRequest>,
+    config: web::Data<Config>,
+    body: ValidatedJson<SignInRequest>,
 ) -> HttpResponse {
     let mut conn = match services::get_conn(&pool) {
         Ok(c) => c,
         Err(err) => return err,
     };
-    let secret = services::get_jwt_secret();
 
-    match web::block(move || {
-        service::signin_user(&mut conn, &body.username_or_email, &body.password, &secret)
+    let result = services::block(move || {
+        service::signin_user(&mut conn, &body.username_or_email, &body.password, &config)
     })
-    .await
-    {
+    .await;
+    if let Ok(ref outcome) = result {
+        metrics::sign_in(outcome.as_ref().map(|_| ()));
+    }
+
+    match result {
         Ok(Ok((user, token))) => HttpResponse::Ok().json(serde_json::json!({
             "user": {
                 "username": user.username,
@@ -148,27 +222,22 @@ pub async fn sign_in_endpoint(
             },
             "token": token
         })),
-        Ok(Err(e)) => {
-            eprintln!("Sign-in error: {}", e);
-            HttpResponse::Unauthorized().body("Invalid username/email or password {}")
-        }
-        Err(e) => {
-            eprintln!("Blocking error: {}", e);
-            HttpResponse::InternalServerError().body("Error signing in")
-        }
+        Ok(Err(e)) => e.into_response("Error signing in"),
+        Err(e) => ApiError::from(e).into_response("Error signing in"),
     }
 }
 
 #[post("/users/verify/token")]
 pub async fn users_verify_token_endpoint(
     pool: web::Data<DbPool>,
+    config: web::Data<Config>,
     body: web::Json<VerifyTok>,
 ) -> HttpResponse {
     let token = match &body.token {
         Some(t) => t.clone(),
         None => {
-            return HttpResponse::BadRequest()
-                .json(serde_json::json!({"valid": false, "reason": "no_token"}));
+            return ApiError::MissingCredentials("Missing token")
+                .into_response("Error verifying token");
         }
     };
 
@@ -176,30 +245,49 @@ pub async fn users_verify_token_endpoint(
         Ok(c) => c,
         Err(resp) => return resp,
     };
-    let secret = services::get_jwt_secret();
+    let secret = config.auth.jwt_secret.clone();
 
-    match web::block(move || service::verify_token(&mut conn, &token, &secret)).await {
+    match services::block(move || service::verify_token(&mut conn, &token, &secret)).await {
         Ok(Ok((true, _))) => HttpResponse::Ok().json(serde_json::json!({"valid": true})),
         Ok(Ok((false, reason))) => HttpResponse::Ok().json(serde_json::json!({
             "valid": false,
             "reason": reason
         })),
-        Ok(Err(e)) => {
-            eprintln!("verify_token error: {}", e);
-            HttpResponse::InternalServerError().body("Error verifying token")
-        }
-        Err(e) => {
-            eprintln!("blocking error: {}", e);
-            HttpResponse::InternalServerError().body("Error verifying token")
-        }
+        Ok(Err(e)) => e.into_response("Error verifying token"),
+        Err(e) => ApiError::from(e).into_response("Error verifying token"),
+    }
+}
+
+#[get("/user")]
+pub async fn get_current_user_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: actix_web::HttpRequest,
+) -> HttpResponse {
+    let token = match service::extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || service::get_profile(&mut conn, &token, &secret)).await {
+        Ok(Ok(profile)) => HttpResponse::Ok().json(profile),
+        Ok(Err(e)) => e.into_response("Error fetching user"),
+        Err(e) => ApiError::from(e).into_response("Error fetching user"),
     }
 }
 
 #[patch("/user")]
 pub async fn update_user_endpoint(
     pool: web::Data<DbPool>,
+    config: web::Data<Config>,
     req: actix_web::HttpRequest,
-    body: web::Json<UpdateUserRequest>,
+    body: ValidatedJson<UpdateUserRequest>,
 ) -> HttpResponse {
     let token = match service::extract_bearer_token(&req) {
         Ok(t) => t,
@@ -210,10 +298,12 @@ pub async fn update_user_endpoint(
         Ok(c) => c,
         Err(err) => return err,
     };
-    let secret = services::get_jwt_secret();
+    let secret = config.auth.jwt_secret.clone();
 
-    match web::block(move || service::update_user(&mut conn, &token, &secret, body.into_inner()))
-        .await
+    match services::block(move || {
+        service::update_user(&mut conn, &token, &secret, body.into_inner())
+    })
+    .await
     {
         Ok(Ok(user)) => HttpResponse::Ok().json(serde_json::json!({
             "user": {
@@ -223,22 +313,17 @@ pub async fn update_user_endpoint(
                 "last_name": user.last_name,
             }
         })),
-        Ok(Err(e)) => {
-            eprintln!("Update user error: {}", e);
-            HttpResponse::BadRequest().body(format!("Error updating user: {}", e))
-        }
-        Err(e) => {
-            eprintln!("Blocking error: {}", e);
-            HttpResponse::InternalServerError().body("Error updating user")
-        }
+        Ok(Err(e)) => e.into_response("Error updating user"),
+        Err(e) => ApiError::from(e).into_response("Error updating user"),
     }
 }
 
 #[patch("/user/password")]
 pub async fn update_user_password_endpoint(
     pool: web::Data<DbPool>,
+    config: web::Data<Config>,
     req: actix_web::HttpRequest,
-    body: web::Json<UpdatePasswordRequest>,
+    body: ValidatedJson<UpdatePasswordRequest>,
 ) -> HttpResponse {
     let token = match service::extract_bearer_token(&req) {
         Ok(t) => t,
@@ -249,21 +334,14 @@ pub async fn update_user_password_endpoint(
         Ok(c) => c,
         Err(err) => return err,
     };
-    let secret = services::get_jwt_secret();
 
-    match web::block(move || {
-        service::update_password(&mut conn, &token, &secret, body.into_inner())
+    match services::block(move || {
+        service::update_password(&mut conn, &token, body.into_inner(), &config)
     })
     .await
     {
         Ok(Ok(())) => HttpResponse::Ok().json(serde_json::json!({"success": true})),
-        Ok(Err(e)) => {
-            eprintln!("Update password error: {}", e);
-            HttpResponse::BadRequest().body(format!("Error updating password: {}", e))
-        }
-        Err(e) => {
-            eprintln!("Blocking error: {}", e);
-            HttpResponse::InternalServerError().body("Error updating password")
-        }
+        Ok(Err(e)) => e.into_response("Error updating password"),
+        Err(e) => ApiError::from(e).into_response("Error updating password"),
     }
 }
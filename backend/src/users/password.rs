@@ -0,0 +1,249 @@
+//! Password policy: length and character classes, a rough strength estimate,
+//! personal-information checks and an optional breached-password list.
+//!
+//! Configured in the `[password]` section of the config file or through the
+//! environment:
+//!
+//! | Setting             | Variable                      | Default |
+//! |---------------------|-------------------------------|---------|
+//! | `min_length`        | `PASSWORD_MIN_LENGTH`         | 8       |
+//! | `max_length`        | `PASSWORD_MAX_LENGTH`         | 128     |
+//! | `require_uppercase` | `PASSWORD_REQUIRE_UPPERCASE`  | true    |
+//! | `require_lowercase` | `PASSWORD_REQUIRE_LOWERCASE`  | true    |
+//! | `require_digit`     | `PASSWORD_REQUIRE_DIGIT`      | true    |
+//! | `require_symbol`    | `PASSWORD_REQUIRE_SYMBOL`     | true    |
+//! | `min_entropy_bits`  | `PASSWORD_MIN_ENTROPY_BITS`   | 40      |
+//! | `breached_list`     | `PASSWORD_BREACHED_LIST`      | unset   |
+//!
+//! The breached list points at a local copy of a k-anonymity style
+//! SHA-1 list, so no password or hash ever leaves the server. It is either a
+//! directory of range files named after the first five hex digits of the hash
+//! (`ABCDE` or `ABCDE.txt`, one `SUFFIX:COUNT` per line), or a single file
+//! with one full `HASH:COUNT` per line, sorted by hash as the downloadable
+//! lists are, which is binary searched. Looking a password up reads the disk,
+//! so [`PasswordPolicy::check_breached`] is separate from
+//! [`PasswordPolicy::check`] and only runs on the blocking thread pool.
+
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    pub min_entropy_bits: f64,
+    pub breached_list: Option<PathBuf>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: 8,
+            max_length: 128,
+            require_uppercase: true,
+            require_lowercase: true,
+            require_digit: true,
+            require_symbol: true,
+            min_entropy_bits: 40.0,
+            breached_list: None,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Checks `password` against the policy. `personal` holds the username,
+    /// email and similar values the password must not contain.
+    pub fn check(&self, password: &str, personal: &[&str]) -> Result<(), String> {
+        let length = password.chars().count();
+        if length < self.min_length {
+            return Err(format!(
+                "Password must be at least {} characters long",
+                self.min_length
+            ));
+        }
+        if length > self.max_length {
+            return Err(format!(
+                "Password must be at most {} characters long",
+                self.max_length
+            ));
+        }
+
+        if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+            return Err("Password must contain at least one uppercase letter".into());
+        }
+        if self.require_lowercase && !password.chars().any(char::is_lowercase) {
+            return Err("Password must contain at least one lowercase letter".into());
+        }
+        if self.require_digit && !password.chars().any(char::is_numeric) {
+            return Err("Password must contain at least one number".into());
+        }
+        if self.require_symbol && !password.chars().any(is_symbol) {
+            return Err("Password must contain at least one special character".into());
+        }
+
+        check_personal(password, personal)?;
+
+        if estimate_entropy_bits(password) < self.min_entropy_bits {
+            return Err(
+                "Password is too predictable; use a longer or less repetitive password".into(),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Rejects passwords found on the breached list, if one is configured.
+    /// Reads from disk; call it from blocking code, never while extracting a
+    /// request.
+    pub fn check_breached(&self, password: &str) -> Result<(), String> {
+        let Some(ref list) = self.breached_list else {
+            return Ok(());
+        };
+        match is_breached(list, password) {
+            Ok(true) => {
+                Err("Password appears in a list of breached passwords; choose another".into())
+            }
+            Ok(false) => Ok(()),
+            // A missing or unreadable list must not lock everyone out.
+            Err(e) => {
+                tracing::warn!(list = %list.display(), error = %e, "breached password list unusable");
+                Ok(())
+            }
+        }
+    }
+}
+
+fn is_symbol(c: char) -> bool {
+    !c.is_alphanumeric() && !c.is_whitespace()
+}
+
+/// Rejects passwords containing any personal value (or the local part of an
+/// email address) of three or more characters, ignoring case.
+pub fn check_personal(password: &str, personal: &[&str]) -> Result<(), String> {
+    let lowered = password.to_lowercase();
+    let contains = personal
+        .iter()
+        .flat_map(|value| [*value, value.split('@').next().unwrap_or(value)])
+        .map(|value| value.trim().to_lowercase())
+        .filter(|value| value.chars().count() >= 3)
+        .any(|value| lowered.contains(&value));
+    if contains {
+        Err("Password must not contain your username or email".into())
+    } else {
+        Ok(())
+    }
+}
+
+/// A rough estimate in bits: each character is worth `log2` of the alphabet
+/// the password draws from, except repeats and steps of one from the previous
+/// character (`aaaa`, `abcd`, `4321`), which are worth a single bit.
+pub fn estimate_entropy_bits(password: &str) -> f64 {
+    let mut alphabet = 0u32;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        alphabet += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        alphabet += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        alphabet += 10;
+    }
+    if password.chars().any(|c| c.is_ascii() && is_symbol(c)) {
+        alphabet += 33;
+    }
+    if !password.is_ascii() {
+        alphabet += 100;
+    }
+    let per_char = f64::from(alphabet.max(1)).log2();
+
+    let mut bits = 0.0;
+    let mut previous: Option<char> = None;
+    for c in password.chars() {
+        let predictable = previous
+            .is_some_and(|p| (i64::from(u32::from(c)) - i64::from(u32::from(p))).abs() <= 1);
+        bits += if predictable { 1.0 } else { per_char };
+        previous = Some(c);
+    }
+    bits
+}
+
+fn is_breached(list: &Path, password: &str) -> std::io::Result<bool> {
+    let hash = hex::encode_upper(Sha1::digest(password.as_bytes()));
+    let (prefix, suffix) = hash.split_at(5);
+
+    if list.is_dir() {
+        let range = [list.join(prefix), list.join(format!("{}.txt", prefix))]
+            .into_iter()
+            .find(|p| p.is_file());
+        match range {
+            Some(path) => range_contains(&path, suffix),
+            None => Ok(false),
+        }
+    } else {
+        sorted_contains(list, &hash)
+    }
+}
+
+/// The hash part of a `HASH:COUNT` line, upper-cased.
+fn entry_of(line: &str) -> String {
+    line.split(':')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_uppercase()
+}
+
+/// Whether a range file, a few hundred lines at most, holds `suffix`.
+fn range_contains(path: &Path, suffix: &str) -> std::io::Result<bool> {
+    for line in BufReader::new(File::open(path)?).lines() {
+        if entry_of(&line?) == suffix {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Binary search over the lines of a file sorted by hash. Each step seeks to
+/// the middle of the remaining byte range and compares the first line that
+/// starts there or later.
+fn sorted_contains(path: &Path, hash: &str) -> std::io::Result<bool> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut low = 0;
+    let mut high = file.get_ref().metadata()?.len();
+    let mut line = Vec::new();
+
+    // Lines starting in `low..high` are still candidates.
+    while low < high {
+        let mid = low + (high - low) / 2;
+        let start = if mid == 0 {
+            file.seek(SeekFrom::Start(0))?;
+            0
+        } else {
+            file.seek(SeekFrom::Start(mid - 1))?;
+            line.clear();
+            mid - 1 + file.read_until(b'\n', &mut line)? as u64
+        };
+        line.clear();
+        let read = file.read_until(b'\n', &mut line)? as u64;
+        if start >= high || read == 0 {
+            high = mid;
+            continue;
+        }
+
+        match entry_of(&String::from_utf8_lossy(&line)).as_str().cmp(hash) {
+            Ordering::Equal => return Ok(true),
+            Ordering::Less => low = start + read,
+            Ordering::Greater => high = mid,
+        }
+    }
+    Ok(false)
+}
@@ -1,17 +1,19 @@
+use crate::config::{AuthConfig, Config};
+use crate::errors::{ApiError, FieldError};
 use crate::models::{NewUser, User, UserBasic};
-use crate::users::{UpdatePasswordRequest, UpdateUserRequest};
-use actix_web::{HttpRequest, HttpResponse};
+use crate::services::like_pattern;
+use crate::users::hashing;
+use crate::users::password::PasswordPolicy;
+use crate::users::{ListUsersQuery, UpdatePasswordRequest, UpdateUserRequest};
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
 use anyhow::{anyhow, Result};
-use argon2::{password_hash::SaltString, Argon2, PasswordHasher, PasswordVerifier};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
+use diesel::sql_types::{Nullable, Text};
 use jsonwebtoken::errors::ErrorKind;
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
-use rand::rngs::OsRng;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::env;
 use uuid::Uuid;
 
 #[derive(Serialize, Deserialize)]
@@ -22,86 +24,23 @@ struct Claims {
     exp: i64,
 }
 
-pub(crate) fn validate_password(password: &str) -> Result<(), String> {
-    if password.len() < 8 {
-        return Err("Password must be at least 8 characters long".into());
-    }
-
-    if !password.chars().any(|c| c.is_uppercase()) {
-        return Err("Password must contain at least one uppercase letter".into());
-    }
-
-    if !password.chars().any(|c| c.is_lowercase()) {
-        return Err("Password must contain at least one lowercase letter".into());
-    }
-
-    if !password.chars().any(|c| c.is_numeric()) {
-        return Err("Password must contain at least one number".into());
-    }
-
-    let special_chars = "!@#$%^&*()-_=+[]{}|;:'\",.<>?/`~";
-    if !password.chars().any(|c| special_chars.contains(c)) {
-        return Err("Password must contain at least one special character".into());
-    }
-
-    Ok(())
-}
-
-pub fn validate_user_fields(
-    username: Option<&str>,
-    first_name: Option<&str>,
-    last_name: Option<&str>,
-    email: Option<&str>,
-) -> Result<(), String> {
-    if username.map(|u| u.len() < 3).unwrap_or(false) {
-        return Err("Username must be at least 3 characters long".into());
-    }
-
-    if first_name.map(|f| f.len() < 3).unwrap_or(false) {
-        return Err("First name must be at least 3 characters long".into());
-    }
-
-    if last_name.map(|l| l.len() < 3).unwrap_or(false) {
-        return Err("Last name must be at least 3 characters long".into());
-    }
-
-    if email.map(|e| e.len() < 3).unwrap_or(false) {
-        return Err("Email must be at least 3 characters long".into());
-    }
-
-    Ok(())
-}
-
 fn generate_new_token_version() -> i32 {
     rand::thread_rng().gen_range(1..=i32::MAX)
 }
 
-fn get_jwt_expire() -> i64 {
-    env::var("JWT_EXPIRE_SECONDS")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(3600) // 1 hour
-}
-
+#[allow(clippy::result_large_err)]
 pub fn extract_bearer_token(req: &HttpRequest) -> Result<String, HttpResponse> {
-    match req.headers().get("Authorization") {
+    let reason = match req.headers().get("Authorization") {
         Some(hdr_value) => match hdr_value.to_str() {
-            Ok(s) if s.starts_with("Bearer ") => Ok(s.trim_start_matches("Bearer ").to_string()),
-            Ok(_) => Err(HttpResponse::Unauthorized().body("Invalid Authorization header format")),
-            Err(_) => Err(HttpResponse::Unauthorized().body("Invalid header value")),
+            Ok(s) if s.starts_with("Bearer ") => {
+                return Ok(s.trim_start_matches("Bearer ").to_string())
+            }
+            Ok(_) => "Invalid Authorization header format",
+            Err(_) => "Invalid header value",
         },
-        None => Err(HttpResponse::Unauthorized().body("Missing Authorization header")),
-    }
-}
-
-pub fn hash_password(password: &str) -> std::result::Result<String, argon2::password_hash::Error> {
-    let argon2 = Argon2::default();
-    let salt = SaltString::generate(&mut OsRng);
-
-    let hash = argon2
-        .hash_password(password.as_bytes(), &salt)?
-        .to_string();
-    Ok(hash)
+        None => "Missing Authorization header",
+    };
+    Err(ApiError::MissingCredentials(reason).error_response())
 }
 
 pub fn generate_jwt(user: &User, secret: &str, expire_seconds: i64) -> String {
@@ -139,97 +78,240 @@ pub fn username_exists(conn: &mut PgConnection, username_check: &str) -> Result<
     Ok(exists.is_some())
 }
 
-pub fn create_user(
-    conn: &mut PgConnection,
-    new_user: NewUser,
-    secret: &str,
-) -> Result<(User, String)> {
-    use anyhow::anyhow;
-
-    validate_user_fields(
-        Some(&new_user.username),
-        Some(&new_user.first_name),
-        Some(&new_user.last_name),
-        Some(&new_user.email),
-    )
-    .map_err(|e| anyhow::anyhow!(e))?;
-
+fn ensure_available(conn: &mut PgConnection, new_user: &NewUser) -> Result<(), ApiError> {
+    let mut taken = Vec::new();
     if email_exists(conn, &new_user.email)? {
-        return Err(anyhow!("Email already in use"));
+        taken.push(FieldError::new("email", "Email already in use"));
     }
-
     if username_exists(conn, &new_user.username)? {
-        return Err(anyhow!("Username already in use"));
+        taken.push(FieldError::new("username", "Username already in use"));
+    }
+    if !taken.is_empty() {
+        return Err(ApiError::Validation(taken));
     }
+    Ok(())
+}
+
+pub fn insert_user(conn: &mut PgConnection, new_user: NewUser) -> Result<User, ApiError> {
+    ensure_available(conn, &new_user)?;
 
     use crate::schema::users::dsl::*;
-    let user: User = diesel::insert_into(users)
+    Ok(diesel::insert_into(users)
         .values(&new_user)
-        .get_result(conn)?;
+        .get_result(conn)?)
+}
 
-    let token = generate_jwt(&user, secret, get_jwt_expire());
+pub fn create_user(
+    conn: &mut PgConnection,
+    new_user: NewUser,
+    auth: &AuthConfig,
+) -> Result<(User, String), ApiError> {
+    let user = insert_user(conn, new_user)?;
+    let token = generate_jwt(&user, &auth.jwt_secret, auth.token_lifetime_secs);
     Ok((user, token))
 }
 
-pub fn get_users(conn: &mut PgConnection) -> QueryResult<Vec<UserBasic>> {
+/// Creates the first owner of a new installation. Refused once any live user
+/// holds the owner role, so it cannot be used to take over a running system.
+pub fn bootstrap_owner(conn: &mut PgConnection, new_user: NewUser) -> Result<User, ApiError> {
+    use crate::schema::roles::dsl as r_dsl;
+    use crate::schema::users::dsl as u_dsl;
+    use crate::schema::users_roles::dsl as ur_dsl;
+
+    conn.transaction(|conn| {
+        // Locking the role row makes concurrent bootstraps wait for each other.
+        let owner_role: i32 = r_dsl::roles
+            .filter(r_dsl::name.eq("owner"))
+            .select(r_dsl::id)
+            .for_update()
+            .first(conn)?;
+        let owners: i64 = ur_dsl::users_roles
+            .inner_join(u_dsl::users)
+            .filter(ur_dsl::role_id.eq(owner_role))
+            .filter(u_dsl::deleted_at.is_null())
+            .count()
+            .get_result(conn)?;
+        if owners > 0 {
+            return Err(ApiError::Invalid(
+                "An owner already exists; manage accounts through it instead".into(),
+            ));
+        }
+
+        let user = insert_user(conn, new_user)?;
+        diesel::insert_into(ur_dsl::users_roles)
+            .values((ur_dsl::user_id.eq(user.id), ur_dsl::role_id.eq(owner_role)))
+            .execute(conn)?;
+        Ok(user)
+    })
+}
+
+diesel::define_sql_function! {
+    #[aggregate]
+    fn array_agg(value: Nullable<Text>) -> Array<Nullable<Text>>;
+}
+
+const DEFAULT_USERS_LIMIT: i64 = 50;
+const MAX_USERS_LIMIT: i64 = 200;
+
+#[derive(Debug, Serialize)]
+pub struct UserPage {
+    pub items: Vec<UserBasic>,
+    /// Pass as `cursor` to fetch the next page; absent on the last page.
+    pub next_cursor: Option<String>,
+}
+
+/// Users ordered by username, one page at a time. The cursor is the
+/// hex-encoded username of the last user on the previous page.
+pub fn get_users(
+    conn: &mut PgConnection,
+    actor: &User,
+    query: ListUsersQuery,
+) -> Result<UserPage, ApiError> {
+    if !has_permission(conn, actor.id, "users:view")? {
+        return Err(ApiError::Forbidden);
+    }
+    list_users(conn, query)
+}
+
+/// One page of users matching `query`, without any permission check.
+pub fn list_users(conn: &mut PgConnection, query: ListUsersQuery) -> Result<UserPage, ApiError> {
     use crate::schema::roles::dsl as roles_dsl;
     use crate::schema::users::dsl as users_dsl;
     use crate::schema::users_roles::dsl as ur_dsl;
 
-    let user_rows = users_dsl::users
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_USERS_LIMIT)
+        .clamp(1, MAX_USERS_LIMIT);
+
+    let mut found = users_dsl::users
+        .left_join(ur_dsl::users_roles.left_join(roles_dsl::roles))
+        .group_by(users_dsl::id)
         .select((
             users_dsl::id,
             users_dsl::username,
             users_dsl::email,
             users_dsl::first_name,
             users_dsl::last_name,
+            users_dsl::is_active,
+            users_dsl::locked_until,
+            users_dsl::deleted_at,
+            array_agg(roles_dsl::name.nullable()),
         ))
-        .load::<(Uuid, String, String, String, String)>(conn)?;
-
-    let user_ids: Vec<Uuid> = user_rows.iter().map(|(id, _, _, _, _)| *id).collect();
-
-    let roles_rows = if user_ids.is_empty() {
-        Vec::new()
+        .into_boxed();
+
+    if let Some(ref cursor) = query.cursor {
+        let after = hex::decode(cursor)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or_else(|| ApiError::Invalid("Invalid cursor".into()))?;
+        found = found.filter(users_dsl::username.gt(after));
+    }
+    if let Some(text) = query.q.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+        let pattern = like_pattern(text);
+        found = found.filter(
+            users_dsl::username
+                .ilike(pattern.clone())
+                .or(users_dsl::email.ilike(pattern.clone()))
+                .or(users_dsl::first_name.ilike(pattern.clone()))
+                .or(users_dsl::last_name.ilike(pattern)),
+        );
+    }
+    if let Some(ref role) = query.role {
+        let with_role: Vec<Uuid> = ur_dsl::users_roles
+            .inner_join(roles_dsl::roles)
+            .filter(roles_dsl::name.eq(role))
+            .select(ur_dsl::user_id)
+            .load(conn)?;
+        found = found.filter(users_dsl::id.eq_any(with_role));
+    }
+    if let Some(active) = query.active {
+        found = found.filter(users_dsl::is_active.eq(active));
+    }
+    if let Some(locked) = query.locked {
+        let now = Utc::now();
+        found = if locked {
+            found.filter(users_dsl::locked_until.gt(now))
+        } else {
+            found.filter(
+                users_dsl::locked_until
+                    .is_null()
+                    .or(users_dsl::locked_until.le(now)),
+            )
+        };
+    }
+    found = if query.deleted.unwrap_or(false) {
+        found.filter(users_dsl::deleted_at.is_not_null())
     } else {
-        ur_dsl::users_roles
-            .inner_join(roles_dsl::roles.on(roles_dsl::id.eq(ur_dsl::role_id)))
-            .select((ur_dsl::user_id, roles_dsl::name))
-            .filter(ur_dsl::user_id.eq_any(&user_ids))
-            .load::<(Uuid, String)>(conn)?
+        found.filter(users_dsl::deleted_at.is_null())
     };
 
-    let mut roles_map: HashMap<Uuid, Vec<String>> = HashMap::new();
-    for (uid, role_name) in roles_rows {
-        roles_map.entry(uid).or_default().push(role_name);
-    }
-
-    let users: Vec<UserBasic> = user_rows
+    let rows = found
+        .order(users_dsl::username.asc())
+        .limit(limit + 1)
+        .load::<(
+            Uuid,
+            String,
+            String,
+            String,
+            String,
+            bool,
+            Option<DateTime<Utc>>,
+            Option<DateTime<Utc>>,
+            Vec<Option<String>>,
+        )>(conn)?;
+
+    let mut items: Vec<UserBasic> = rows
         .into_iter()
-        .map(|(id, username, email, first_name, last_name)| {
-            let roles_for_user = roles_map
-                .remove(&id)
-                .unwrap_or_else(|| vec!["default".to_string()]);
-            UserBasic {
+        .map(
+            |(
+                id,
                 username,
                 email,
                 first_name,
                 last_name,
-                roles: roles_for_user,
-            }
-        })
+                is_active,
+                locked_until,
+                deleted_at,
+                role_names,
+            )| {
+                let mut roles: Vec<String> = role_names.into_iter().flatten().collect();
+                roles.sort();
+                if roles.is_empty() {
+                    roles.push("default".to_string());
+                }
+                UserBasic {
+                    id,
+                    username,
+                    email,
+                    first_name,
+                    last_name,
+                    is_active,
+                    locked_until,
+                    deleted_at,
+                    roles,
+                }
+            },
+        )
         .collect();
 
-    Ok(users)
+    let next_cursor = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        items.last().map(|user| hex::encode(&user.username))
+    } else {
+        None
+    };
+
+    Ok(UserPage { items, next_cursor })
 }
 
 pub fn signin_user(
     conn: &mut PgConnection,
     username_or_email: &str,
     password: &str,
-    secret: &str,
-) -> Result<(User, String)> {
+    config: &Config,
+) -> Result<(User, String), ApiError> {
     use crate::schema::users::dsl::*;
-    use anyhow::anyhow;
 
     let user = users
         .filter(
@@ -238,26 +320,56 @@ pub fn signin_user(
                 .or(email.eq(username_or_email)),
         )
         .first::<User>(conn)
-        .map_err(|_| anyhow!("Invalid username/email or password"))?;
+        .optional()?
+        .ok_or(ApiError::InvalidCredentials)?;
 
-    let parsed_hash = argon2::PasswordHash::new(&user.password_hash)
-        .map_err(|_| anyhow!("Failed to parse password hash"))?;
+    let matches = hashing::verify_password(password, &user.password_hash, &config.hashing)
+        .map_err(|e| anyhow!("Failed to verify password hash: {}", e))?;
+    if !matches {
+        return Err(ApiError::InvalidCredentials);
+    }
+    if let Some(until) = user.locked_until
+        && until > Utc::now()
+    {
+        return Err(ApiError::Locked(until));
+    }
 
-    Argon2::default()
-        .verify_password(password.as_bytes(), &parsed_hash)
-        .map_err(|_| anyhow!("Invalid username/email or password"))?;
+    // Upgrade hashes made with weaker parameters or an older pepper while the
+    // plaintext is at hand.
+    if hashing::needs_rehash(&user.password_hash, &config.hashing) {
+        match hashing::hash_password(password, &config.hashing) {
+            Ok(upgraded) => {
+                diesel::update(users.find(user.id))
+                    .set(password_hash.eq(upgraded))
+                    .execute(conn)?;
+            }
+            Err(e) => tracing::warn!(user_id = %user.id, error = %e, "rehashing password failed"),
+        }
+    }
 
     let new_token_version: i32 = generate_new_token_version();
     let updated_user = diesel::update(users.find(user.id))
-        .set(token_version.eq(new_token_version))
+        .set((
+            token_version.eq(new_token_version),
+            last_login_at.eq(Utc::now()),
+        ))
         .get_result::<User>(conn)?;
 
-    let token = generate_jwt(&updated_user, secret, get_jwt_expire());
+    let token = generate_jwt(
+        &updated_user,
+        &config.auth.jwt_secret,
+        config.auth.token_lifetime_secs,
+    );
+    crate::telemetry::record_user(updated_user.id);
 
     Ok((updated_user, token))
 }
 
-pub fn verify_token(conn: &mut PgConnection, token: &str, secret: &str) -> Result<(bool, String)> {
+pub fn verify_token(
+    conn: &mut PgConnection,
+    token: &str,
+    secret: &str,
+) -> Result<(bool, String), ApiError> {
     use crate::schema::users::dsl::*;
 
     match decode::<Claims>(
@@ -285,12 +397,7 @@ pub fn verify_token(conn: &mut PgConnection, token: &str, secret: &str) -> Resul
     }
 }
 
-pub fn update_user(
-    conn: &mut PgConnection,
-    token: &str,
-    secret: &str,
-    data: UpdateUserRequest,
-) -> Result<User> {
+pub fn authenticate(conn: &mut PgConnection, token: &str, secret: &str) -> Result<User> {
     use crate::schema::users::dsl::*;
 
     let claims = decode::<Claims>(
@@ -300,34 +407,167 @@ pub fn update_user(
     )?
     .claims;
 
-    let current_token_version: i32 = users
-        .filter(id.eq(claims.sub))
-        .select(token_version)
-        .first(conn)?;
+    let user = users
+        .find(claims.sub)
+        .filter(deleted_at.is_null())
+        .first::<User>(conn)
+        .optional()?
+        .ok_or_else(|| anyhow!("User not found"))?;
 
-    if current_token_version != claims.token_version {
-        return Err(anyhow::anyhow!("Invalid or expired token"));
+    if user.token_version != claims.token_version {
+        return Err(anyhow!("Invalid or expired token"));
     }
 
-    if let Some(ref new_username) = data.username {
-        if username_exists(conn, new_username)? {
-            return Err(anyhow::anyhow!("Username already taken"));
-        }
-    }
+    crate::telemetry::record_user(user.id);
+    Ok(user)
+}
 
-    if let Some(ref new_email) = data.email {
-        if email_exists(conn, new_email)? {
-            return Err(anyhow::anyhow!("Email already in use"));
-        }
-    }
+/// Everything a client needs to rebuild its state from a stored token.
+#[derive(Debug, Serialize)]
+pub struct UserProfile {
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub is_active: bool,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub last_login_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub roles: Vec<String>,
+    /// Union of the permissions granted by every role.
+    pub permissions: Vec<String>,
+    /// Neither is tracked by the server yet, so both are always `null`.
+    pub two_factor_enabled: Option<bool>,
+    pub email_verified: Option<bool>,
+    pub session: SessionInfo,
+}
 
-    validate_user_fields(
-        data.username.as_deref(),
-        data.first_name.as_deref(),
-        data.last_name.as_deref(),
-        data.email.as_deref(),
+#[derive(Debug, Serialize)]
+pub struct SessionInfo {
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Rotated on every sign-in and password change; tokens carrying an older
+    /// version are rejected, so only the latest session stays valid.
+    pub token_version: i32,
+}
+
+pub fn get_profile(
+    conn: &mut PgConnection,
+    token: &str,
+    secret: &str,
+) -> Result<UserProfile, ApiError> {
+    let user = crate::services::authenticate(conn, token, secret)?;
+    let claims = decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(secret.as_ref()),
+        &Validation::default(),
     )
-    .map_err(|e| anyhow::anyhow!(e))?;
+    .map_err(|_| ApiError::Unauthorized)?
+    .claims;
+
+    let mut roles = get_user_roles(conn, user.id)?;
+    if roles.is_empty() {
+        roles.push("default".to_string());
+    }
+    let mut permissions = get_user_permissions(conn, user.id)?;
+    permissions.sort();
+
+    Ok(UserProfile {
+        id: user.id,
+        username: user.username,
+        email: user.email,
+        first_name: user.first_name,
+        last_name: user.last_name,
+        is_active: user.is_active,
+        locked_until: user.locked_until,
+        last_login_at: user.last_login_at,
+        created_at: user.created_at,
+        roles,
+        permissions,
+        two_factor_enabled: None,
+        email_verified: None,
+        session: SessionInfo {
+            expires_at: DateTime::from_timestamp(claims.exp, 0),
+            token_version: user.token_version,
+        },
+    })
+}
+
+pub fn get_user_permissions(conn: &mut PgConnection, user_uuid: Uuid) -> QueryResult<Vec<String>> {
+    use crate::schema::permissions::dsl as p_dsl;
+    use crate::schema::roles_permissions::dsl as rp_dsl;
+    use crate::schema::users_roles::dsl as ur_dsl;
+
+    ur_dsl::users_roles
+        .inner_join(rp_dsl::roles_permissions.on(rp_dsl::role_id.eq(ur_dsl::role_id)))
+        .inner_join(p_dsl::permissions.on(p_dsl::id.eq(rp_dsl::permission_id)))
+        .filter(ur_dsl::user_id.eq(user_uuid))
+        .select(p_dsl::name)
+        .distinct()
+        .load::<String>(conn)
+}
+
+pub fn get_user_roles(conn: &mut PgConnection, user_uuid: Uuid) -> QueryResult<Vec<String>> {
+    use crate::schema::roles::dsl as r_dsl;
+    use crate::schema::users_roles::dsl as ur_dsl;
+
+    ur_dsl::users_roles
+        .inner_join(r_dsl::roles.on(r_dsl::id.eq(ur_dsl::role_id)))
+        .filter(ur_dsl::user_id.eq(user_uuid))
+        .select(r_dsl::name)
+        .order(r_dsl::name.asc())
+        .load::<String>(conn)
+}
+
+/// Every user holding `permission` through one of their roles.
+pub fn users_with_permission(conn: &mut PgConnection, permission: &str) -> QueryResult<Vec<Uuid>> {
+    use crate::schema::permissions::dsl as p_dsl;
+    use crate::schema::roles_permissions::dsl as rp_dsl;
+    use crate::schema::users_roles::dsl as ur_dsl;
+
+    ur_dsl::users_roles
+        .inner_join(rp_dsl::roles_permissions.on(rp_dsl::role_id.eq(ur_dsl::role_id)))
+        .inner_join(p_dsl::permissions.on(p_dsl::id.eq(rp_dsl::permission_id)))
+        .filter(p_dsl::name.eq(permission))
+        .select(ur_dsl::user_id)
+        .distinct()
+        .load::<Uuid>(conn)
+}
+
+pub fn has_permission(
+    conn: &mut PgConnection,
+    user_uuid: Uuid,
+    permission: &str,
+) -> QueryResult<bool> {
+    Ok(get_user_permissions(conn, user_uuid)?
+        .iter()
+        .any(|p| p == permission))
+}
+
+pub fn update_user(
+    conn: &mut PgConnection,
+    token: &str,
+    secret: &str,
+    data: UpdateUserRequest,
+) -> Result<User, ApiError> {
+    use crate::schema::users::dsl::*;
+
+    let user = crate::services::authenticate(conn, token, secret)?;
+
+    let mut taken = Vec::new();
+    if let Some(ref new_username) = data.username
+        && username_exists(conn, new_username)?
+    {
+        taken.push(FieldError::new("username", "Username already taken"));
+    }
+    if let Some(ref new_email) = data.email
+        && email_exists(conn, new_email)?
+    {
+        taken.push(FieldError::new("email", "Email already in use"));
+    }
+    if !taken.is_empty() {
+        return Err(ApiError::Validation(taken));
+    }
 
     let changes = crate::models::UpdateUserChangeset {
         username: data.username,
@@ -336,7 +576,7 @@ pub fn update_user(
         last_name: data.last_name,
     };
 
-    let updated_user = diesel::update(users.find(claims.sub))
+    let updated_user = diesel::update(users.find(user.id))
         .set(&changes)
         .get_result::<User>(conn)?;
 
@@ -346,47 +586,40 @@ pub fn update_user(
 pub fn update_password(
     conn: &mut PgConnection,
     token: &str,
-    secret: &str,
     data: UpdatePasswordRequest,
-) -> Result<()> {
+    config: &Config,
+) -> Result<(), ApiError> {
     use crate::schema::users::dsl::*;
 
-    let claims = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(secret.as_ref()),
-        &Validation::default(),
-    )?
-    .claims;
-
-    let (current_token_version, current_password_hash): (i32, String) = users
-        .filter(id.eq(claims.sub))
-        .select((token_version, password_hash))
-        .first(conn)?;
+    let user = crate::services::authenticate(conn, token, &config.auth.jwt_secret)?;
 
-    if current_token_version != claims.token_version {
-        return Err(anyhow::anyhow!("Invalid or expired token"));
+    let verify = |candidate: &str| {
+        hashing::verify_password(candidate, &user.password_hash, &config.hashing)
+            .map_err(|e| anyhow!("Failed to verify password hash: {}", e))
+    };
+    if !verify(&data.old_password)? {
+        return Err(ApiError::Validation(vec![FieldError::new(
+            "old_password",
+            "Invalid old password",
+        )]));
     }
 
-    let parsed_hash = argon2::PasswordHash::new(&current_password_hash)
-        .map_err(|e| anyhow::anyhow!("Failed to parse password hash: {}", e))?;
-    Argon2::default()
-        .verify_password(data.old_password.as_bytes(), &parsed_hash)
-        .map_err(|_| anyhow::anyhow!("Invalid old password"))?;
-
-    if Argon2::default()
-        .verify_password(data.new_password.as_bytes(), &parsed_hash)
-        .is_ok()
-    {
-        return Err(anyhow::anyhow!(
-            "New password cannot be the same as the old password"
-        ));
+    if verify(&data.new_password)? {
+        return Err(ApiError::Validation(vec![FieldError::new(
+            "new_password",
+            "New password cannot be the same as the old password",
+        )]));
     }
 
-    validate_password(&data.new_password)
-        .map_err(|e| anyhow::anyhow!("New password validation failed: {}", e))?;
+    ensure_password_allowed(
+        "new_password",
+        &data.new_password,
+        &[&user.username, &user.email],
+        &config.password,
+    )?;
 
-    let new_hash = hash_password(&data.new_password)
-        .map_err(|_| anyhow::anyhow!("Failed to hash new password"))?;
+    let new_hash = hashing::hash_password(&data.new_password, &config.hashing)
+        .map_err(|e| anyhow!("Failed to hash new password: {}", e))?;
 
     let new_version: i32 = generate_new_token_version();
 
@@ -395,9 +628,132 @@ pub fn update_password(
         token_version: new_version,
     };
 
-    diesel::update(users.find(claims.sub))
+    diesel::update(users.find(user.id))
         .set(&changes)
         .execute(conn)?;
 
     Ok(())
 }
+
+/// Checks a new password against the password policy, breached list
+/// included. `personal` holds the owner's username, email and the like.
+/// Request validation leaves this out: the policy is part of the settings and
+/// the breached list reads from disk.
+pub fn ensure_password_allowed(
+    field: &'static str,
+    candidate: &str,
+    personal: &[&str],
+    policy: &PasswordPolicy,
+) -> Result<(), ApiError> {
+    policy
+        .check(candidate, personal)
+        .and_then(|()| policy.check_breached(candidate))
+        .map_err(|e| ApiError::Validation(vec![FieldError::new(field, e)]))
+}
+
+/// Looks a user up by id, username or email, soft-deleted ones included.
+pub fn find_user(conn: &mut PgConnection, key: &str) -> Result<User, ApiError> {
+    use crate::schema::users::dsl::*;
+
+    let found = match Uuid::parse_str(key) {
+        Ok(uuid) => users.find(uuid).first::<User>(conn).optional()?,
+        Err(_) => users
+            .filter(username.eq(key).or(email.eq(key)))
+            .first::<User>(conn)
+            .optional()?,
+    };
+    found.ok_or(ApiError::NotFound)
+}
+
+/// Replaces the password hash and ends every session of the user.
+pub fn set_password_hash(
+    conn: &mut PgConnection,
+    user_uuid: Uuid,
+    new_hash: String,
+) -> Result<User, ApiError> {
+    use crate::schema::users::dsl::*;
+
+    let changes = crate::models::UpdatePasswordChangeset {
+        password_hash: new_hash,
+        token_version: generate_new_token_version(),
+    };
+    Ok(diesel::update(users.find(user_uuid))
+        .set(&changes)
+        .get_result(conn)?)
+}
+
+/// Invalidates every token issued to the user so far.
+pub fn revoke_sessions(conn: &mut PgConnection, user_uuid: Uuid) -> Result<User, ApiError> {
+    use crate::schema::users::dsl::*;
+
+    Ok(diesel::update(users.find(user_uuid))
+        .set(token_version.eq(generate_new_token_version()))
+        .get_result(conn)?)
+}
+
+/// Locks the user out until `until`, ending their sessions, or unlocks them
+/// when `until` is `None`.
+pub fn set_locked_until(
+    conn: &mut PgConnection,
+    user_uuid: Uuid,
+    until: Option<DateTime<Utc>>,
+) -> Result<User, ApiError> {
+    use crate::schema::users::dsl::*;
+
+    let target = users.find(user_uuid);
+    Ok(match until {
+        Some(_) => diesel::update(target)
+            .set((
+                locked_until.eq(until),
+                token_version.eq(generate_new_token_version()),
+            ))
+            .get_result(conn)?,
+        None => diesel::update(target)
+            .set(locked_until.eq(until))
+            .get_result(conn)?,
+    })
+}
+
+fn role_id_by_name(conn: &mut PgConnection, role: &str) -> Result<i32, ApiError> {
+    use crate::schema::roles::dsl::*;
+
+    roles
+        .filter(name.eq(role))
+        .select(id)
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| ApiError::Invalid(format!("Unknown role {:?}", role)))
+}
+
+/// Gives the user `role`; returns whether they did not have it yet.
+pub fn assign_role(conn: &mut PgConnection, user_uuid: Uuid, role: &str) -> Result<bool, ApiError> {
+    use crate::schema::users_roles::dsl::*;
+
+    let role = role_id_by_name(conn, role)?;
+    let inserted = diesel::insert_into(users_roles)
+        .values((user_id.eq(user_uuid), role_id.eq(role)))
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    Ok(inserted > 0)
+}
+
+/// Takes `role` away from the user; returns whether they had it.
+pub fn remove_role(conn: &mut PgConnection, user_uuid: Uuid, role: &str) -> Result<bool, ApiError> {
+    use crate::schema::users_roles::dsl::*;
+
+    let role = role_id_by_name(conn, role)?;
+    let deleted = diesel::delete(users_roles.filter(user_id.eq(user_uuid).and(role_id.eq(role))))
+        .execute(conn)?;
+    Ok(deleted > 0)
+}
+
+/// Permanently deletes users soft-deleted before `cutoff`, with everything
+/// that belongs to them.
+pub fn purge_deleted_users(
+    conn: &mut PgConnection,
+    cutoff: DateTime<Utc>,
+) -> Result<usize, ApiError> {
+    use crate::schema::users::dsl::*;
+
+    Ok(diesel::delete(users.filter(deleted_at.lt(cutoff))).execute(conn)?)
+}
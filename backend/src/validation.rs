@@ -0,0 +1,199 @@
+//! Declarative validation for request bodies.
+//!
+//! A DTO implements [`Validate`] by normalising its text fields and listing
+//! the rules each field must satisfy. Handlers take it as [`ValidatedJson`],
+//! which rejects the request with every failing field before the handler
+//! runs. Only the first broken rule of a field is reported.
+
+use crate::errors::{ApiError, FieldError};
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpRequest};
+use icu_normalizer::ComposingNormalizerBorrowed;
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+
+pub trait Validate {
+    /// Brings text fields into canonical form before they are validated.
+    fn normalize(&mut self) {}
+
+    fn validate(&self, v: &mut Validator);
+}
+
+#[derive(Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn field<'a>(&'a mut self, name: &'static str, value: &'a str) -> FieldRules<'a> {
+        self.optional(name, Some(value))
+    }
+
+    /// Rules on an absent value always pass.
+    pub fn optional<'a>(
+        &'a mut self,
+        name: &'static str,
+        value: Option<&'a str>,
+    ) -> FieldRules<'a> {
+        FieldRules {
+            validator: self,
+            name,
+            value,
+            failed: false,
+        }
+    }
+
+    /// Records `message` against `name` unless `ok` holds.
+    pub fn check(&mut self, name: &'static str, ok: bool, message: &str) {
+        if !ok {
+            self.errors.push(FieldError::new(name, message));
+        }
+    }
+
+    pub fn finish(self) -> Result<(), ApiError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ApiError::Validation(self.errors))
+        }
+    }
+}
+
+pub struct FieldRules<'a> {
+    validator: &'a mut Validator,
+    name: &'static str,
+    value: Option<&'a str>,
+    failed: bool,
+}
+
+impl FieldRules<'_> {
+    fn rule(self, ok: impl FnOnce(&str) -> bool, message: impl FnOnce() -> String) -> Self {
+        self.custom(|v| if ok(v) { Ok(()) } else { Err(message()) })
+    }
+
+    pub fn not_blank(self) -> Self {
+        self.rule(|v| !v.trim().is_empty(), || "Must not be empty".into())
+    }
+
+    /// Length in characters, matching how `VARCHAR(n)` counts.
+    pub fn length(self, min: usize, max: usize) -> Self {
+        self.rule(
+            |v| (min..=max).contains(&v.chars().count()),
+            || format!("Must be between {} and {} characters long", min, max),
+        )
+    }
+
+    /// ASCII letters, digits, `.`, `_` and `-`.
+    pub fn username(self) -> Self {
+        self.rule(
+            |v| {
+                v.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+            },
+            || "May only contain letters, digits, '.', '_' and '-'".into(),
+        )
+    }
+
+    pub fn email(self) -> Self {
+        self.rule(is_email, || "Must be a valid email address".into())
+    }
+
+    /// Runs a check that explains its own failure.
+    pub fn custom(mut self, check: impl FnOnce(&str) -> Result<(), String>) -> Self {
+        if let Some(value) = self.value
+            && !self.failed
+            && let Err(message) = check(value)
+        {
+            self.validator
+                .errors
+                .push(FieldError::new(self.name, message));
+            self.failed = true;
+        }
+        self
+    }
+}
+
+/// `local@domain` with a dotted domain of letters, digits and hyphens.
+fn is_email(value: &str) -> bool {
+    let Some((local, domain)) = value.rsplit_once('@') else {
+        return false;
+    };
+    let local_ok = !local.is_empty()
+        && local.len() <= 64
+        && !local.starts_with('.')
+        && !local.ends_with('.')
+        && !local.contains("..")
+        && local
+            .chars()
+            .all(|c| c.is_alphanumeric() || "!#$%&'*+/=?^_`{|}~.-".contains(c));
+    let labels: Vec<&str> = domain.split('.').collect();
+    let domain_ok = labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        });
+    local_ok && domain_ok
+}
+
+/// Unicode NFC, so visually identical input compares and stores equally.
+/// Never applied to passwords, which are hashed exactly as typed.
+pub fn nfc(value: &mut String) {
+    let normalized = ComposingNormalizerBorrowed::new_nfc().normalize(value);
+    if normalized != value.as_str() {
+        *value = normalized.into_owned();
+    }
+}
+
+/// NFC plus trimming, for names and identifiers where surrounding
+/// whitespace is never intended.
+pub fn nfc_trim(value: &mut String) {
+    nfc(value);
+    let trimmed = value.trim();
+    if trimmed.len() != value.len() {
+        *value = trimmed.to_string();
+    }
+}
+
+/// Normalises `value` and checks it, reporting every failing field.
+pub fn validate<T: Validate>(value: &mut T) -> Result<(), ApiError> {
+    value.normalize();
+    let mut validator = Validator::default();
+    value.validate(&mut validator);
+    validator.finish()
+}
+
+/// A JSON body that has been normalised and validated.
+pub struct ValidatedJson<T>(pub T);
+
+impl<T> ValidatedJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ValidatedJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidatedJson<T> {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let json = web::Json::<T>::from_request(req, payload);
+        Box::pin(async move {
+            let mut value = json.await?.into_inner();
+            validate(&mut value)?;
+            Ok(ValidatedJson(value))
+        })
+    }
+}
@@ -0,0 +1,192 @@
+use crate::bookings::service::{check_booking_text, check_slot};
+use crate::config::Config;
+use crate::errors::ApiError;
+use crate::users::service::extract_bearer_token;
+use crate::validation::{nfc, nfc_trim, Validate, ValidatedJson, Validator};
+use crate::{services, DbPool};
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+pub mod service;
+
+#[derive(Deserialize)]
+pub struct JoinWaitlistRequest {
+    pub resource_id: Uuid,
+    pub title: String,
+    pub description: Option<String>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    /// Requires `waitlist:prioritize`; higher values are offered first.
+    pub priority: Option<i32>,
+}
+
+impl Validate for JoinWaitlistRequest {
+    fn normalize(&mut self) {
+        nfc_trim(&mut self.title);
+        if let Some(ref mut text) = self.description {
+            nfc(text);
+        }
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        check_booking_text(v, Some(&self.title), self.description.as_deref());
+        check_slot(v, "ends_at", (self.starts_at, self.ends_at));
+    }
+}
+
+#[post("/waitlist")]
+pub async fn join_waitlist_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    body: ValidatedJson<JoinWaitlistRequest>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::join_waitlist(&mut conn, &user, body.into_inner())
+    })
+    .await
+    {
+        Ok(Ok(entry)) => HttpResponse::Created().json(entry),
+        Ok(Err(e)) => e.into_response("Error joining waitlist"),
+        Err(e) => ApiError::from(e).into_response("Error joining waitlist"),
+    }
+}
+
+#[get("/waitlist")]
+pub async fn get_waitlist_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::get_waitlist_entries(&mut conn, &user)
+    })
+    .await
+    {
+        Ok(Ok(entries)) => HttpResponse::Ok().json(entries),
+        Ok(Err(e)) => e.into_response("Error fetching waitlist"),
+        Err(e) => ApiError::from(e).into_response("Error fetching waitlist"),
+    }
+}
+
+#[post("/waitlist/{id}/accept")]
+pub async fn accept_waitlist_offer_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let entry_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::accept_offer(&mut conn, &user, entry_id)
+    })
+    .await
+    {
+        Ok(Ok((entry, booking))) => HttpResponse::Ok().json(serde_json::json!({
+            "entry": entry,
+            "booking": booking,
+        })),
+        Ok(Err(e)) => e.into_response("Error accepting waitlist offer"),
+        Err(e) => ApiError::from(e).into_response("Error accepting waitlist offer"),
+    }
+}
+
+#[post("/waitlist/{id}/decline")]
+pub async fn decline_waitlist_offer_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let entry_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::decline_offer(&mut conn, &user, entry_id, &config.bookings)
+    })
+    .await
+    {
+        Ok(Ok(entry)) => HttpResponse::Ok().json(entry),
+        Ok(Err(e)) => e.into_response("Error declining waitlist offer"),
+        Err(e) => ApiError::from(e).into_response("Error declining waitlist offer"),
+    }
+}
+
+#[delete("/waitlist/{id}")]
+pub async fn leave_waitlist_endpoint(
+    pool: web::Data<DbPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    let token = match extract_bearer_token(&req) {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let mut conn = match services::get_conn(&pool) {
+        Ok(c) => c,
+        Err(err) => return err,
+    };
+    let secret = config.auth.jwt_secret.clone();
+    let entry_id = path.into_inner();
+
+    match services::block(move || {
+        let user = services::authenticate(&mut conn, &token, &secret)?;
+        service::leave_waitlist(&mut conn, &user, entry_id, &config.bookings)
+    })
+    .await
+    {
+        Ok(Ok(entry)) => HttpResponse::Ok().json(entry),
+        Ok(Err(e)) => e.into_response("Error leaving waitlist"),
+        Err(e) => ApiError::from(e).into_response("Error leaving waitlist"),
+    }
+}
@@ -0,0 +1,347 @@
+use crate::booking_rules::service::enforce_rules;
+use crate::bookings::approval::{request_approval, requires_approval, withdraw_approvals};
+use crate::bookings::service::{find_conflicts, lock_resource};
+use crate::config::BookingsConfig;
+use crate::errors::ApiError;
+use crate::metrics;
+use crate::models::{
+    Booking, BookingStatus, NewBooking, NewWaitlistEntry, User, WaitlistEntry, WaitlistStatus,
+};
+use crate::notifications::service::notify;
+use crate::users::service::has_permission;
+use crate::waitlist::JoinWaitlistRequest;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+/// How long a promoted user has to confirm before the offer moves on.
+pub fn hold_period(config: &BookingsConfig) -> Duration {
+    Duration::minutes(config.waitlist_hold_minutes)
+}
+
+fn entry_payload(entry: &WaitlistEntry) -> serde_json::Value {
+    serde_json::json!({
+        "waitlist_entry_id": entry.id,
+        "resource_id": entry.resource_id,
+        "title": entry.title,
+        "starts_at": entry.starts_at,
+        "ends_at": entry.ends_at,
+        "booking_id": entry.booking_id,
+        "offer_expires_at": entry.offer_expires_at,
+    })
+}
+
+fn set_status(
+    conn: &mut PgConnection,
+    entry: &WaitlistEntry,
+    new_status: WaitlistStatus,
+    kind: &str,
+) -> QueryResult<WaitlistEntry> {
+    use crate::schema::waitlist_entries::dsl::*;
+
+    let entry = diesel::update(waitlist_entries.find(entry.id))
+        .set(status.eq(new_status))
+        .get_result::<WaitlistEntry>(conn)?;
+    notify(conn, entry.user_id, kind, entry_payload(&entry))?;
+    Ok(entry)
+}
+
+/// Cancels the pending booking that holds the slot for an open offer.
+fn release_hold(conn: &mut PgConnection, entry: &WaitlistEntry) -> QueryResult<()> {
+    use crate::schema::bookings::dsl::*;
+
+    if let Some(held) = entry.booking_id {
+        diesel::update(bookings.find(held))
+            .filter(status.eq(BookingStatus::Pending))
+            .set(status.eq(BookingStatus::Cancelled))
+            .execute(conn)?;
+        withdraw_approvals(conn, &[held], None)?;
+    }
+    Ok(())
+}
+
+pub fn join_waitlist(
+    conn: &mut PgConnection,
+    user: &User,
+    data: JoinWaitlistRequest,
+) -> Result<WaitlistEntry, ApiError> {
+    use crate::schema::waitlist_entries::dsl::*;
+
+    let slot = (data.starts_at, data.ends_at);
+
+    if data.starts_at <= Utc::now() {
+        return Err(ApiError::Invalid(
+            "Cannot wait for a slot in the past".into(),
+        ));
+    }
+
+    let requested_priority = data.priority.unwrap_or(0);
+    if requested_priority != 0 && !has_permission(conn, user.id, "waitlist:prioritize")? {
+        return Err(ApiError::Forbidden);
+    }
+
+    conn.transaction(|conn| {
+        lock_resource(conn, data.resource_id)?;
+        enforce_rules(conn, user, user.id, data.resource_id, &[slot], &[])?;
+
+        if find_conflicts(conn, data.resource_id, &[slot], &[])?.is_empty() {
+            return Err(ApiError::Invalid(
+                "The slot is available and can be booked directly".into(),
+            ));
+        }
+
+        let already_waiting = waitlist_entries
+            .filter(user_id.eq(user.id))
+            .filter(resource_id.eq(data.resource_id))
+            .filter(starts_at.eq(data.starts_at))
+            .filter(ends_at.eq(data.ends_at))
+            .filter(status.eq_any([WaitlistStatus::Waiting, WaitlistStatus::Offered]))
+            .select(id)
+            .first::<Uuid>(conn)
+            .optional()?
+            .is_some();
+        if already_waiting {
+            return Err(ApiError::Invalid(
+                "You are already on the waitlist for this slot".into(),
+            ));
+        }
+
+        let entry = diesel::insert_into(waitlist_entries)
+            .values(&NewWaitlistEntry {
+                user_id: user.id,
+                resource_id: data.resource_id,
+                title: data.title,
+                description: data.description,
+                starts_at: data.starts_at,
+                ends_at: data.ends_at,
+                priority: requested_priority,
+            })
+            .get_result::<WaitlistEntry>(conn)?;
+        notify(conn, user.id, "waitlist.joined", entry_payload(&entry))?;
+
+        Ok(entry)
+    })
+}
+
+pub fn get_waitlist_entries(
+    conn: &mut PgConnection,
+    user: &User,
+) -> Result<Vec<WaitlistEntry>, ApiError> {
+    use crate::schema::waitlist_entries::dsl::*;
+
+    Ok(waitlist_entries
+        .filter(user_id.eq(user.id))
+        .order(created_at.desc())
+        .load::<WaitlistEntry>(conn)?)
+}
+
+fn lock_own_entry(
+    conn: &mut PgConnection,
+    user: &User,
+    entry_uuid: Uuid,
+) -> Result<WaitlistEntry, ApiError> {
+    use crate::schema::waitlist_entries::dsl::*;
+
+    waitlist_entries
+        .find(entry_uuid)
+        .filter(user_id.eq(user.id))
+        .for_update()
+        .first::<WaitlistEntry>(conn)
+        .optional()?
+        .ok_or(ApiError::NotFound)
+}
+
+/// Confirms an open offer, turning the held booking into a confirmed one.
+pub fn accept_offer(
+    conn: &mut PgConnection,
+    user: &User,
+    entry_uuid: Uuid,
+) -> Result<(WaitlistEntry, Booking), ApiError> {
+    use crate::schema::bookings::dsl::*;
+
+    conn.transaction(|conn| {
+        let entry = lock_own_entry(conn, user, entry_uuid)?;
+        if entry.status != WaitlistStatus::Offered {
+            return Err(ApiError::Invalid(
+                "There is no open offer for this waitlist entry".into(),
+            ));
+        }
+        if entry.offer_expires_at.is_some_and(|at| at <= Utc::now()) {
+            return Err(ApiError::Invalid("The offer has expired".into()));
+        }
+
+        let held = entry
+            .booking_id
+            .ok_or_else(|| ApiError::Invalid("The held booking no longer exists".into()))?;
+        let needs_approval = requires_approval(conn, Some(entry.resource_id))?;
+        let booking = diesel::update(bookings.find(held))
+            .filter(status.eq(BookingStatus::Pending))
+            .filter(deleted_at.is_null())
+            .set(status.eq(if needs_approval {
+                BookingStatus::Pending
+            } else {
+                BookingStatus::Confirmed
+            }))
+            .get_result::<Booking>(conn)
+            .optional()?
+            .ok_or_else(|| ApiError::Invalid("The held booking no longer exists".into()))?;
+        if needs_approval {
+            request_approval(conn, std::slice::from_ref(&booking))?;
+        }
+
+        let entry = set_status(conn, &entry, WaitlistStatus::Accepted, "waitlist.accepted")?;
+        Ok((entry, booking))
+    })
+}
+
+pub fn decline_offer(
+    conn: &mut PgConnection,
+    user: &User,
+    entry_uuid: Uuid,
+    config: &BookingsConfig,
+) -> Result<WaitlistEntry, ApiError> {
+    conn.transaction(|conn| {
+        let entry = lock_own_entry(conn, user, entry_uuid)?;
+        if entry.status != WaitlistStatus::Offered {
+            return Err(ApiError::Invalid(
+                "There is no open offer for this waitlist entry".into(),
+            ));
+        }
+
+        release_hold(conn, &entry)?;
+        let entry = set_status(conn, &entry, WaitlistStatus::Declined, "waitlist.declined")?;
+        promote_waitlist(conn, entry.resource_id, config)?;
+        Ok(entry)
+    })
+}
+
+/// Leaves the waitlist. Leaving with an open offer releases the held slot to
+/// the next person in line.
+pub fn leave_waitlist(
+    conn: &mut PgConnection,
+    user: &User,
+    entry_uuid: Uuid,
+    config: &BookingsConfig,
+) -> Result<WaitlistEntry, ApiError> {
+    conn.transaction(|conn| {
+        let entry = lock_own_entry(conn, user, entry_uuid)?;
+        match entry.status {
+            WaitlistStatus::Waiting => Ok(set_status(
+                conn,
+                &entry,
+                WaitlistStatus::Cancelled,
+                "waitlist.left",
+            )?),
+            WaitlistStatus::Offered => {
+                release_hold(conn, &entry)?;
+                let entry = set_status(conn, &entry, WaitlistStatus::Cancelled, "waitlist.left")?;
+                promote_waitlist(conn, entry.resource_id, config)?;
+                Ok(entry)
+            }
+            _ => Err(ApiError::Invalid(
+                "This waitlist entry is no longer active".into(),
+            )),
+        }
+    })
+}
+
+/// Offers freed time on the resource to whoever is waiting for it, highest
+/// priority first and then in join order. Each offer is backed by a pending
+/// booking so the slot stays held until the user confirms or the hold
+/// expires. Must run inside the transaction that freed the time.
+pub fn promote_waitlist(
+    conn: &mut PgConnection,
+    resource_uuid: Uuid,
+    config: &BookingsConfig,
+) -> Result<(), ApiError> {
+    use crate::schema::bookings::dsl as b_dsl;
+    use crate::schema::waitlist_entries::dsl::*;
+
+    lock_resource(conn, resource_uuid)?;
+
+    let now = Utc::now();
+    let waiting = waitlist_entries
+        .filter(resource_id.eq(resource_uuid))
+        .filter(status.eq(WaitlistStatus::Waiting))
+        .order((priority.desc(), created_at.asc()))
+        .load::<WaitlistEntry>(conn)?;
+
+    for entry in waiting {
+        if entry.starts_at <= now {
+            set_status(conn, &entry, WaitlistStatus::Expired, "waitlist.expired")?;
+            continue;
+        }
+
+        let slot = (entry.starts_at, entry.ends_at);
+        if !find_conflicts(conn, resource_uuid, &[slot], &[])?.is_empty() {
+            continue;
+        }
+
+        let held = diesel::insert_into(b_dsl::bookings)
+            .values(&NewBooking {
+                user_id: entry.user_id,
+                resource_id: Some(resource_uuid),
+                title: entry.title.clone(),
+                description: entry.description.clone(),
+                booking_date: entry.starts_at,
+                end_date: entry.ends_at,
+                status: BookingStatus::Pending,
+                series_id: None,
+                recurrence_id: None,
+                hold_expires_at: None,
+            })
+            .get_result::<Booking>(conn)?;
+        metrics::bookings_created(std::slice::from_ref(&held));
+
+        let expires = (now + hold_period(config)).min(entry.starts_at);
+        let entry = diesel::update(waitlist_entries.find(entry.id))
+            .set((
+                status.eq(WaitlistStatus::Offered),
+                booking_id.eq(Some(held.id)),
+                offer_expires_at.eq(Some(expires)),
+            ))
+            .get_result::<WaitlistEntry>(conn)?;
+        notify(
+            conn,
+            entry.user_id,
+            "waitlist.offered",
+            entry_payload(&entry),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Moves every offer whose hold has run out on to the next person in line.
+pub fn expire_offers(conn: &mut PgConnection, config: &BookingsConfig) -> Result<usize, ApiError> {
+    use crate::schema::waitlist_entries::dsl::*;
+
+    let due = waitlist_entries
+        .filter(status.eq(WaitlistStatus::Offered))
+        .filter(offer_expires_at.le(Utc::now()))
+        .select(id)
+        .load::<Uuid>(conn)?;
+
+    let mut expired = 0;
+    for entry_uuid in due {
+        conn.transaction(|conn| {
+            let Some(entry) = waitlist_entries
+                .find(entry_uuid)
+                .filter(status.eq(WaitlistStatus::Offered))
+                .for_update()
+                .first::<WaitlistEntry>(conn)
+                .optional()?
+            else {
+                return Ok::<_, ApiError>(());
+            };
+
+            release_hold(conn, &entry)?;
+            set_status(conn, &entry, WaitlistStatus::Expired, "waitlist.expired")?;
+            promote_waitlist(conn, entry.resource_id, config)?;
+            expired += 1;
+            Ok(())
+        })?;
+    }
+
+    Ok(expired)
+}